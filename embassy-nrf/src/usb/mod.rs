@@ -14,7 +14,9 @@ use cortex_m::peripheral::NVIC;
 use embassy_hal_internal::{into_ref, PeripheralRef};
 use embassy_sync::waitqueue::AtomicWaker;
 use embassy_usb_driver as driver;
-use embassy_usb_driver::{Direction, EndpointAddress, EndpointError, EndpointInfo, EndpointType, Event, Unsupported};
+use embassy_usb_driver::{
+    Direction, EndpointAddress, EndpointError, EndpointInfo, EndpointType, Event, RemoteWakeupError,
+};
 
 use self::vbus_detect::VbusDetect;
 use crate::interrupt::typelevel::Interrupt;
@@ -360,7 +362,7 @@ impl<'d, T: Instance, V: VbusDetect> driver::Bus for Bus<'d, T, V> {
     }
 
     #[inline]
-    async fn remote_wakeup(&mut self) -> Result<(), Unsupported> {
+    async fn remote_wakeup(&mut self) -> Result<(), RemoteWakeupError> {
         let regs = T::regs();
 
         if regs.lowpower().read().lowpower() == vals::Lowpower::LOW_POWER {
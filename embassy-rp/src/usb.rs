@@ -8,7 +8,7 @@ use core::task::Poll;
 use embassy_sync::waitqueue::AtomicWaker;
 use embassy_usb_driver as driver;
 use embassy_usb_driver::{
-    Direction, EndpointAddress, EndpointAllocError, EndpointError, EndpointInfo, EndpointType, Event, Unsupported,
+    Direction, EndpointAddress, EndpointAllocError, EndpointError, EndpointInfo, EndpointType, Event, RemoteWakeupError,
 };
 
 use crate::interrupt::typelevel::{Binding, Interrupt};
@@ -486,8 +486,8 @@ impl<'d, T: Instance> driver::Bus for Bus<'d, T> {
 
     async fn disable(&mut self) {}
 
-    async fn remote_wakeup(&mut self) -> Result<(), Unsupported> {
-        Err(Unsupported)
+    async fn remote_wakeup(&mut self) -> Result<(), RemoteWakeupError> {
+        Err(RemoteWakeupError::Unsupported)
     }
 }
 
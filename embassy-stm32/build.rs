@@ -214,6 +214,8 @@ fn main() {
         Some("tim22") => "TIM22",
         Some("tim23") => "TIM23",
         Some("tim24") => "TIM24",
+        Some("lptim1") => "LPTIM1",
+        Some("lptim2") => "LPTIM2",
         Some("any") => {
             // Order of TIM candidators:
             // 1. 2CH -> 2CH_CMP -> GP16 -> GP32 -> ADV
@@ -234,12 +236,16 @@ fn main() {
     if !time_driver_singleton.is_empty() {
         cfgs.enable(format!("time_driver_{}", time_driver_singleton.to_lowercase()));
     }
+    if time_driver_singleton.starts_with("LPTIM") {
+        cfgs.enable("time_driver_lptim");
+    }
     for tim in [
         "tim1", "tim2", "tim3", "tim4", "tim5", "tim8", "tim9", "tim12", "tim15", "tim20", "tim21", "tim22", "tim23",
-        "tim24",
+        "tim24", "lptim1", "lptim2",
     ] {
         cfgs.declare(format!("time_driver_{}", tim));
     }
+    cfgs.declare("time_driver_lptim");
 
     // ========
     // Write singletons
@@ -812,6 +818,15 @@ fn main() {
         (("rcc", "MCO_1"), quote!(crate::rcc::McoPin)),
         (("rcc", "MCO_2"), quote!(crate::rcc::McoPin)),
         (("rcc", "MCO"), quote!(crate::rcc::McoPin)),
+        (("dfsdm", "CKOUT"), quote!(crate::dfsdm::CkOutPin)),
+        (("dfsdm", "DATIN0"), quote!(crate::dfsdm::DatinPin<Ch0>)),
+        (("dfsdm", "DATIN1"), quote!(crate::dfsdm::DatinPin<Ch1>)),
+        (("dfsdm", "DATIN2"), quote!(crate::dfsdm::DatinPin<Ch2>)),
+        (("dfsdm", "DATIN3"), quote!(crate::dfsdm::DatinPin<Ch3>)),
+        (("dfsdm", "DATIN4"), quote!(crate::dfsdm::DatinPin<Ch4>)),
+        (("dfsdm", "DATIN5"), quote!(crate::dfsdm::DatinPin<Ch5>)),
+        (("dfsdm", "DATIN6"), quote!(crate::dfsdm::DatinPin<Ch6>)),
+        (("dfsdm", "DATIN7"), quote!(crate::dfsdm::DatinPin<Ch7>)),
         (("dcmi", "D0"), quote!(crate::dcmi::D0Pin)),
         (("dcmi", "D1"), quote!(crate::dcmi::D1Pin)),
         (("dcmi", "D2"), quote!(crate::dcmi::D2Pin)),
@@ -892,6 +907,7 @@ fn main() {
         (("eth", "TXD2"), quote!(crate::eth::TXD2Pin)),
         (("eth", "TXD3"), quote!(crate::eth::TXD3Pin)),
         (("eth", "TX_EN"), quote!(crate::eth::TXEnPin)),
+        (("eth", "PPS_OUT"), quote!(crate::eth::PPSOutPin)),
         (("fmc", "A0"), quote!(crate::fmc::A0Pin)),
         (("fmc", "A1"), quote!(crate::fmc::A1Pin)),
         (("fmc", "A2"), quote!(crate::fmc::A2Pin)),
@@ -1246,6 +1262,10 @@ fn main() {
         (("spi", "TX"), quote!(crate::spi::TxDma)),
         (("i2c", "RX"), quote!(crate::i2c::RxDma)),
         (("i2c", "TX"), quote!(crate::i2c::TxDma)),
+        (("dfsdm", "FLT0"), quote!(crate::dfsdm::FilterDma<Flt0>)),
+        (("dfsdm", "FLT1"), quote!(crate::dfsdm::FilterDma<Flt1>)),
+        (("dfsdm", "FLT2"), quote!(crate::dfsdm::FilterDma<Flt2>)),
+        (("dfsdm", "FLT3"), quote!(crate::dfsdm::FilterDma<Flt3>)),
         (("dcmi", "DCMI"), quote!(crate::dcmi::FrameDma)),
         (("dcmi", "PSSI"), quote!(crate::dcmi::FrameDma)),
         // SDMMCv1 uses the same channel for both directions, so just implement for RX
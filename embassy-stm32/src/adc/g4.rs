@@ -10,6 +10,14 @@ use super::{blocking_delay_us, Adc, AdcChannel, Instance, Resolution, SampleTime
 use crate::time::Hertz;
 use crate::{pac, rcc, Peripheral};
 
+#[cfg(stm32g4)]
+mod triggered_g4;
+#[cfg(stm32g4)]
+pub use triggered_g4::{
+    DualAdc, DualMaster, DualMode, InjectedSequence, InjectedTriggerSource, RegularSequence, RegularTriggerSource,
+    Trigger, TriggerEdge,
+};
+
 /// Default VREF voltage used for sample conversion to millivolts.
 pub const VREF_DEFAULT_MV: u32 = 3300;
 /// VREF voltage used for factory calibration of VREFINTCAL register.
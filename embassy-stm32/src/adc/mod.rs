@@ -74,19 +74,8 @@ pub(crate) trait SealedAdcChannel<T> {
     fn channel(&self) -> u8;
 }
 
-/// Performs a busy-wait delay for a specified number of microseconds.
 #[allow(unused)]
-pub(crate) fn blocking_delay_us(us: u32) {
-    #[cfg(feature = "time")]
-    embassy_time::block_for(embassy_time::Duration::from_micros(us as u64));
-    #[cfg(not(feature = "time"))]
-    {
-        let freq = unsafe { crate::rcc::get_freqs() }.sys.to_hertz().unwrap().0 as u64;
-        let us = us as u64;
-        let cycles = freq * us / 1_000_000;
-        cortex_m::asm::delay(cycles as u32);
-    }
-}
+pub(crate) use crate::blocking_delay_us;
 
 /// ADC instance.
 #[cfg(not(any(
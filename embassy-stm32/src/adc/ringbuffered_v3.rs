@@ -0,0 +1,225 @@
+use core::marker::PhantomData;
+use core::mem;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use embassy_hal_internal::{into_ref, Peripheral};
+use pac::adc::vals::Dmacfg;
+
+use crate::adc::{Adc, AnyAdcChannel, Instance, RxDma, SampleTime};
+use crate::dma::{Priority, ReadableRingBuffer, TransferOptions};
+use crate::{pac, rcc};
+
+/// ADC or DMA overrun while streaming measurements.
+///
+/// Measurements were lost because the ring buffer was not read fast enough.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OverrunError;
+
+/// ADC streaming measurements into a circular DMA buffer.
+///
+/// Created with [`Adc::read_continuous`].
+pub struct RingBufferedAdc<'d, T: Instance> {
+    _phantom: PhantomData<T>,
+    ring_buf: ReadableRingBuffer<'d, u16>,
+}
+
+impl<'d, T: Instance> Adc<'d, T> {
+    /// Configures the ADC for continuous conversions of `sequence` into a circular DMA buffer.
+    ///
+    /// The `dma_buf` should be large enough to prevent DMA buffer overrun, and its length should
+    /// be an even multiple of the sequence length so that each half-buffer holds whole sequences.
+    /// For example, if 3 channels are measured, its length can be 3 * 40 = 120 measurements.
+    ///
+    /// Conversions are started on the first call to [`RingBufferedAdc::read`], which then yields
+    /// every completed half of `dma_buf`.
+    ///
+    /// Example
+    /// ```rust,ignore
+    /// use embassy_stm32::adc::{Adc, AdcChannel}
+    ///
+    /// let adc = Adc::new(p.ADC1);
+    /// let mut adc_pin0 = p.PA0.degrade_adc();
+    /// let mut adc_pin1 = p.PA1.degrade_adc();
+    /// let mut dma_buf = [0u16; 128];
+    ///
+    /// let mut adc = adc.read_continuous(
+    ///     p.DMA1_CH1,
+    ///     &mut dma_buf,
+    ///     [
+    ///         (&mut adc_pin0, SampleTime::CYCLES47_5),
+    ///         (&mut adc_pin1, SampleTime::CYCLES47_5),
+    ///     ]
+    ///     .into_iter(),
+    /// );
+    ///
+    /// let mut measurements = [0u16; 64];
+    /// loop {
+    ///     match adc.read(&mut measurements).await {
+    ///         Ok(_) => defmt::info!("measurements: {}", measurements),
+    ///         // Next call to `read` restarts the ADC.
+    ///         Err(e) => defmt::warn!("Error: {:?}", e),
+    ///     }
+    /// }
+    /// ```
+    pub fn read_continuous(
+        mut self,
+        dma: impl Peripheral<P = impl RxDma<T>> + 'd,
+        dma_buf: &'d mut [u16],
+        sequence: impl ExactSizeIterator<Item = (&mut AnyAdcChannel<T>, SampleTime)>,
+    ) -> RingBufferedAdc<'d, T> {
+        assert!(!dma_buf.is_empty() && dma_buf.len() <= 0xFFFF);
+        assert!(sequence.len() != 0, "Continuous read sequence cannot be empty");
+        assert!(
+            sequence.len() <= 16,
+            "Continuous read sequence cannot be more than 16 in length"
+        );
+        into_ref!(dma);
+
+        // Ensure no conversions are ongoing and ADC is enabled.
+        Self::cancel_conversions();
+        self.enable();
+
+        Self::configure_sequence(sequence);
+
+        let opts = TransferOptions {
+            half_transfer_ir: true,
+            priority: Priority::VeryHigh,
+            ..Default::default()
+        };
+
+        // Safety: we forget the struct before this function returns.
+        let rx_src = T::regs().dr().as_ptr() as *mut u16;
+        let request = dma.request();
+
+        let ring_buf = unsafe { ReadableRingBuffer::new(dma, request, rx_src, dma_buf, opts) };
+
+        // Don't disable the clock
+        mem::forget(self);
+
+        RingBufferedAdc {
+            _phantom: PhantomData,
+            ring_buf,
+        }
+    }
+}
+
+impl<'d, T: Instance> RingBufferedAdc<'d, T> {
+    /// Starts continuous conversions and the circular DMA transfer.
+    pub fn start(&mut self) {
+        compiler_fence(Ordering::SeqCst);
+
+        self.ring_buf.start();
+
+        let r = T::regs();
+
+        // Clear overrun flag before starting transfer.
+        r.isr().modify(|reg| {
+            reg.set_ovr(true);
+        });
+
+        #[cfg(not(any(adc_g0, adc_u0)))]
+        r.cfgr().modify(|reg| {
+            reg.set_discen(false);
+            reg.set_cont(true);
+            reg.set_dmacfg(Dmacfg::CIRCULAR);
+            reg.set_dmaen(true);
+        });
+        #[cfg(any(adc_g0, adc_u0))]
+        r.cfgr1().modify(|reg| {
+            reg.set_discen(false);
+            reg.set_cont(true);
+            reg.set_dmacfg(Dmacfg::CIRCULAR);
+            reg.set_dmaen(true);
+        });
+
+        // Start conversion
+        r.cr().modify(|reg| {
+            reg.set_adstart(true);
+        });
+    }
+
+    /// Stops conversions and the DMA transfer.
+    ///
+    /// The ADC stays enabled. Calling [`start`](Self::start) or [`read`](Self::read) restarts
+    /// the stream.
+    pub fn stop(&mut self) {
+        // Stop the DMA transfer
+        self.ring_buf.request_pause();
+
+        Adc::<T>::cancel_conversions();
+
+        let r = T::regs();
+
+        #[cfg(not(any(adc_g0, adc_u0)))]
+        r.cfgr().modify(|reg| {
+            reg.set_cont(false);
+            reg.set_dmaen(false);
+        });
+        #[cfg(any(adc_g0, adc_u0))]
+        r.cfgr1().modify(|reg| {
+            reg.set_cont(false);
+            reg.set_dmaen(false);
+        });
+
+        r.isr().modify(|reg| {
+            reg.set_ovr(true);
+        });
+
+        compiler_fence(Ordering::SeqCst);
+    }
+
+    fn is_running() -> bool {
+        #[cfg(not(any(adc_g0, adc_u0)))]
+        return T::regs().cfgr().read().dmaen();
+        #[cfg(any(adc_g0, adc_u0))]
+        return T::regs().cfgr1().read().dmaen();
+    }
+
+    fn overrun(&mut self) -> Result<usize, OverrunError> {
+        self.stop();
+        Err(OverrunError)
+    }
+
+    /// Waits for the next completed half of the DMA buffer and copies it into `measurements`.
+    ///
+    /// The length of `measurements` must be exactly half of the DMA buffer length, since the DMA
+    /// only signals half and full transfer completion. Measurements are interleaved in sequence
+    /// order, e.g. `[sq0 sq1 sq2 sq0 sq1 sq2 ..]` when 3 channels are sampled.
+    ///
+    /// Returns the number of measurements still available in the ring buffer. If an overrun of
+    /// the ADC data register or of the DMA buffer is detected, the stream is stopped and
+    /// [`OverrunError`] is returned; the next call to `read` restarts it.
+    pub async fn read<const N: usize>(&mut self, measurements: &mut [u16; N]) -> Result<usize, OverrunError> {
+        assert_eq!(
+            self.ring_buf.capacity() / 2,
+            N,
+            "Buffer size must be half the size of the ring buffer"
+        );
+
+        // Start background receive if it was not already started
+        if !Self::is_running() {
+            self.start();
+        }
+
+        if T::regs().isr().read().ovr() {
+            return self.overrun();
+        }
+
+        match self.ring_buf.read_exact(measurements).await {
+            Ok(_) if T::regs().isr().read().ovr() => self.overrun(),
+            Ok(len) => Ok(len),
+            Err(_) => self.overrun(),
+        }
+    }
+}
+
+impl<T: Instance> Drop for RingBufferedAdc<'_, T> {
+    fn drop(&mut self) {
+        self.stop();
+
+        T::regs().cr().modify(|reg| reg.set_addis(true));
+
+        rcc::disable::<T>();
+    }
+}
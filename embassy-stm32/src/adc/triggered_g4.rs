@@ -0,0 +1,440 @@
+use pac::adc::vals::Exten;
+pub use pac::adccommon::vals::Dual as DualMode;
+
+use crate::adc::{Adc, AdcChannel, Instance, SampleTime};
+use crate::{pac, peripherals};
+
+/// Edge of an external trigger that starts a conversion sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TriggerEdge {
+    /// Rising edge.
+    Rising,
+    /// Falling edge.
+    Falling,
+    /// Both edges.
+    Both,
+}
+
+impl From<TriggerEdge> for Exten {
+    fn from(edge: TriggerEdge) -> Self {
+        match edge {
+            TriggerEdge::Rising => Exten::RISINGEDGE,
+            TriggerEdge::Falling => Exten::FALLINGEDGE,
+            TriggerEdge::Both => Exten::BOTHEDGES,
+        }
+    }
+}
+
+/// Trigger event of the regular sequence (`EXTSEL`).
+///
+/// Only the timer trigger outputs are listed: their encoding is the same on all ADC instances.
+/// Configure the timer side with [`Timer::set_master_mode`]. `TIM20` is only present on some
+/// devices.
+///
+/// [`Timer::set_master_mode`]: crate::timer::low_level::Timer::set_master_mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+#[allow(missing_docs)]
+pub enum RegularTriggerSource {
+    Tim3Trgo = 4,
+    Tim8Trgo = 7,
+    Tim8Trgo2 = 8,
+    Tim1Trgo = 9,
+    Tim1Trgo2 = 10,
+    Tim2Trgo = 11,
+    Tim4Trgo = 12,
+    Tim6Trgo = 13,
+    Tim15Trgo = 14,
+    Tim20Trgo = 16,
+    Tim20Trgo2 = 17,
+    LptimOut = 29,
+    Tim7Trgo = 30,
+}
+
+/// Trigger event of the injected sequence (`JEXTSEL`).
+///
+/// Only the timer trigger outputs are listed: their encoding is the same on all ADC instances.
+/// Configure the timer side with [`Timer::set_master_mode`]. `TIM20` is only present on some
+/// devices.
+///
+/// [`Timer::set_master_mode`]: crate::timer::low_level::Timer::set_master_mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+#[allow(missing_docs)]
+pub enum InjectedTriggerSource {
+    Tim1Trgo = 0,
+    Tim2Trgo = 2,
+    Tim4Trgo = 5,
+    Tim1Trgo2 = 8,
+    Tim8Trgo = 9,
+    Tim8Trgo2 = 10,
+    Tim3Trgo = 12,
+    Tim6Trgo = 14,
+    Tim15Trgo = 15,
+    Tim20Trgo = 16,
+    Tim20Trgo2 = 17,
+    LptimOut = 29,
+    Tim7Trgo = 30,
+}
+
+/// External trigger of a conversion sequence.
+///
+/// `S` is [`RegularTriggerSource`] or [`InjectedTriggerSource`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Trigger<S> {
+    /// Trigger event.
+    pub source: S,
+    /// Edge of the trigger event.
+    pub edge: TriggerEdge,
+}
+
+/// Builder for the regular conversion sequence, created by [`Adc::regular_sequence`].
+pub struct RegularSequence<'a, 'd, T: Instance> {
+    _adc: &'a mut Adc<'d, T>,
+    len: usize,
+    trigger: Option<Trigger<RegularTriggerSource>>,
+}
+
+impl<'a, 'd, T: Instance> RegularSequence<'a, 'd, T> {
+    /// Append a channel to the sequence.
+    ///
+    /// Panics if the sequence already contains 16 channels.
+    pub fn channel(mut self, channel: &mut impl AdcChannel<T>, sample_time: SampleTime) -> Self {
+        assert!(self.len < 16, "Regular sequence cannot be more than 16 in length");
+
+        channel.setup();
+        Adc::<T>::set_channel_sample_time(channel.channel(), sample_time);
+
+        let i = self.len;
+        let ch = channel.channel();
+        match i {
+            0..=3 => T::regs().sqr1().modify(|w| w.set_sq(i, ch)),
+            4..=8 => T::regs().sqr2().modify(|w| w.set_sq(i - 4, ch)),
+            9..=13 => T::regs().sqr3().modify(|w| w.set_sq(i - 9, ch)),
+            _ => T::regs().sqr4().modify(|w| w.set_sq(i - 14, ch)),
+        }
+        self.len += 1;
+        self
+    }
+
+    /// Start the sequence on an external trigger instead of software.
+    pub fn trigger(mut self, trigger: Trigger<RegularTriggerSource>) -> Self {
+        self.trigger = Some(trigger);
+        self
+    }
+
+    /// Apply the configuration.
+    ///
+    /// With a trigger set, the ADC is armed and converts the sequence on each trigger event.
+    /// Results are read with [`Adc::blocking_read_sequence`].
+    pub fn apply(self) {
+        assert!(self.len != 0, "Regular sequence cannot be empty");
+
+        let r = T::regs();
+        r.sqr1().modify(|w| w.set_l(self.len as u8 - 1));
+        r.cfgr().modify(|w| {
+            w.set_cont(false);
+            match self.trigger {
+                Some(trigger) => {
+                    w.set_extsel(trigger.source as u8);
+                    w.set_exten(trigger.edge.into());
+                }
+                None => w.set_exten(Exten::DISABLED),
+            }
+        });
+
+        if self.trigger.is_some() {
+            r.isr().modify(|w| {
+                w.set_eoc(true);
+                w.set_eos(true);
+                w.set_ovr(true);
+            });
+            r.cr().modify(|w| w.set_adstart(true));
+        }
+    }
+}
+
+/// Builder for the injected conversion sequence, created by [`Adc::injected_sequence`].
+pub struct InjectedSequence<'a, 'd, T: Instance> {
+    _adc: &'a mut Adc<'d, T>,
+    channels: [u8; 4],
+    len: usize,
+    trigger: Option<Trigger<InjectedTriggerSource>>,
+}
+
+impl<'a, 'd, T: Instance> InjectedSequence<'a, 'd, T> {
+    /// Append a channel to the sequence.
+    ///
+    /// Panics if the sequence already contains 4 channels.
+    pub fn channel(mut self, channel: &mut impl AdcChannel<T>, sample_time: SampleTime) -> Self {
+        assert!(self.len < 4, "Injected sequence cannot be more than 4 in length");
+
+        channel.setup();
+        Adc::<T>::set_channel_sample_time(channel.channel(), sample_time);
+
+        self.channels[self.len] = channel.channel();
+        self.len += 1;
+        self
+    }
+
+    /// Start the sequence on an external trigger instead of software.
+    pub fn trigger(mut self, trigger: Trigger<InjectedTriggerSource>) -> Self {
+        self.trigger = Some(trigger);
+        self
+    }
+
+    /// Apply the configuration and arm the injected sequence.
+    ///
+    /// Without a trigger the sequence is converted once immediately. Results are read with
+    /// [`Adc::blocking_read_injected`].
+    pub fn apply(self) {
+        assert!(self.len != 0, "Injected sequence cannot be empty");
+
+        let r = T::regs();
+
+        Adc::<T>::stop_injected();
+
+        r.isr().modify(|w| {
+            w.set_jeoc(true);
+            w.set_jeos(true);
+        });
+
+        // JSQR must be written in one go, the hardware latches it as a whole.
+        r.jsqr().write(|w| {
+            w.set_jl(self.len as u8 - 1);
+            for (i, ch) in self.channels[..self.len].iter().enumerate() {
+                w.set_jsq(i, *ch);
+            }
+            match self.trigger {
+                Some(trigger) => {
+                    w.set_jextsel(trigger.source as u8);
+                    w.set_jexten(trigger.edge.into());
+                }
+                None => w.set_jexten(Exten::DISABLED),
+            }
+        });
+
+        r.cr().modify(|w| w.set_jadstart(true));
+    }
+}
+
+impl<'d, T: Instance> Adc<'d, T> {
+    /// Configure the regular sequence and its trigger.
+    ///
+    /// Example
+    /// ```rust,ignore
+    /// use embassy_stm32::adc::{Adc, RegularTriggerSource, Trigger, TriggerEdge};
+    /// use embassy_stm32::timer::low_level::MasterMode;
+    ///
+    /// tim.set_master_mode(MasterMode::UPDATE);
+    ///
+    /// adc.regular_sequence()
+    ///     .channel(&mut p.PA0, SampleTime::CYCLES24_5)
+    ///     .channel(&mut p.PA1, SampleTime::CYCLES24_5)
+    ///     .trigger(Trigger {
+    ///         source: RegularTriggerSource::Tim2Trgo,
+    ///         edge: TriggerEdge::Rising,
+    ///     })
+    ///     .apply();
+    ///
+    /// let mut readings = [0u16; 2];
+    /// loop {
+    ///     adc.blocking_read_sequence(&mut readings);
+    /// }
+    /// ```
+    pub fn regular_sequence(&mut self) -> RegularSequence<'_, 'd, T> {
+        // The sequence registers must not be written while conversions are ongoing.
+        Self::stop_regular();
+
+        RegularSequence {
+            _adc: self,
+            len: 0,
+            trigger: None,
+        }
+    }
+
+    /// Configure the injected sequence and its trigger.
+    ///
+    /// Injected conversions preempt the regular sequence and store their results in dedicated
+    /// data registers, so they are suited for sampling at a precise point of a PWM period.
+    pub fn injected_sequence(&mut self) -> InjectedSequence<'_, 'd, T> {
+        InjectedSequence {
+            _adc: self,
+            channels: [0; 4],
+            len: 0,
+            trigger: None,
+        }
+    }
+
+    /// Wait for the next conversion of the configured regular sequence and read its results.
+    ///
+    /// `readings` must have the length of the sequence. Without a trigger the conversion is
+    /// started by software.
+    pub fn blocking_read_sequence(&mut self, readings: &mut [u16]) {
+        let r = T::regs();
+        assert_eq!(
+            readings.len(),
+            r.sqr1().read().l() as usize + 1,
+            "Readings length must be equal to sequence length"
+        );
+
+        if r.cfgr().read().exten() == Exten::DISABLED {
+            r.isr().modify(|w| {
+                w.set_eoc(true);
+                w.set_eos(true);
+            });
+            r.cr().modify(|w| w.set_adstart(true));
+        }
+
+        for reading in readings.iter_mut() {
+            while !r.isr().read().eoc() {}
+            // Reading DR clears EOC.
+            *reading = r.dr().read().0 as u16;
+        }
+
+        r.isr().modify(|w| w.set_eos(true));
+    }
+
+    /// Returns true if the injected sequence has finished converting.
+    pub fn injected_ready(&self) -> bool {
+        T::regs().isr().read().jeos()
+    }
+
+    /// Wait for the injected sequence to finish and read its results.
+    ///
+    /// Reads as many results as `readings` is long, up to 4. Triggered sequences stay armed for
+    /// the next trigger event.
+    pub fn blocking_read_injected(&mut self, readings: &mut [u16]) {
+        assert!(readings.len() <= 4, "Injected sequence cannot be more than 4 in length");
+
+        let r = T::regs();
+        while !r.isr().read().jeos() {}
+        r.isr().modify(|w| {
+            w.set_jeoc(true);
+            w.set_jeos(true);
+        });
+
+        for (i, reading) in readings.iter_mut().enumerate() {
+            *reading = r.jdr(i).read().jdata();
+        }
+
+        // Software-started sequences must be restarted for every conversion.
+        if r.jsqr().read().jexten() == Exten::DISABLED {
+            r.cr().modify(|w| w.set_jadstart(true));
+        }
+    }
+
+    fn stop_regular() {
+        let r = T::regs();
+        if r.cr().read().adstart() {
+            r.cr().modify(|w| w.set_adstp(true));
+            while r.cr().read().adstart() {}
+        }
+    }
+
+    fn stop_injected() {
+        let r = T::regs();
+        if r.cr().read().jadstart() {
+            r.cr().modify(|w| w.set_jadstp(true));
+            while r.cr().read().jadstart() {}
+        }
+    }
+
+    /// Stop all conversions and disable the ADC, e.g. to write registers that require ADEN=0.
+    fn disable() {
+        Self::stop_regular();
+        Self::stop_injected();
+
+        let r = T::regs();
+        if r.cr().read().aden() {
+            r.cr().modify(|w| w.set_addis(true));
+            while r.cr().read().aden() {}
+        }
+    }
+}
+
+/// ADC instance that can act as master of a dual ADC pair.
+pub trait DualMaster: Instance {
+    /// Slave ADC instance converting in lockstep with this one.
+    type Slave: Instance;
+}
+
+#[cfg(all(peri_adc1, peri_adc2))]
+impl DualMaster for peripherals::ADC1 {
+    type Slave = peripherals::ADC2;
+}
+
+#[cfg(all(peri_adc3, peri_adc4))]
+impl DualMaster for peripherals::ADC3 {
+    type Slave = peripherals::ADC4;
+}
+
+/// Master and slave ADC converting in dual mode.
+///
+/// Configure the sequences of both ADCs through [`master`](Self::master) and
+/// [`slave`](Self::slave); only the master's trigger is used, the slave starts with it.
+///
+/// The dual mode can only be changed while both ADCs are disabled, so creating, reconfiguring
+/// or splitting a `DualAdc` stops any ongoing conversion and disarms triggered sequences. Apply
+/// the sequences again afterwards.
+pub struct DualAdc<'d, M: DualMaster> {
+    master: Adc<'d, M>,
+    slave: Adc<'d, M::Slave>,
+}
+
+impl<'d, M: DualMaster> DualAdc<'d, M> {
+    /// Combine two ADCs into a dual ADC running in `mode`.
+    pub fn new(master: Adc<'d, M>, slave: Adc<'d, M::Slave>, mode: DualMode) -> Self {
+        let mut this = Self { master, slave };
+        this.set_mode(mode);
+        this
+    }
+
+    /// Change the dual mode.
+    pub fn set_mode(&mut self, mode: DualMode) {
+        Adc::<M>::disable();
+        Adc::<M::Slave>::disable();
+
+        M::common_regs().ccr().modify(|w| w.set_dual(mode));
+
+        self.master.enable();
+        self.slave.enable();
+    }
+
+    /// Access the master ADC.
+    pub fn master(&mut self) -> &mut Adc<'d, M> {
+        &mut self.master
+    }
+
+    /// Access the slave ADC.
+    pub fn slave(&mut self) -> &mut Adc<'d, M::Slave> {
+        &mut self.slave
+    }
+
+    /// Wait for the next simultaneous regular conversion and read `(master, slave)` results.
+    ///
+    /// Both regular sequences must have a single channel.
+    pub fn blocking_read(&mut self) -> (u16, u16) {
+        let r = M::regs();
+        if r.cfgr().read().exten() == Exten::DISABLED {
+            r.cr().modify(|w| w.set_adstart(true));
+        }
+
+        while !r.isr().read().eoc() || !M::Slave::regs().isr().read().eoc() {}
+
+        // Reading the common data register clears EOC of both ADCs.
+        let cdr = M::common_regs().cdr().read();
+        (cdr.rdata_mst(), cdr.rdata_slv())
+    }
+
+    /// Split back into independent ADCs.
+    pub fn split(mut self) -> (Adc<'d, M>, Adc<'d, M::Slave>) {
+        self.set_mode(DualMode::INDEPENDENT);
+
+        (self.master, self.slave)
+    }
+}
@@ -17,7 +17,7 @@ use self::registers::{Registers, RxFifo};
 pub use super::common::{BufferedCanReceiver, BufferedCanSender};
 use super::frame::{Envelope, Frame};
 use super::util;
-use crate::can::enums::{BusError, TryReadError};
+use crate::can::enums::{BusError, BusErrorMode, BusOffRecovery, TryReadError};
 use crate::gpio::{AfType, OutputType, Pull, Speed};
 use crate::interrupt::typelevel::Interrupt;
 use crate::rcc::{self, RccPeripheral};
@@ -144,6 +144,18 @@ impl CanConfig<'_> {
         self.info.regs.set_automatic_retransmit(enabled);
         self
     }
+
+    /// Sets the bus-off recovery policy.
+    ///
+    /// With [`BusOffRecovery::Manual`], use [`Can::recover_from_bus_off`] to rejoin the bus.
+    ///
+    /// Automatic recovery is enabled by default.
+    pub fn set_bus_off_recovery(self, recovery: BusOffRecovery) -> Self {
+        self.info
+            .regs
+            .set_automatic_bus_off_recovery(recovery == BusOffRecovery::Automatic);
+        self
+    }
 }
 
 impl Drop for CanConfig<'_> {
@@ -207,6 +219,8 @@ impl<'d> Can<'d> {
                 // Enable timestamps on rx messages
 
                 w.set_ttcm(true);
+                // Automatic bus-off recovery by default
+                w.set_abom(true);
             });
         }
 
@@ -310,6 +324,74 @@ impl<'d> Can<'d> {
         self.info.regs.0.ier().modify(|i| i.set_slkie(false));
     }
 
+    /// Get the CAN RX error counter
+    pub fn rx_error_count(&self) -> u8 {
+        self.info.regs.0.esr().read().rec()
+    }
+
+    /// Get the CAN TX error counter
+    pub fn tx_error_count(&self) -> u8 {
+        self.info.regs.0.esr().read().tec()
+    }
+
+    /// Get the current bus error mode
+    pub fn bus_error_mode(&self) -> BusErrorMode {
+        let esr = self.info.regs.0.esr().read();
+        match (esr.boff(), esr.epvf()) {
+            (false, false) => BusErrorMode::ErrorActive,
+            (false, true) => BusErrorMode::ErrorPassive,
+            (true, _) => BusErrorMode::BusOff,
+        }
+    }
+
+    /// Get the last protocol error code
+    pub fn last_error_code(&self) -> Option<BusError> {
+        self.info.regs.last_error_code()
+    }
+
+    /// Waits until the controller enters bus-off state.
+    ///
+    /// Errors are signalled through the shared error interrupt. A pending error that hasn't been
+    /// reported by [`read`](Self::read) or [`try_read`](Self::try_read) yet is acknowledged here
+    /// so the interrupt can fire again, and is then no longer reported by them.
+    pub async fn wait_for_bus_off(&self) {
+        let regs = self.info.regs.0;
+        poll_fn(|cx| {
+            self.state.err_waker.register(cx.waker());
+            if regs.esr().read().boff() {
+                return Poll::Ready(());
+            }
+
+            // ERRI only rises again once acknowledged, and the interrupt handler masks ERRIE.
+            if regs.msr().read().erri() {
+                regs.msr().modify(|m| m.set_erri(true));
+            }
+            regs.ier().modify(|i| {
+                i.set_bofie(true);
+                i.set_errie(true);
+            });
+
+            // The controller may have gone bus-off before the interrupt was armed.
+            if regs.esr().read().boff() {
+                return Poll::Ready(());
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Starts the bus-off recovery sequence and re-enables the peripheral.
+    ///
+    /// The controller rejoins the bus after monitoring 128 occurrences of 11 consecutive
+    /// recessive bits. This is only needed with [`BusOffRecovery::Manual`].
+    pub async fn recover_from_bus_off(&mut self) {
+        if self.info.regs.0.esr().read().boff() {
+            self.info.regs.enter_init_mode();
+            self.info.regs.leave_init_mode();
+            self.enable().await;
+        }
+    }
+
     /// Enable FIFO scheduling of outgoing frames.
     ///
     /// If this is enabled, frames will be transmitted in the order that they are passed to
@@ -70,6 +70,14 @@ impl Registers {
         self.0.mcr().modify(|reg| reg.set_nart(enabled));
     }
 
+    /// Enables or disables automatic bus-off recovery.
+    ///
+    /// If this is disabled, the software must request the recovery by entering and leaving
+    /// initialization mode.
+    pub fn set_automatic_bus_off_recovery(&self, enabled: bool) {
+        self.0.mcr().modify(|reg| reg.set_abom(enabled));
+    }
+
     /// Enables or disables loopback mode: Internally connects the TX and RX
     /// signals together.
     pub fn set_loopback(&self, enabled: bool) {
@@ -100,7 +108,6 @@ impl Registers {
         let msr = self.0.msr().read();
         if msr.slak() {
             self.0.mcr().modify(|reg| {
-                reg.set_abom(true);
                 reg.set_sleep(false);
             });
             Err(nb::Error::WouldBlock)
@@ -166,19 +173,26 @@ impl Registers {
             return Some(BusError::BusPassive);
         } else if err.ewgf() {
             return Some(BusError::BusWarning);
-        } else if err.lec() != Lec::NOERROR {
-            return Some(match err.lec() {
-                Lec::STUFF => BusError::Stuff,
-                Lec::FORM => BusError::Form,
-                Lec::ACK => BusError::Acknowledge,
-                Lec::BITRECESSIVE => BusError::BitRecessive,
-                Lec::BITDOMINANT => BusError::BitDominant,
-                Lec::CRC => BusError::Crc,
-                Lec::CUSTOM => BusError::Software,
-                Lec::NOERROR => unreachable!(),
-            });
         }
-        None
+        Self::lec_to_error(err.lec())
+    }
+
+    fn lec_to_error(lec: Lec) -> Option<BusError> {
+        match lec {
+            Lec::NOERROR => None,
+            Lec::STUFF => Some(BusError::Stuff),
+            Lec::FORM => Some(BusError::Form),
+            Lec::ACK => Some(BusError::Acknowledge),
+            Lec::BITRECESSIVE => Some(BusError::BitRecessive),
+            Lec::BITDOMINANT => Some(BusError::BitDominant),
+            Lec::CRC => Some(BusError::Crc),
+            Lec::CUSTOM => Some(BusError::Software),
+        }
+    }
+
+    /// Returns the last protocol error code, without acknowledging the error.
+    pub fn last_error_code(&self) -> Option<BusError> {
+        Self::lec_to_error(self.0.esr().read().lec())
     }
 
     /// Enables or disables FIFO scheduling of outgoing mailboxes.
@@ -47,6 +47,21 @@ pub enum BusErrorMode {
     BusOff,
 }
 
+/// Bus-off recovery policy.
+///
+/// After too many transmit errors the controller enters bus-off state and stops participating in
+/// bus traffic. It may rejoin the bus after monitoring 128 occurrences of 11 consecutive recessive
+/// bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BusOffRecovery {
+    /// Recovery is started by the driver as soon as bus-off state is entered (default).
+    Automatic,
+    /// Recovery is only started when requested by the application, e.g. after it has inspected
+    /// the error state or waited for a back-off period.
+    Manual,
+}
+
 /// Frame Create Errors
 #[derive(Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -3,6 +3,8 @@
 
 use core::num::{NonZeroU16, NonZeroU8};
 
+use crate::can::enums::BusOffRecovery;
+
 /// Configures the bit timings.
 ///
 /// You can use <http://www.bittiming.can-wiki.info/> to calculate the `btr` parameter. Enter
@@ -356,6 +358,8 @@ pub struct FdCanConfig {
     pub global_filter: GlobalFilter,
     /// TX buffer mode (FIFO or priority queue)
     pub tx_buffer_mode: TxBufferMode,
+    /// Bus-off recovery policy
+    pub bus_off_recovery: BusOffRecovery,
 }
 
 impl FdCanConfig {
@@ -452,6 +456,13 @@ impl FdCanConfig {
         self.tx_buffer_mode = txbm;
         self
     }
+
+    /// Sets the bus-off recovery policy
+    #[inline]
+    pub const fn set_bus_off_recovery(mut self, recovery: BusOffRecovery) -> Self {
+        self.bus_off_recovery = recovery;
+        self
+    }
 }
 
 impl Default for FdCanConfig {
@@ -470,6 +481,7 @@ impl Default for FdCanConfig {
             timestamp_source: TimestampSource::None,
             global_filter: GlobalFilter::default(),
             tx_buffer_mode: TxBufferMode::Priority,
+            bus_off_recovery: BusOffRecovery::Automatic,
         }
     }
 }
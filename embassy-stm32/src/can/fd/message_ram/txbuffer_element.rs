@@ -284,7 +284,7 @@ impl<'a> MM_W<'a> {
     #[doc = r"Writes raw bits to the field"]
     #[inline(always)]
     pub unsafe fn bits(self, value: u8) -> &'a mut W {
-        self.w.bits[1] = (self.w.bits[1] & !(0x7F << 24)) | (((value as u32) & 0x7F) << 24);
+        self.w.bits[1] = (self.w.bits[1] & !(0xFF << 24)) | (((value as u32) & 0xFF) << 24);
         self.w
     }
 
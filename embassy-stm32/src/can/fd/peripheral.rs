@@ -8,7 +8,7 @@ use cfg_if::cfg_if;
 use crate::can::enums::*;
 use crate::can::fd::config::*;
 use crate::can::fd::message_ram::enums::*;
-use crate::can::fd::message_ram::{RegisterBlock, RxFifoElement, TxBufferElement};
+use crate::can::fd::message_ram::{RegisterBlock, RxFifoElement, TxBufferElement, TX_FIFO_MAX};
 use crate::can::frame::*;
 
 /// Loopback Mode
@@ -71,10 +71,10 @@ impl Registers {
         }
     }
 
-    pub fn put_tx_frame(&self, bufidx: usize, header: &Header, buffer: &[u8]) {
+    pub fn put_tx_frame(&self, bufidx: usize, header: &Header, buffer: &[u8], marker: Option<u8>) {
         let mailbox = self.tx_buffer_element(bufidx);
         mailbox.reset();
-        put_tx_header(mailbox, header);
+        put_tx_header(mailbox, header, marker.into());
         put_tx_data(mailbox, &buffer[..header.len() as usize]);
 
         // Set <idx as Mailbox> as ready to transmit
@@ -117,6 +117,19 @@ impl Registers {
         }
         None
     }
+
+    /// Returns the last protocol error code. Reading it resets the code in hardware.
+    pub fn last_error_code(&self) -> Option<BusError> {
+        let err = { self.regs.psr().read() };
+        cfg_if! {
+            if #[cfg(can_fdcan_h7)] {
+                let lec = err.lec();
+            } else {
+                let lec = err.lec().to_bits();
+            }
+        }
+        Self::reg_to_error(lec)
+    }
     /// Returns if the tx queue is able to accept new messages without having to cancel an existing one
     #[inline]
     pub fn tx_queue_is_full(&self) -> bool {
@@ -212,35 +225,80 @@ impl Registers {
     }
 
     pub fn write<F: embedded_can::Frame + CanHeader>(&self, frame: &F) -> nb::Result<Option<F>, Infallible> {
+        self.write_with_marker(frame, None)
+            .map(|(_, pending_frame)| pending_frame)
+    }
+
+    /// Returns the mailbox holding the lowest priority pending frame, if that frame has a lower
+    /// priority than the identifier `id`.
+    fn lowest_priority_mailbox(&self, id: &embedded_can::Id) -> Option<usize> {
+        let mut lowest: Option<(usize, embedded_can::Id)> = None;
+        for bufidx in 0..TX_FIFO_MAX as usize {
+            if !self.has_pending_frame(bufidx) {
+                continue;
+            }
+            let header_reg = self.tx_buffer_element(bufidx).header.read();
+            let pending_id = make_id(header_reg.id().bits(), header_reg.xtd().bits());
+            // A higher ID means a lower priority. Only evict frames the new one outranks, and pick
+            // the lowest priority of them.
+            if pending_id > *id && lowest.map_or(true, |(_, lowest_id)| pending_id > lowest_id) {
+                lowest = Some((bufidx, pending_id));
+            }
+        }
+        lowest.map(|(bufidx, _)| bufidx)
+    }
+
+    /// Queues a frame, storing a TX event with message marker `marker` once it is sent.
+    ///
+    /// Returns the mailbox the frame was put in, and the frame that was removed from it, if any.
+    /// When the queue is full, the lowest priority pending frame is aborted to make room, so
+    /// higher priority frames are never stuck behind lower priority ones.
+    pub fn write_with_marker<F: embedded_can::Frame + CanHeader>(
+        &self,
+        frame: &F,
+        marker: Option<u8>,
+    ) -> nb::Result<(usize, Option<F>), Infallible> {
         let (idx, pending_frame) = if self.tx_queue_is_full() {
             if self.tx_queue_mode() == TxBufferMode::Fifo {
                 // Does not make sense to cancel a pending frame when using FIFO
                 return Err(nb::Error::WouldBlock);
             }
-            // If the queue is full,
-            // Discard the first slot with a lower priority message
-            let id = frame.header().id();
-            if self.is_available(0, id) {
-                (0, self.abort_pending_mailbox(0))
-            } else if self.is_available(1, id) {
-                (1, self.abort_pending_mailbox(1))
-            } else if self.is_available(2, id) {
-                (2, self.abort_pending_mailbox(2))
-            } else {
-                // For now we bail when there is no lower priority slot available
-                // Can this lead to priority inversion?
-                return Err(nb::Error::WouldBlock);
+            // If the queue is full, discard the lowest priority message, as long as it has a
+            // lower priority than the new one.
+            match self.lowest_priority_mailbox(frame.header().id()) {
+                Some(idx) => (idx, self.abort_pending_mailbox(idx)),
+                None => return Err(nb::Error::WouldBlock),
             }
         } else {
             // Read the Write Pointer
             let idx = self.regs.txfqs().read().tfqpi();
 
-            (idx, None)
+            (idx as usize, None)
         };
 
-        self.put_tx_frame(idx as usize, frame.header(), frame.data());
+        self.put_tx_frame(idx, frame.header(), frame.data(), marker);
+
+        Ok((idx, pending_frame))
+    }
+
+    /// Pops the oldest element of the TX event FIFO.
+    ///
+    /// Returns the identifier, message marker and timestamp of the sent frame.
+    pub fn read_tx_event(&self) -> Option<(embedded_can::Id, u8, u16)> {
+        let txefs = self.regs.txefs().read();
+        if txefs.effl() < 1 {
+            return None;
+        }
+
+        let get_idx = txefs.efgi();
+        let event = self.msg_ram_mut().transmit.efsa[get_idx as usize].read();
+        let id = make_id(event.id().bits(), event.xtd().bits());
+        let result = (id, event.mm().bits(), event.txts().bits());
+
+        // Acknowledge the element, freeing it for the next event
+        self.regs.txefa().modify(|w| w.set_efai(get_idx));
 
-        Ok(pending_frame)
+        Some(result)
     }
 
     #[inline]
@@ -368,6 +426,7 @@ impl Registers {
             w.set_rfne(0, true); // Rx Fifo 0 New Msg
             w.set_rfne(1, true); // Rx Fifo 1 New Msg
             w.set_tce(true); //  Tx Complete
+            w.set_tefne(true); // Tx Event Fifo New Entry
             w.set_boe(true); // Bus-Off Status Changed
         });
         self.regs.ile().modify(|w| {
@@ -650,7 +709,7 @@ fn make_id(id: u32, extended: bool) -> embedded_can::Id {
     }
 }
 
-fn put_tx_header(mailbox: &mut TxBufferElement, header: &Header) {
+fn put_tx_header(mailbox: &mut TxBufferElement, header: &Header, event: Event) {
     let (id, id_type) = match header.id() {
         // A standard identifier has to be written to ID[28:18].
         embedded_can::Id::Standard(id) => ((id.as_raw() as u32) << 18, IdType::StandardId),
@@ -672,7 +731,7 @@ fn put_tx_header(mailbox: &mut TxBufferElement, header: &Header) {
             .xtd()
             .set_id_type(id_type)
             .set_len(DataLength::new(header.len(), frame_format))
-            .set_event(Event::NoEvent)
+            .set_event(event)
             .fdf()
             .set_format(frame_format)
             .brs()
@@ -33,6 +33,31 @@ pub type Timestamp = embassy_time::Instant;
 #[cfg(not(feature = "time"))]
 pub type Timestamp = u16;
 
+/// Transmit confirmation read from the TX event FIFO.
+#[derive(Debug, Clone)]
+pub struct TxEvent {
+    /// Transmission time.
+    pub ts: Timestamp,
+    /// Identifier of the sent frame.
+    pub id: embedded_can::Id,
+    /// Message marker the frame was queued with.
+    pub marker: u8,
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for TxEvent {
+    fn format(&self, fmt: defmt::Formatter<'_>) {
+        match self.id {
+            embedded_can::Id::Standard(id) => {
+                defmt::write!(fmt, "TxEvent Standard ID={:x} marker={}", id.as_raw(), self.marker)
+            }
+            embedded_can::Id::Extended(id) => {
+                defmt::write!(fmt, "TxEvent Extended ID={:x} marker={}", id.as_raw(), self.marker)
+            }
+        }
+    }
+}
+
 /// Interrupt handler channel 0.
 pub struct IT0InterruptHandler<T: Instance> {
     _phantom: PhantomData<T>,
@@ -86,8 +111,11 @@ impl<T: Instance> interrupt::typelevel::Handler<T::IT0Interrupt> for IT0Interrup
         if ir.bo() {
             regs.ir().write(|w| w.set_bo(true));
             if regs.psr().read().bo() {
-                // Initiate bus-off recovery sequence by resetting CCCR.INIT
-                regs.cccr().modify(|w| w.set_init(false));
+                if T::state().bus_off_recovery == BusOffRecovery::Automatic {
+                    // Initiate bus-off recovery sequence by resetting CCCR.INIT
+                    regs.cccr().modify(|w| w.set_init(false));
+                }
+                T::state().err_waker.wake();
             }
         }
     }
@@ -264,6 +292,7 @@ impl<'d> CanConfigurator<'d> {
             unsafe {
                 let mut_state = state as *mut State;
                 (*mut_state).ns_per_timer_tick = ns_per_timer_tick;
+                (*mut_state).bus_off_recovery = self.config.bus_off_recovery;
             }
         });
         self.info.regs.into_mode(self.config, mode);
@@ -353,6 +382,71 @@ impl<'d> Can<'d> {
         self.state.rx_mode.read_fd(self.info, self.state).await
     }
 
+    /// Queues the message to be sent, recording a [`TxEvent`] with `marker` once it is sent.
+    ///
+    /// Behaves like [`write`](Self::write), but also returns the index of the TX mailbox the
+    /// frame was put in, which can be passed to [`abort`](Self::abort) or [`flush`](Self::flush).
+    pub async fn write_with_marker(&mut self, frame: &Frame, marker: u8) -> (usize, Option<Frame>) {
+        self.state.tx_mode.write_with_marker(self.info, frame, marker).await
+    }
+
+    /// Queues the message to be sent, recording a [`TxEvent`] with `marker` once it is sent.
+    ///
+    /// Behaves like [`write_fd`](Self::write_fd), but also returns the index of the TX mailbox
+    /// the frame was put in, which can be passed to [`abort`](Self::abort) or [`flush`](Self::flush).
+    pub async fn write_fd_with_marker(&mut self, frame: &FdFrame, marker: u8) -> (usize, Option<FdFrame>) {
+        self.state.tx_mode.write_with_marker(self.info, frame, marker).await
+    }
+
+    /// Cancels the transmission of the frame pending in TX mailbox `idx`.
+    ///
+    /// Returns `true` if the frame was removed before being sent, `false` if the mailbox was
+    /// empty or the frame was already (being) transmitted.
+    pub fn abort(&mut self, idx: usize) -> bool {
+        self.info.regs.abort(idx)
+    }
+
+    /// Returns the next entry of the TX event FIFO, if there is one.
+    pub fn try_read_tx_event(&mut self) -> Option<TxEvent> {
+        self.state.tx_mode.try_read_tx_event(self.info, self.state)
+    }
+
+    /// Waits for the next entry of the TX event FIFO.
+    ///
+    /// Events are only recorded for frames queued with [`write_with_marker`](Self::write_with_marker)
+    /// or [`write_fd_with_marker`](Self::write_fd_with_marker).
+    pub async fn read_tx_event(&mut self) -> TxEvent {
+        self.state.tx_mode.read_tx_event(self.info, self.state).await
+    }
+
+    /// Waits until the controller enters bus-off state.
+    ///
+    /// With [`BusOffRecovery::Manual`], the controller then stays off the bus until
+    /// [`recover_from_bus_off`](Self::recover_from_bus_off) is called.
+    pub async fn wait_for_bus_off(&self) {
+        poll_fn(|cx| {
+            self.state.err_waker.register(cx.waker());
+
+            if self.info.regs.regs.psr().read().bo() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Starts the bus-off recovery sequence.
+    ///
+    /// The controller rejoins the bus after monitoring 128 occurrences of 11 consecutive
+    /// recessive bits. This is only needed with [`BusOffRecovery::Manual`].
+    pub fn recover_from_bus_off(&mut self) {
+        let regs = self.info.regs.regs;
+        if regs.psr().read().bo() {
+            regs.cccr().modify(|w| w.set_init(false));
+        }
+    }
+
     /// Split instance into separate portions: Tx(write), Rx(read), common properties
     pub fn split(self) -> (CanTx<'d>, CanRx<'d>, Properties) {
         (
@@ -678,6 +772,37 @@ impl<'c, 'd> CanTx<'d> {
     pub async fn write_fd(&mut self, frame: &FdFrame) -> Option<FdFrame> {
         self.state.tx_mode.write_fd(self.info, frame).await
     }
+
+    /// Queues the message to be sent, recording a [`TxEvent`] with `marker` once it is sent.
+    ///
+    /// See [`Can::write_with_marker`].
+    pub async fn write_with_marker(&mut self, frame: &Frame, marker: u8) -> (usize, Option<Frame>) {
+        self.state.tx_mode.write_with_marker(self.info, frame, marker).await
+    }
+
+    /// Queues the message to be sent, recording a [`TxEvent`] with `marker` once it is sent.
+    ///
+    /// See [`Can::write_fd_with_marker`].
+    pub async fn write_fd_with_marker(&mut self, frame: &FdFrame, marker: u8) -> (usize, Option<FdFrame>) {
+        self.state.tx_mode.write_with_marker(self.info, frame, marker).await
+    }
+
+    /// Cancels the transmission of the frame pending in TX mailbox `idx`.
+    ///
+    /// See [`Can::abort`].
+    pub fn abort(&mut self, idx: usize) -> bool {
+        self.info.regs.abort(idx)
+    }
+
+    /// Returns the next entry of the TX event FIFO, if there is one.
+    pub fn try_read_tx_event(&mut self) -> Option<TxEvent> {
+        self.state.tx_mode.try_read_tx_event(self.info, self.state)
+    }
+
+    /// Waits for the next entry of the TX event FIFO.
+    pub async fn read_tx_event(&mut self) -> TxEvent {
+        self.state.tx_mode.read_tx_event(self.info, self.state).await
+    }
 }
 
 enum RxMode {
@@ -849,6 +974,46 @@ impl TxMode {
     async fn write_fd(&self, info: &'static Info, frame: &FdFrame) -> Option<FdFrame> {
         self.write_generic::<_>(info, frame).await
     }
+
+    /// Queues the message to be sent with a TX event marker, exerting the same backpressure
+    /// as [`write_generic`](Self::write_generic).
+    async fn write_with_marker<F: embedded_can::Frame + CanHeader>(
+        &self,
+        info: &'static Info,
+        frame: &F,
+        marker: u8,
+    ) -> (usize, Option<F>) {
+        poll_fn(|cx| {
+            self.register(cx.waker());
+
+            if let Ok(queued) = info.regs.write_with_marker(frame, Some(marker)) {
+                return Poll::Ready(queued);
+            }
+
+            Poll::Pending
+        })
+        .await
+    }
+
+    fn try_read_tx_event(&self, info: &'static Info, state: &'static State) -> Option<TxEvent> {
+        info.regs.read_tx_event().map(|(id, marker, ts)| TxEvent {
+            ts: info.calc_timestamp(state.ns_per_timer_tick, ts),
+            id,
+            marker,
+        })
+    }
+
+    async fn read_tx_event(&self, info: &'static Info, state: &'static State) -> TxEvent {
+        poll_fn(|cx| {
+            self.register(cx.waker());
+
+            match self.try_read_tx_event(info, state) {
+                Some(event) => Poll::Ready(event),
+                None => Poll::Pending,
+            }
+        })
+        .await
+    }
 }
 
 /// Common driver properties, including filters and error counters
@@ -904,6 +1069,13 @@ impl Properties {
         self.info.regs.regs.ecr().read().tec()
     }
 
+    /// Get the last protocol error code, if an error occurred since the last call
+    ///
+    /// Reading the error code clears it, which also affects the errors reported by `read`.
+    pub fn last_error_code(&self) -> Option<BusError> {
+        self.info.regs.last_error_code()
+    }
+
     /// Get the current bus error mode
     pub fn bus_error_mode(&self) -> BusErrorMode {
         // This read will clear LEC and DLEC. This is not ideal, but protocol
@@ -922,6 +1094,7 @@ struct State {
     pub rx_mode: RxMode,
     pub tx_mode: TxMode,
     pub ns_per_timer_tick: u64,
+    pub bus_off_recovery: BusOffRecovery,
 
     pub err_waker: AtomicWaker,
 }
@@ -932,6 +1105,7 @@ impl State {
             rx_mode: RxMode::NonBuffered(AtomicWaker::new()),
             tx_mode: TxMode::NonBuffered(AtomicWaker::new()),
             ns_per_timer_tick: 0,
+            bus_off_recovery: BusOffRecovery::Automatic,
             err_waker: AtomicWaker::new(),
         }
     }
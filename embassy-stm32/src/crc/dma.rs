@@ -0,0 +1,72 @@
+use embassy_hal_internal::{into_ref, PeripheralRef};
+
+use super::Crc;
+use crate::dma::word::Word;
+use crate::dma::{AnyChannel, Channel, Transfer};
+use crate::pac::CRC as PAC_CRC;
+use crate::Peripheral;
+
+/// CRC driver fed by DMA, created with [`Crc::with_dma`].
+///
+/// Data is written to the CRC unit with memory-to-memory DMA transfers, so the CPU is free while
+/// large blocks, such as a firmware image, are checksummed. The data must be readable by the DMA
+/// controller, and on chips with a data cache it must be cleaned first.
+pub struct DmaCrc<'d> {
+    crc: Crc<'d>,
+    dma: PeripheralRef<'d, AnyChannel>,
+}
+
+impl<'d> Crc<'d> {
+    /// Feed data to the CRC unit with DMA on `dma`.
+    ///
+    /// On chips with the stream DMA (`DMA1`/`DMA2` with FIFOs) and no DMAMUX, only `DMA2` can do
+    /// memory-to-memory transfers, so the channel must belong to it.
+    pub fn with_dma(self, dma: impl Peripheral<P = impl Channel> + 'd) -> DmaCrc<'d> {
+        into_ref!(dma);
+        let dma: PeripheralRef<'d, AnyChannel> = dma.map_into();
+
+        #[cfg(all(dma, not(dmamux)))]
+        assert!(
+            matches!(dma.info().dma, crate::dma::DmaInfo::Dma(r) if r.as_ptr() == crate::pac::DMA2.as_ptr()),
+            "the CRC can only be fed by a DMA2 channel"
+        );
+
+        DmaCrc { crc: self, dma }
+    }
+}
+
+impl<'d> DmaCrc<'d> {
+    /// Reset the CRC unit to its initial value.
+    pub fn reset(&mut self) {
+        self.crc.reset();
+    }
+
+    /// Feed a slice of words to the CRC unit and return the result.
+    pub async fn feed_words(&mut self, words: &[u32]) -> u32 {
+        #[cfg(crc_v1)]
+        let dr = PAC_CRC.dr().as_ptr();
+        #[cfg(not(crc_v1))]
+        let dr = PAC_CRC.dr32().as_ptr();
+        self.feed(words, dr).await
+    }
+
+    /// Feed a slice of bytes to the CRC unit and return the result.
+    #[cfg(not(crc_v1))]
+    pub async fn feed_bytes(&mut self, bytes: &[u8]) -> u32 {
+        self.feed(bytes, PAC_CRC.dr8().as_ptr()).await
+    }
+
+    /// Get back the CRC driver, releasing the DMA channel.
+    pub fn into_inner(self) -> Crc<'d> {
+        self.crc
+    }
+
+    async fn feed<W: Word>(&mut self, data: &[W], dr: *mut W) -> u32 {
+        // Transfers are limited to 65535 bytes on some DMA versions
+        let max_chunk = 0xFFFF / core::mem::size_of::<W>();
+        for chunk in data.chunks(max_chunk) {
+            unsafe { Transfer::new_write_to_memory(self.dma.reborrow(), chunk, dr, Default::default()) }.await;
+        }
+        self.crc.read()
+    }
+}
@@ -4,4 +4,7 @@
 #[cfg_attr(crc_v3, path = "v2v3.rs")]
 mod _version;
 
+mod dma;
+
 pub use _version::*;
+pub use dma::DmaCrc;
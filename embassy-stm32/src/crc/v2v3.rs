@@ -99,6 +99,17 @@ impl<'d> Crc<'d> {
         PAC_CRC.cr().modify(|w| w.set_reset(true));
     }
 
+    /// Change the configuration (polynomial, initial value and reflection) and reset the CRC engine.
+    pub fn set_config(&mut self, config: Config) {
+        self._config = config;
+        self.reconfigure();
+    }
+
+    /// Read the CRC result value.
+    pub fn read(&self) -> u32 {
+        PAC_CRC.dr32().read()
+    }
+
     /// Reconfigures the CRC peripheral. Doesn't reset.
     fn reconfigure(&mut self) {
         // Init CRC value
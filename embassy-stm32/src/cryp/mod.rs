@@ -11,6 +11,11 @@ use crate::dma::{NoDma, Transfer, TransferOptions};
 use crate::interrupt::typelevel::Interrupt;
 use crate::{interrupt, pac, peripherals, rcc, Peripheral};
 
+#[cfg(any(feature = "cipher", feature = "aead"))]
+mod rustcrypto;
+#[cfg(any(feature = "cipher", feature = "aead"))]
+pub use rustcrypto::*;
+
 const DES_BLOCK_SIZE: usize = 8; // 64 bits
 const AES_BLOCK_SIZE: usize = 16; // 128 bits
 
@@ -0,0 +1,219 @@
+//! [`cipher`] and [`aead`] trait implementations.
+//!
+//! The trait methods are synchronous, so data is processed with the blocking methods of [`Cryp`].
+//! Use [`Cryp::payload`] directly to offload large buffers with DMA.
+#[cfg(all(feature = "aead", any(cryp_v2, cryp_v3, cryp_v4)))]
+pub use aead_impl::AesGcmAead;
+#[cfg(feature = "cipher")]
+pub use cipher_impl::AesBlockCipher;
+
+#[cfg(feature = "cipher")]
+mod cipher_impl {
+    use cipher::consts::{U1, U16};
+    use cipher::inout::InOut;
+    use cipher::{
+        Block, BlockBackend, BlockClosure, BlockDecryptMut, BlockEncryptMut, BlockSizeUser, ParBlocksSizeUser,
+    };
+
+    use crate::cryp::{AesEcb, CipherSized, Context, Cryp, Direction, Instance};
+
+    /// AES block cipher implementing the [`cipher`] traits, created with [`Cryp::aes_block_cipher`].
+    ///
+    /// Each call to the traits processes the blocks independently, in ECB mode. Chaining modes from
+    /// the RustCrypto `block-modes` crates can be layered on top.
+    pub struct AesBlockCipher<'a, 'd, T: Instance, DmaIn, DmaOut, const KEY_SIZE: usize> {
+        cryp: &'a Cryp<'d, T, DmaIn, DmaOut>,
+        key: [u8; KEY_SIZE],
+    }
+
+    impl<'d, T: Instance, DmaIn, DmaOut> Cryp<'d, T, DmaIn, DmaOut> {
+        /// Create an AES block cipher implementing the [`cipher`] traits.
+        ///
+        /// The key must be 16, 24 or 32 bytes long.
+        pub fn aes_block_cipher<const KEY_SIZE: usize>(
+            &self,
+            key: &[u8; KEY_SIZE],
+        ) -> AesBlockCipher<'_, 'd, T, DmaIn, DmaOut, KEY_SIZE> {
+            AesBlockCipher { cryp: self, key: *key }
+        }
+    }
+
+    struct Backend<'a, 'd, T: Instance, DmaIn, DmaOut, const KEY_SIZE: usize>
+    where
+        AesEcb<'a, KEY_SIZE>: CipherSized,
+    {
+        cryp: &'a Cryp<'d, T, DmaIn, DmaOut>,
+        ctx: Context<'a, AesEcb<'a, KEY_SIZE>>,
+    }
+
+    macro_rules! impl_key_size {
+        ($key_size:literal) => {
+            impl<'a, 'd, T: Instance, DmaIn, DmaOut> BlockSizeUser
+                for AesBlockCipher<'a, 'd, T, DmaIn, DmaOut, $key_size>
+            {
+                type BlockSize = U16;
+            }
+
+            impl<'a, 'd, T: Instance, DmaIn, DmaOut> AesBlockCipher<'a, 'd, T, DmaIn, DmaOut, $key_size> {
+                fn with_backend(&self, dir: Direction, f: impl BlockClosure<BlockSize = U16>) {
+                    let cipher = AesEcb::new(&self.key);
+                    let ctx = self.cryp.start_blocking(&cipher, dir);
+                    f.call(&mut Backend { cryp: self.cryp, ctx });
+                }
+            }
+
+            impl<'a, 'd, T: Instance, DmaIn, DmaOut> BlockEncryptMut
+                for AesBlockCipher<'a, 'd, T, DmaIn, DmaOut, $key_size>
+            {
+                fn encrypt_with_backend_mut(&mut self, f: impl BlockClosure<BlockSize = U16>) {
+                    self.with_backend(Direction::Encrypt, f);
+                }
+            }
+
+            impl<'a, 'd, T: Instance, DmaIn, DmaOut> BlockDecryptMut
+                for AesBlockCipher<'a, 'd, T, DmaIn, DmaOut, $key_size>
+            {
+                fn decrypt_with_backend_mut(&mut self, f: impl BlockClosure<BlockSize = U16>) {
+                    self.with_backend(Direction::Decrypt, f);
+                }
+            }
+
+            impl<'a, 'd, T: Instance, DmaIn, DmaOut> BlockSizeUser for Backend<'a, 'd, T, DmaIn, DmaOut, $key_size> {
+                type BlockSize = U16;
+            }
+
+            impl<'a, 'd, T: Instance, DmaIn, DmaOut> ParBlocksSizeUser
+                for Backend<'a, 'd, T, DmaIn, DmaOut, $key_size>
+            {
+                type ParBlocksSize = U1;
+            }
+
+            impl<'a, 'd, T: Instance, DmaIn, DmaOut> BlockBackend for Backend<'a, 'd, T, DmaIn, DmaOut, $key_size> {
+                fn proc_block(&mut self, mut block: InOut<'_, '_, Block<Self>>) {
+                    let input = *block.get_in();
+                    self.cryp
+                        .payload_blocking(&mut self.ctx, &input, block.get_out(), false);
+                }
+            }
+        };
+    }
+
+    impl_key_size!(16);
+    impl_key_size!(24);
+    impl_key_size!(32);
+}
+
+#[cfg(all(feature = "aead", any(cryp_v2, cryp_v3, cryp_v4)))]
+mod aead_impl {
+    use aead::consts::{U0, U12, U16};
+    use aead::{AeadCore, AeadMutInPlace, Nonce, Tag};
+
+    use crate::cryp::{AesGcm, CipherSized, Context, Cryp, Direction, Instance, AES_BLOCK_SIZE};
+
+    /// AES-GCM implementing the [`aead`] traits, created with [`Cryp::aes_gcm_aead`].
+    pub struct AesGcmAead<'a, 'd, T: Instance, DmaIn, DmaOut, const KEY_SIZE: usize> {
+        cryp: &'a Cryp<'d, T, DmaIn, DmaOut>,
+        key: [u8; KEY_SIZE],
+    }
+
+    impl<'d, T: Instance, DmaIn, DmaOut> Cryp<'d, T, DmaIn, DmaOut> {
+        /// Create an AES-GCM cipher implementing the [`aead`] traits, with a 96-bit nonce and a 128-bit tag.
+        ///
+        /// The key must be 16, 24 or 32 bytes long.
+        pub fn aes_gcm_aead<const KEY_SIZE: usize>(
+            &self,
+            key: &[u8; KEY_SIZE],
+        ) -> AesGcmAead<'_, 'd, T, DmaIn, DmaOut, KEY_SIZE> {
+            AesGcmAead { cryp: self, key: *key }
+        }
+    }
+
+    impl<'a, 'd, T: Instance, DmaIn, DmaOut, const KEY_SIZE: usize> AeadCore
+        for AesGcmAead<'a, 'd, T, DmaIn, DmaOut, KEY_SIZE>
+    {
+        type NonceSize = U12;
+        type TagSize = U16;
+        type CiphertextOverhead = U0;
+    }
+
+    macro_rules! impl_key_size {
+        ($key_size:literal) => {
+            impl<'a, 'd, T: Instance, DmaIn, DmaOut> AesGcmAead<'a, 'd, T, DmaIn, DmaOut, $key_size> {
+                fn process(
+                    &self,
+                    dir: Direction,
+                    nonce: &Nonce<Self>,
+                    associated_data: &[u8],
+                    buffer: &mut [u8],
+                ) -> [u8; 16] {
+                    let iv: [u8; 12] = (*nonce).into();
+                    let cipher = AesGcm::new(&self.key, &iv);
+                    let mut ctx = self.cryp.start_blocking(&cipher, dir);
+                    if !associated_data.is_empty() {
+                        self.cryp.aad_blocking(&mut ctx, associated_data, true);
+                    }
+                    payload_in_place(self.cryp, &mut ctx, buffer);
+                    self.cryp.finish_blocking(ctx)
+                }
+            }
+
+            impl<'a, 'd, T: Instance, DmaIn, DmaOut> AeadMutInPlace
+                for AesGcmAead<'a, 'd, T, DmaIn, DmaOut, $key_size>
+            {
+                fn encrypt_in_place_detached(
+                    &mut self,
+                    nonce: &Nonce<Self>,
+                    associated_data: &[u8],
+                    buffer: &mut [u8],
+                ) -> aead::Result<Tag<Self>> {
+                    Ok(self
+                        .process(Direction::Encrypt, nonce, associated_data, buffer)
+                        .into())
+                }
+
+                fn decrypt_in_place_detached(
+                    &mut self,
+                    nonce: &Nonce<Self>,
+                    associated_data: &[u8],
+                    buffer: &mut [u8],
+                    tag: &Tag<Self>,
+                ) -> aead::Result<()> {
+                    let expected = self.process(Direction::Decrypt, nonce, associated_data, buffer);
+                    let diff = expected
+                        .iter()
+                        .zip(tag.iter())
+                        .fold(0, |acc, (a, b)| acc | (a ^ b));
+                    if diff == 0 {
+                        Ok(())
+                    } else {
+                        // Encrypt again to leave the buffer as it was, like the software implementations.
+                        self.process(Direction::Encrypt, nonce, associated_data, buffer);
+                        Err(aead::Error)
+                    }
+                }
+            }
+        };
+    }
+
+    impl_key_size!(16);
+    impl_key_size!(24);
+    impl_key_size!(32);
+
+    /// Run the payload phase over `buffer`, block by block through a temporary copy.
+    fn payload_in_place<'c, T: Instance, DmaIn, DmaOut, const KEY_SIZE: usize>(
+        cryp: &Cryp<'_, T, DmaIn, DmaOut>,
+        ctx: &mut Context<'c, AesGcm<'c, KEY_SIZE>>,
+        buffer: &mut [u8],
+    ) where
+        AesGcm<'c, KEY_SIZE>: CipherSized,
+    {
+        let len = buffer.len();
+        let mut block = [0u8; AES_BLOCK_SIZE];
+        for (i, chunk) in buffer.chunks_mut(AES_BLOCK_SIZE).enumerate() {
+            let n = chunk.len();
+            block[..n].copy_from_slice(chunk);
+            let last = (i + 1) * AES_BLOCK_SIZE >= len;
+            cryp.payload_blocking(ctx, &block[..n], chunk, last);
+        }
+    }
+}
@@ -1,19 +1,47 @@
 //! Digital to Analog Converter (DAC)
 #![macro_use]
 
+#[cfg(not(any(gpdma, dac_v1)))]
+use core::future::poll_fn;
 use core::marker::PhantomData;
+#[cfg(not(any(gpdma, dac_v1)))]
+use core::task::Poll;
 
+#[cfg(not(any(gpdma, dac_v1)))]
+use embassy_hal_internal::drop::OnDrop;
 use embassy_hal_internal::{into_ref, PeripheralRef};
+#[cfg(not(any(gpdma, dac_v1)))]
+use embassy_sync::waitqueue::AtomicWaker;
 
 use crate::dma::NoDma;
+#[cfg(not(any(gpdma, dac_v1)))]
+use crate::interrupt;
+#[cfg(not(any(gpdma, dac_v1)))]
+use crate::interrupt::typelevel::{Binding, Interrupt};
+#[cfg(not(any(gpdma, dac_v1)))]
+use crate::mode::Async;
+use crate::mode::{Blocking, Mode as PeriMode};
 #[cfg(any(dac_v3, dac_v4, dac_v5, dac_v6, dac_v7))]
 use crate::pac::dac;
 use crate::rcc::{self, RccPeripheral};
+#[cfg(not(any(gpdma, dac_v1)))]
+use crate::time::Hertz;
+#[cfg(not(any(gpdma, dac_v1)))]
+use crate::timer::low_level::{MasterMode, Timer};
+#[cfg(not(any(gpdma, dac_v1)))]
+use crate::timer::BasicInstance;
 use crate::{peripherals, Peripheral};
 
 mod tsel;
 pub use tsel::TriggerSel;
 
+/// DMA underrun during waveform playback.
+///
+/// The DAC was triggered before the DMA delivered the next sample.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Underrun;
+
 /// Operating mode for DAC channel
 #[cfg(any(dac_v3, dac_v4, dac_v5, dac_v6, dac_v7))]
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -100,20 +128,22 @@ pub enum ValueArray<'a> {
 ///
 /// If you want to use both channels, either together or independently,
 /// create a [`Dac`] first and use it to access each channel.
-pub struct DacChannel<'d, T: Instance, const N: u8, DMA = NoDma> {
+///
+/// Channels created in [`Async`](crate::mode::Async) mode are bound to the DAC interrupt, which is
+/// needed to play looped waveforms with `write_looped`.
+pub struct DacChannel<'d, T: Instance, const N: u8, DMA = NoDma, M: PeriMode = Blocking> {
     phantom: PhantomData<&'d mut T>,
     #[allow(unused)]
     dma: PeripheralRef<'d, DMA>,
+    _mode: PhantomData<M>,
 }
 
 /// DAC channel 1 type alias.
-pub type DacCh1<'d, T, DMA = NoDma> = DacChannel<'d, T, 1, DMA>;
+pub type DacCh1<'d, T, DMA = NoDma, M = Blocking> = DacChannel<'d, T, 1, DMA, M>;
 /// DAC channel 2 type alias.
-pub type DacCh2<'d, T, DMA = NoDma> = DacChannel<'d, T, 2, DMA>;
-
-impl<'d, T: Instance, const N: u8, DMA> DacChannel<'d, T, N, DMA> {
-    const IDX: usize = (N - 1) as usize;
+pub type DacCh2<'d, T, DMA = NoDma, M = Blocking> = DacChannel<'d, T, 2, DMA, M>;
 
+impl<'d, T: Instance, const N: u8, DMA> DacChannel<'d, T, N, DMA, Blocking> {
     /// Create a new `DacChannel` instance, consuming the underlying DAC peripheral.
     ///
     /// If you're not using DMA, pass [`dma::NoDma`] for the `dma` argument.
@@ -135,6 +165,7 @@ impl<'d, T: Instance, const N: u8, DMA> DacChannel<'d, T, N, DMA> {
         let mut dac = Self {
             phantom: PhantomData,
             dma,
+            _mode: PhantomData,
         };
         #[cfg(any(dac_v5, dac_v6, dac_v7))]
         dac.set_hfsel();
@@ -161,6 +192,7 @@ impl<'d, T: Instance, const N: u8, DMA> DacChannel<'d, T, N, DMA> {
         let mut dac = Self {
             phantom: PhantomData,
             dma,
+            _mode: PhantomData,
         };
         #[cfg(any(dac_v5, dac_v6, dac_v7))]
         dac.set_hfsel();
@@ -168,6 +200,39 @@ impl<'d, T: Instance, const N: u8, DMA> DacChannel<'d, T, N, DMA> {
         dac.enable();
         dac
     }
+}
+
+#[cfg(not(any(gpdma, dac_v1)))]
+impl<'d, T: InterruptInstance, const N: u8, DMA> DacChannel<'d, T, N, DMA, Async> {
+    /// Create a new `DacChannel` instance bound to the DAC interrupt, consuming the underlying
+    /// DAC peripheral.
+    ///
+    /// This is like [`DacChannel::new`], and also allows playing looped waveforms with
+    /// `write_looped`. The DAC interrupt is shared with TIM6 on most parts; bind
+    /// [`InterruptHandler`] alongside any TIM6 handler.
+    pub fn new_async(
+        _peri: impl Peripheral<P = T> + 'd,
+        dma: impl Peripheral<P = DMA> + 'd,
+        pin: impl Peripheral<P = impl DacPin<T, N> + crate::gpio::Pin> + 'd,
+        _irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+    ) -> Self {
+        into_ref!(dma, pin);
+        pin.set_as_analog();
+        rcc::enable_and_reset::<T>();
+        let mut dac = Self {
+            phantom: PhantomData,
+            dma,
+            _mode: PhantomData,
+        };
+        #[cfg(any(dac_v5, dac_v6, dac_v7))]
+        dac.set_hfsel();
+        dac.enable();
+        dac
+    }
+}
+
+impl<'d, T: Instance, const N: u8, DMA, M: PeriMode> DacChannel<'d, T, N, DMA, M> {
+    const IDX: usize = (N - 1) as usize;
 
     /// Enable or disable this channel.
     pub fn set_enable(&mut self, on: bool) {
@@ -281,7 +346,7 @@ impl<'d, T: Instance, const N: u8, DMA> DacChannel<'d, T, N, DMA> {
 
 macro_rules! impl_dma_methods {
     ($n:literal, $trait:ident) => {
-        impl<'d, T: Instance, DMA> DacChannel<'d, T, $n, DMA>
+        impl<'d, T: Instance, DMA, M: PeriMode> DacChannel<'d, T, $n, DMA, M>
         where
             DMA: $trait<T>,
         {
@@ -348,18 +413,137 @@ macro_rules! impl_dma_methods {
                 });
             }
         }
+
+        #[cfg(not(any(gpdma, dac_v1)))]
+        impl<'d, T: InterruptInstance, DMA> DacChannel<'d, T, $n, DMA, Async>
+        where
+            DMA: $trait<T>,
+        {
+            /// Play `data` as a looped waveform at `sample_rate`.
+            ///
+            /// `timer` is configured to run at `sample_rate` and to emit its update event on TRGO,
+            /// which triggers the DAC to output the next 12-bit right-aligned sample delivered by a
+            /// circular DMA transfer. `trigger` must select the TRGO of `timer`.
+            ///
+            /// Playback runs until the DMA fails to deliver a sample before the next trigger, in
+            /// which case [`Underrun`] is returned. Dropping the future stops playback.
+            ///
+            /// The underrun is signalled by the DAC interrupt, which is only enabled while
+            /// playing.
+            pub async fn write_looped<TIM: BasicInstance>(
+                &mut self,
+                data: &[u16],
+                sample_rate: Hertz,
+                timer: &mut Timer<'_, TIM>,
+                trigger: TriggerSel,
+            ) -> Underrun {
+                timer.stop();
+                timer.set_frequency(sample_rate);
+                timer.set_master_mode(MasterMode::UPDATE);
+
+                self.set_trigger(trigger);
+                self.set_triggering(true);
+
+                // Clear a stale underrun flag
+                T::regs().sr().write(|w| w.set_dmaudr(Self::IDX, true));
+
+                T::regs().cr().modify(|w| {
+                    w.set_en(Self::IDX, true);
+                    w.set_dmaen(Self::IDX, true);
+                });
+
+                let tx_request = self.dma.request();
+                let tx_options = crate::dma::TransferOptions {
+                    circular: true,
+                    half_transfer_ir: false,
+                    complete_transfer_ir: false,
+                    ..Default::default()
+                };
+
+                let _transfer = unsafe {
+                    crate::dma::Transfer::new_write(
+                        &mut self.dma,
+                        tx_request,
+                        data,
+                        T::regs().dhr12r(Self::IDX).as_ptr() as *mut u16,
+                        tx_options,
+                    )
+                };
+
+                timer.start();
+
+                let _on_drop = OnDrop::new(|| {
+                    timer.stop();
+                    critical_section::with(|_| {
+                        T::regs().cr().modify(|w| {
+                            w.set_en(Self::IDX, false);
+                            w.set_dmaen(Self::IDX, false);
+                            w.set_dmaudrie(Self::IDX, false);
+                        });
+                        // Leave the interrupt enabled while the other channel plays, and for the
+                        // time driver it is shared with.
+                        #[cfg(not(time_driver_tim6))]
+                        if !T::regs().cr().read().dmaudrie(1 - Self::IDX) {
+                            T::Interrupt::disable();
+                        }
+                    });
+                });
+
+                T::Interrupt::unpend();
+                unsafe { T::Interrupt::enable() };
+
+                poll_fn(|cx| {
+                    T::underrun_wakers()[Self::IDX].register(cx.waker());
+
+                    if T::regs().sr().read().dmaudr(Self::IDX) {
+                        Poll::Ready(())
+                    } else {
+                        // The interrupt handler masks the interrupt when it fires.
+                        T::regs().cr().modify(|w| w.set_dmaudrie(Self::IDX, true));
+                        Poll::Pending
+                    }
+                })
+                .await;
+
+                T::regs().sr().write(|w| w.set_dmaudr(Self::IDX, true));
+
+                Underrun
+            }
+        }
     };
 }
 
 impl_dma_methods!(1, DacDma1);
 impl_dma_methods!(2, DacDma2);
 
-impl<'d, T: Instance, const N: u8, DMA> Drop for DacChannel<'d, T, N, DMA> {
+impl<'d, T: Instance, const N: u8, DMA, M: PeriMode> Drop for DacChannel<'d, T, N, DMA, M> {
     fn drop(&mut self) {
         rcc::disable::<T>();
     }
 }
 
+/// DAC underrun interrupt handler.
+#[cfg(not(any(gpdma, dac_v1)))]
+pub struct InterruptHandler<T: InterruptInstance> {
+    _phantom: PhantomData<T>,
+}
+
+#[cfg(not(any(gpdma, dac_v1)))]
+impl<T: InterruptInstance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        let r = T::regs();
+        let sr = r.sr().read();
+        let cr = r.cr().read();
+
+        for idx in 0..2 {
+            if sr.dmaudr(idx) && cr.dmaudrie(idx) {
+                r.cr().modify(|w| w.set_dmaudrie(idx, false));
+                T::underrun_wakers()[idx].wake();
+            }
+        }
+    }
+}
+
 /// DAC driver.
 ///
 /// Use this struct when you want to use both channels, either together or independently.
@@ -370,12 +554,12 @@ impl<'d, T: Instance, const N: u8, DMA> Drop for DacChannel<'d, T, N, DMA> {
 /// // Pins may need to be changed for your specific device.
 /// let (dac_ch1, dac_ch2) = embassy_stm32::dac::Dac::new(p.DAC1, NoDma, NoDma, p.PA4, p.PA5).split();
 /// ```
-pub struct Dac<'d, T: Instance, DMACh1 = NoDma, DMACh2 = NoDma> {
-    ch1: DacChannel<'d, T, 1, DMACh1>,
-    ch2: DacChannel<'d, T, 2, DMACh2>,
+pub struct Dac<'d, T: Instance, DMACh1 = NoDma, DMACh2 = NoDma, M: PeriMode = Blocking> {
+    ch1: DacChannel<'d, T, 1, DMACh1, M>,
+    ch2: DacChannel<'d, T, 2, DMACh2, M>,
 }
 
-impl<'d, T: Instance, DMACh1, DMACh2> Dac<'d, T, DMACh1, DMACh2> {
+impl<'d, T: Instance, DMACh1, DMACh2> Dac<'d, T, DMACh1, DMACh2, Blocking> {
     /// Create a new `Dac` instance, consuming the underlying DAC peripheral.
     ///
     /// This struct allows you to access both channels of the DAC, where available. You can either
@@ -406,6 +590,7 @@ impl<'d, T: Instance, DMACh1, DMACh2> Dac<'d, T, DMACh1, DMACh2> {
         let mut ch1 = DacCh1 {
             phantom: PhantomData,
             dma: dma_ch1,
+            _mode: PhantomData,
         };
         #[cfg(any(dac_v5, dac_v6, dac_v7))]
         ch1.set_hfsel();
@@ -414,6 +599,7 @@ impl<'d, T: Instance, DMACh1, DMACh2> Dac<'d, T, DMACh1, DMACh2> {
         let mut ch2 = DacCh2 {
             phantom: PhantomData,
             dma: dma_ch2,
+            _mode: PhantomData,
         };
         #[cfg(any(dac_v5, dac_v6, dac_v7))]
         ch2.set_hfsel();
@@ -450,6 +636,7 @@ impl<'d, T: Instance, DMACh1, DMACh2> Dac<'d, T, DMACh1, DMACh2> {
         let mut ch1 = DacCh1 {
             phantom: PhantomData,
             dma: dma_ch1,
+            _mode: PhantomData,
         };
         #[cfg(any(dac_v5, dac_v6, dac_v7))]
         ch1.set_hfsel();
@@ -459,6 +646,7 @@ impl<'d, T: Instance, DMACh1, DMACh2> Dac<'d, T, DMACh1, DMACh2> {
         let mut ch2 = DacCh2 {
             phantom: PhantomData,
             dma: dma_ch2,
+            _mode: PhantomData,
         };
         #[cfg(any(dac_v5, dac_v6, dac_v7))]
         ch2.set_hfsel();
@@ -467,21 +655,69 @@ impl<'d, T: Instance, DMACh1, DMACh2> Dac<'d, T, DMACh1, DMACh2> {
 
         Self { ch1, ch2 }
     }
+}
 
+#[cfg(not(any(gpdma, dac_v1)))]
+impl<'d, T: InterruptInstance, DMACh1, DMACh2> Dac<'d, T, DMACh1, DMACh2, Async> {
+    /// Create a new `Dac` instance bound to the DAC interrupt, consuming the underlying DAC
+    /// peripheral.
+    ///
+    /// This is like [`Dac::new`], and also allows playing looped waveforms on the channels with
+    /// `write_looped`. The DAC interrupt is shared with TIM6 on most parts; bind
+    /// [`InterruptHandler`] alongside any TIM6 handler.
+    pub fn new_async(
+        _peri: impl Peripheral<P = T> + 'd,
+        dma_ch1: impl Peripheral<P = DMACh1> + 'd,
+        dma_ch2: impl Peripheral<P = DMACh2> + 'd,
+        pin_ch1: impl Peripheral<P = impl DacPin<T, 1> + crate::gpio::Pin> + 'd,
+        pin_ch2: impl Peripheral<P = impl DacPin<T, 2> + crate::gpio::Pin> + 'd,
+        _irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+    ) -> Self {
+        into_ref!(dma_ch1, dma_ch2, pin_ch1, pin_ch2);
+        pin_ch1.set_as_analog();
+        pin_ch2.set_as_analog();
+
+        // Enable twice to increment the DAC refcount for each channel.
+        rcc::enable_and_reset::<T>();
+        rcc::enable_and_reset::<T>();
+
+        let mut ch1 = DacCh1 {
+            phantom: PhantomData,
+            dma: dma_ch1,
+            _mode: PhantomData,
+        };
+        #[cfg(any(dac_v5, dac_v6, dac_v7))]
+        ch1.set_hfsel();
+        ch1.enable();
+
+        let mut ch2 = DacCh2 {
+            phantom: PhantomData,
+            dma: dma_ch2,
+            _mode: PhantomData,
+        };
+        #[cfg(any(dac_v5, dac_v6, dac_v7))]
+        ch2.set_hfsel();
+        ch2.enable();
+
+        Self { ch1, ch2 }
+    }
+}
+
+impl<'d, T: Instance, DMACh1, DMACh2, M: PeriMode> Dac<'d, T, DMACh1, DMACh2, M> {
     /// Split this `Dac` into separate channels.
     ///
     /// You can access and move the channels around separately after splitting.
-    pub fn split(self) -> (DacCh1<'d, T, DMACh1>, DacCh2<'d, T, DMACh2>) {
+    pub fn split(self) -> (DacCh1<'d, T, DMACh1, M>, DacCh2<'d, T, DMACh2, M>) {
         (self.ch1, self.ch2)
     }
 
     /// Temporarily access channel 1.
-    pub fn ch1(&mut self) -> &mut DacCh1<'d, T, DMACh1> {
+    pub fn ch1(&mut self) -> &mut DacCh1<'d, T, DMACh1, M> {
         &mut self.ch1
     }
 
     /// Temporarily access channel 2.
-    pub fn ch2(&mut self) -> &mut DacCh2<'d, T, DMACh2> {
+    pub fn ch2(&mut self) -> &mut DacCh2<'d, T, DMACh2, M> {
         &mut self.ch2
     }
 
@@ -514,6 +750,20 @@ trait SealedInstance {
 /// DAC instance.
 #[allow(private_bounds)]
 pub trait Instance: SealedInstance + RccPeripheral + 'static {}
+
+#[cfg(not(any(gpdma, dac_v1)))]
+trait SealedInterruptInstance {
+    fn underrun_wakers() -> &'static [AtomicWaker; 2];
+}
+
+/// DAC instance with an underrun interrupt.
+#[cfg(not(any(gpdma, dac_v1)))]
+#[allow(private_bounds)]
+pub trait InterruptInstance: Instance + SealedInterruptInstance {
+    /// Interrupt for this instance.
+    type Interrupt: interrupt::typelevel::Interrupt;
+}
+
 dma_trait!(DacDma1, Instance);
 dma_trait!(DacDma2, Instance);
 
@@ -532,6 +782,22 @@ foreach_peripheral!(
     };
 );
 
+#[cfg(not(any(gpdma, dac_v1)))]
+foreach_interrupt!(
+    ($inst:ident, dac, $block:ident, GLOBAL, $irq:ident) => {
+        impl SealedInterruptInstance for peripherals::$inst {
+            fn underrun_wakers() -> &'static [AtomicWaker; 2] {
+                static WAKERS: [AtomicWaker; 2] = [AtomicWaker::new(), AtomicWaker::new()];
+                &WAKERS
+            }
+        }
+
+        impl InterruptInstance for peripherals::$inst {
+            type Interrupt = crate::interrupt::typelevel::$irq;
+        }
+    };
+);
+
 macro_rules! impl_dac_pin {
     ($inst:ident, $pin:ident, $ch:expr) => {
         impl crate::dac::DacPin<peripherals::$inst, $ch> for crate::peripherals::$pin {}
@@ -92,6 +92,30 @@ pub struct Config {
     pub hsync_level: HSyncDataInvalidLevel,
     /// PIXCLK polarity.
     pub pixclk_polarity: PixelClockPolarity,
+    /// Capture only a window of the frame. Not available in JPEG mode.
+    pub crop: Option<Crop>,
+    /// JPEG mode: the frame is a compressed data stream of variable length.
+    ///
+    /// HSYNC is used as a data valid signal, and the buffer only needs to be large enough for the
+    /// largest expected frame.
+    pub jpeg: bool,
+}
+
+/// Capture window.
+///
+/// Horizontal values are counted in pixel clocks, so a pixel taking two clocks on the data bus
+/// (e.g. RGB565 in 8-bit mode) counts twice.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Crop {
+    /// Number of pixel clocks to skip at the start of each line.
+    pub x: u16,
+    /// Number of lines to skip at the start of the frame.
+    pub y: u16,
+    /// Number of pixel clocks to capture in each line.
+    pub width: u16,
+    /// Number of lines to capture.
+    pub height: u16,
 }
 
 impl Default for Config {
@@ -100,6 +124,8 @@ impl Default for Config {
             vsync_level: VSyncDataInvalidLevel::High,
             hsync_level: HSyncDataInvalidLevel::Low,
             pixclk_polarity: PixelClockPolarity::RisingEdge,
+            crop: None,
+            jpeg: false,
         }
     }
 }
@@ -351,6 +377,22 @@ where
     ) -> Self {
         rcc::enable_and_reset::<T>();
 
+        assert!(
+            !(config.jpeg && config.crop.is_some()),
+            "crop is not available in JPEG mode"
+        );
+        if let Some(crop) = config.crop {
+            assert!(crop.width > 0 && crop.height > 0);
+            peri.regs().cwstrt().write(|w| {
+                w.set_hoffcnt(crop.x);
+                w.set_vst(crop.y);
+            });
+            peri.regs().cwsize().write(|w| {
+                w.set_capcnt(crop.width - 1);
+                w.set_vline(crop.height - 1);
+            });
+        }
+
         peri.regs().cr().modify(|r| {
             r.set_cm(true); // disable continuous mode (snapshot mode)
             r.set_ess(use_embedded_synchronization);
@@ -359,6 +401,8 @@ where
             r.set_hspol(config.hsync_level == HSyncDataInvalidLevel::High);
             r.set_fcrc(0x00); // capture every frame
             r.set_edm(edm); // extended data mode
+            r.set_crop(config.crop.is_some());
+            r.set_jpeg(config.jpeg);
         });
 
         T::Interrupt::unpend();
@@ -403,7 +447,34 @@ where
 
         Self::toggle(true);
 
-        let result = poll_fn(|cx| {
+        let (_, result) = embassy_futures::join::join(dma_read, Self::wait_frame()).await;
+
+        Self::toggle(false);
+
+        result
+    }
+
+    /// Start capturing frames continuously, alternating between `buf0` and `buf1`.
+    ///
+    /// While a completed frame is borrowed from [`DoubleBufferedCapture::next_frame`], the next one
+    /// is captured into the other buffer. Both buffers must be large enough to hold a frame.
+    pub fn capture_double_buffered<'a>(
+        &'a mut self,
+        buf0: &'a mut [u32],
+        buf1: &'a mut [u32],
+    ) -> DoubleBufferedCapture<'a, 'd, T, Dma> {
+        let mut capture = DoubleBufferedCapture {
+            dcmi: self,
+            buffers: [buf0, buf1],
+            transfer: None,
+            active: 0,
+        };
+        capture.arm(0);
+        capture
+    }
+
+    async fn wait_frame() -> Result<(), Error> {
+        poll_fn(|cx| {
             STATE.waker.register(cx.waker());
 
             let ris = crate::pac::DCMI.ris().read();
@@ -419,13 +490,62 @@ where
             } else {
                 Poll::Pending
             }
-        });
+        })
+        .await
+    }
+}
 
-        let (_, result) = embassy_futures::join::join(dma_read, result).await;
+/// Continuous capture into two alternating buffers, created with [`Dcmi::capture_double_buffered`].
+///
+/// Each frame is captured in snapshot mode, and the capture of the next frame is armed as soon as
+/// a frame completes. If [`next_frame`](Self::next_frame) is not called again before the next
+/// frame starts, that frame is skipped rather than corrupted.
+pub struct DoubleBufferedCapture<'a, 'd, T: Instance, Dma: FrameDma<T>> {
+    dcmi: &'a mut Dcmi<'d, T, Dma>,
+    buffers: [&'a mut [u32]; 2],
+    transfer: Option<Transfer<'a>>,
+    active: usize,
+}
 
-        Self::toggle(false);
+impl<'a, 'd, T: Instance, Dma: FrameDma<T>> DoubleBufferedCapture<'a, 'd, T, Dma> {
+    /// Wait for the next frame and return the captured data.
+    ///
+    /// In JPEG mode, the returned slice only covers the words written for this frame.
+    pub async fn next_frame(&mut self) -> Result<&[u32], Error> {
+        let result = Dcmi::<T, Dma>::wait_frame().await;
 
-        result
+        // Dropping the transfer stops the DMA.
+        let transfer = unwrap!(self.transfer.take());
+        let received = self.buffers[self.active].len() - transfer.get_remaining_transfers() as usize;
+        drop(transfer);
+
+        let done = self.active;
+        self.arm(done ^ 1);
+
+        result?;
+        Ok(&self.buffers[done][..received])
+    }
+
+    fn arm(&mut self, index: usize) {
+        let r = self.dcmi.inner.regs();
+        let src = r.dr().as_ptr() as *mut u32;
+        let request = self.dcmi.dma.request();
+        let buf: *mut [u32] = &mut *self.buffers[index];
+        // SAFETY: the buffer is not accessed again until the transfer has been dropped.
+        let transfer =
+            unsafe { Transfer::new_read_raw(self.dcmi.dma.clone_unchecked(), request, src, buf, Default::default()) };
+        self.transfer = Some(transfer);
+        self.active = index;
+
+        Dcmi::<T, Dma>::clear_interrupt_flags();
+        Dcmi::<T, Dma>::enable_irqs();
+        Dcmi::<T, Dma>::toggle(true);
+    }
+}
+
+impl<'a, 'd, T: Instance, Dma: FrameDma<T>> Drop for DoubleBufferedCapture<'a, 'd, T, Dma> {
+    fn drop(&mut self) {
+        Dcmi::<T, Dma>::toggle(false);
     }
 }
 
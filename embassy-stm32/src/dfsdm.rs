@@ -0,0 +1,384 @@
+//! Digital filter for sigma-delta modulators (DFSDM)
+//!
+//! Converts the 1-bit stream of PDM (sigma-delta) microphones to PCM samples. The DFSDM drives
+//! the microphone clock on CKOUT, samples the microphone data on a DATINy pin, and a sinc filter
+//! decimates the bitstream. Filtered samples are streamed into a circular DMA buffer.
+
+use core::marker::PhantomData;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use embassy_hal_internal::{into_ref, PeripheralRef};
+
+use crate::dma::{ringbuffer, Priority, ReadableRingBuffer, TransferOptions};
+use crate::gpio::{AfType, AnyPin, OutputType, Pull, SealedPin as _, Speed};
+use crate::time::Hertz;
+use crate::{rcc, Peripheral};
+
+/// DFSDM error.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// Samples were lost because the ring buffer was not read fast enough, or because the DMA
+    /// could not keep up with the filter.
+    Overrun,
+}
+
+impl From<ringbuffer::Error> for Error {
+    fn from(_: ringbuffer::Error) -> Self {
+        Self::Overrun
+    }
+}
+
+/// Sinc filter order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[allow(missing_docs)]
+pub enum FilterOrder {
+    FastSinc,
+    Sinc1,
+    Sinc2,
+    Sinc3,
+    Sinc4,
+    Sinc5,
+}
+
+impl FilterOrder {
+    const fn ford(&self) -> u8 {
+        match self {
+            FilterOrder::FastSinc => 0,
+            FilterOrder::Sinc1 => 1,
+            FilterOrder::Sinc2 => 2,
+            FilterOrder::Sinc3 => 3,
+            FilterOrder::Sinc4 => 4,
+            FilterOrder::Sinc5 => 5,
+        }
+    }
+}
+
+/// Clock edge on which the microphone data is sampled.
+///
+/// Two microphones can share one data line, one driving it after each rising edge of the
+/// clock and the other after each falling edge. Check the microphone datasheet (usually a
+/// `L/R` select pin) for which edge it uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ClockEdge {
+    /// Data is sampled on the rising edge of CKOUT.
+    Rising,
+    /// Data is sampled on the falling edge of CKOUT.
+    Falling,
+}
+
+impl ClockEdge {
+    const fn sitp(&self) -> u8 {
+        match self {
+            ClockEdge::Rising => 0b00,
+            ClockEdge::Falling => 0b01,
+        }
+    }
+}
+
+/// DFSDM configuration.
+///
+/// The PCM sample rate is `clock_frequency / (oversampling * integrator_oversampling)`. For
+/// example, a 3.072 MHz microphone clock with a sinc3 filter and an oversampling of 64 gives
+/// 48 kHz.
+#[non_exhaustive]
+#[derive(Clone, Copy)]
+pub struct Config {
+    /// Microphone clock frequency, output on CKOUT.
+    ///
+    /// The DFSDM kernel clock is divided by an integer between 2 and 256 to produce it; the
+    /// closest frequency that isn't higher is used.
+    pub clock_frequency: Hertz,
+    /// Clock edge the microphone data is sampled on.
+    pub clock_edge: ClockEdge,
+    /// Sinc filter order.
+    pub filter_order: FilterOrder,
+    /// Sinc filter oversampling (decimation) ratio, between 1 and 1024.
+    pub oversampling: u16,
+    /// Integrator oversampling ratio, between 1 and 256.
+    pub integrator_oversampling: u16,
+    /// Right shift applied to the filter output, after the integrator, between 0 and 31.
+    ///
+    /// The filter output grows with `oversampling ^ order * integrator_oversampling`, and is
+    /// saturated to 24 bits after the shift. Use this to bring it back into range, e.g. a sinc3 filter with an oversampling of 64 outputs
+    /// up to 2^18, which fits without shifting.
+    pub right_shift: u8,
+    /// Offset subtracted from the channel data, to cancel the microphone DC offset.
+    pub offset: i32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            clock_frequency: Hertz(3_072_000),
+            clock_edge: ClockEdge::Rising,
+            filter_order: FilterOrder::Sinc3,
+            oversampling: 64,
+            integrator_oversampling: 1,
+            right_shift: 0,
+            offset: 0,
+        }
+    }
+}
+
+/// PDM microphone input, streaming PCM samples through DMA.
+///
+/// The microphone is read through channel `C`, and filtered by filter `F`. The channel is
+/// chosen by the DATIN pin; when the DMA channel can serve several filters, `F` has to be
+/// named, e.g. `PdmMicrophone::<_, Flt0>::new(..)`.
+///
+/// Only a single (mono) microphone is supported: the driver takes the whole DFSDM and uses one
+/// channel and one filter. Two microphones sharing a data line on opposite clock edges (stereo)
+/// cannot be read with this driver.
+pub struct PdmMicrophone<'d, T: Instance, F: FilterInstance> {
+    _peri: PeripheralRef<'d, T>,
+    ckout: PeripheralRef<'d, AnyPin>,
+    datin: PeripheralRef<'d, AnyPin>,
+    channel: usize,
+    ring_buf: ReadableRingBuffer<'d, u32>,
+    _filter: PhantomData<F>,
+}
+
+impl<'d, T: Instance, F: FilterInstance> PdmMicrophone<'d, T, F> {
+    /// Create a new PDM microphone driver.
+    ///
+    /// `dma_buf` should be large enough to absorb the samples produced between two reads, and
+    /// its length should be even, since the DMA only signals half and full transfer completion.
+    pub fn new<C: ChannelInstance>(
+        peri: impl Peripheral<P = T> + 'd,
+        ckout: impl Peripheral<P = impl CkOutPin<T>> + 'd,
+        datin: impl Peripheral<P = impl DatinPin<T, C>> + 'd,
+        dma: impl Peripheral<P = impl FilterDma<T, F>> + 'd,
+        dma_buf: &'d mut [u32],
+        config: Config,
+    ) -> Self {
+        assert!(!dma_buf.is_empty() && dma_buf.len() <= 0xFFFF);
+        assert!(config.oversampling >= 1 && config.oversampling <= 1024);
+        assert!(config.integrator_oversampling >= 1 && config.integrator_oversampling <= 256);
+        assert!(config.right_shift <= 31);
+        assert!(config.offset >= -(1 << 23) && config.offset < (1 << 23));
+
+        into_ref!(peri, ckout, datin, dma);
+
+        rcc::enable_and_reset::<T>();
+
+        ckout.set_as_af(ckout.af_num(), AfType::output(OutputType::PushPull, Speed::VeryHigh));
+        datin.set_as_af(datin.af_num(), AfType::input(Pull::None));
+
+        let regs = T::regs();
+        let ch = C::INDEX;
+        let flt = F::INDEX;
+
+        // The output clock divider is shared by all channels, and only lives in channel 0.
+        let kernel_clock = rcc::frequency::<T>();
+        let div = kernel_clock.0.div_ceil(config.clock_frequency.0).clamp(2, 256);
+        regs.ch(0).cfgr1().modify(|w| {
+            w.set_dfsdmen(false);
+            w.set_ckoutsrc(false);
+            w.set_ckoutdiv((div - 1) as u8);
+        });
+
+        trace!(
+            "DFSDM microphone clock {}Hz, desired was {}Hz (CKOUTDIV={})",
+            kernel_clock.0 / div,
+            config.clock_frequency.0,
+            div - 1
+        );
+
+        regs.ch(ch).cfgr1().modify(|w| {
+            w.set_chen(false);
+            // Serial input from the DATINy pin of this channel, clocked by the internal CKOUT.
+            w.set_datmpx(0b00);
+            w.set_datpack(0b00);
+            w.set_chinsel(false);
+            w.set_spicksel(0b01);
+            w.set_sitp(config.clock_edge.sitp());
+            w.set_ckaben(false);
+            w.set_scden(false);
+        });
+        regs.ch(ch).cfgr2().write(|w| {
+            w.set_offset(config.offset as u32 & 0xFF_FFFF);
+            w.set_dtrbs(config.right_shift);
+        });
+
+        regs.flt(flt).cr1().modify(|w| w.set_dfen(false));
+        regs.flt(flt).fcr().write(|w| {
+            w.set_ford(config.filter_order.ford());
+            w.set_fosr(config.oversampling - 1);
+            w.set_iosr((config.integrator_oversampling - 1) as u8);
+        });
+        regs.flt(flt).cr1().write(|w| {
+            w.set_rch(ch as u8);
+            w.set_rcont(true);
+            w.set_fast(true);
+            w.set_rdmaen(true);
+        });
+
+        let opts = TransferOptions {
+            half_transfer_ir: true,
+            priority: Priority::VeryHigh,
+            ..Default::default()
+        };
+
+        #[allow(clippy::let_unit_value)] // eg. stm32f334
+        let request = dma.request();
+        let src = regs.flt(flt).rdatar().as_ptr() as *mut u32;
+
+        let ring_buf = unsafe { ReadableRingBuffer::new(dma, request, src, dma_buf, opts) };
+
+        Self {
+            _peri: peri,
+            ckout: ckout.map_into(),
+            datin: datin.map_into(),
+            channel: ch,
+            ring_buf,
+            _filter: PhantomData,
+        }
+    }
+
+    /// Starts the microphone clock and the conversions.
+    pub fn start(&mut self) {
+        compiler_fence(Ordering::SeqCst);
+
+        self.ring_buf.start();
+
+        let regs = T::regs();
+        regs.flt(F::INDEX).icr().write(|w| w.set_clrrovrf(true));
+        regs.ch(self.channel).cfgr1().modify(|w| w.set_chen(true));
+        regs.ch(0).cfgr1().modify(|w| w.set_dfsdmen(true));
+        regs.flt(F::INDEX).cr1().modify(|w| w.set_dfen(true));
+        regs.flt(F::INDEX).cr1().modify(|w| w.set_rswstart(true));
+    }
+
+    /// Stops the conversions and the DMA transfer.
+    ///
+    /// The microphone clock on CKOUT keeps running, so the microphone stays awake and the stream
+    /// restarts without waiting for it to power up. Calling [`start`](Self::start) or
+    /// [`read`](Self::read) restarts the stream; dropping the driver stops the clock.
+    pub fn stop(&mut self) {
+        let regs = T::regs();
+        regs.flt(F::INDEX).cr1().modify(|w| w.set_dfen(false));
+        regs.ch(self.channel).cfgr1().modify(|w| w.set_chen(false));
+
+        self.ring_buf.request_pause();
+
+        compiler_fence(Ordering::SeqCst);
+    }
+
+    fn is_running(&self) -> bool {
+        T::regs().flt(F::INDEX).cr1().read().dfen()
+    }
+
+    fn overrun(&mut self) -> Result<usize, Error> {
+        self.stop();
+        Err(Error::Overrun)
+    }
+
+    /// Waits until `samples` can be filled entirely, and copies the oldest PCM samples into it.
+    ///
+    /// Samples are signed 24 bit values, sign-extended to `i32`. The DMA only signals half and
+    /// full transfer completion, so reading half of the DMA buffer at a time returns as soon as
+    /// the DMA fills one half.
+    ///
+    /// Returns the number of samples still available in the ring buffer. If samples were lost,
+    /// the stream is stopped and [`Error::Overrun`] is returned; the next call to `read`
+    /// restarts it.
+    pub async fn read(&mut self, samples: &mut [i32]) -> Result<usize, Error> {
+        if !self.is_running() {
+            self.start();
+        }
+
+        // Safety: i32 and u32 have the same size and alignment.
+        let raw = unsafe { core::slice::from_raw_parts_mut(samples.as_mut_ptr() as *mut u32, samples.len()) };
+
+        let remaining = match self.ring_buf.read_exact(raw).await {
+            Ok(_) if T::regs().flt(F::INDEX).isr().read().rovrf() => return self.overrun(),
+            Ok(len) => len,
+            Err(_) => return self.overrun(),
+        };
+
+        // The filter data is in the top 24 bits, the channel number in the bottom bits.
+        for sample in samples.iter_mut() {
+            *sample >>= 8;
+        }
+
+        Ok(remaining)
+    }
+}
+
+impl<'d, T: Instance, F: FilterInstance> Drop for PdmMicrophone<'d, T, F> {
+    fn drop(&mut self) {
+        self.stop();
+        T::regs().ch(0).cfgr1().modify(|w| w.set_dfsdmen(false));
+        self.ckout.set_as_disconnected();
+        self.datin.set_as_disconnected();
+        rcc::disable::<T>();
+    }
+}
+
+trait SealedChannel {
+    const INDEX: usize;
+}
+
+/// DFSDM channel marker trait.
+#[allow(private_bounds)]
+pub trait ChannelInstance: SealedChannel {}
+
+trait SealedFilter {
+    const INDEX: usize;
+}
+
+/// DFSDM filter marker trait.
+#[allow(private_bounds)]
+pub trait FilterInstance: SealedFilter {}
+
+macro_rules! impl_marker {
+    ($name:ident, $sealed:ident, $trait:ident, $index:expr, $doc:literal) => {
+        #[doc = $doc]
+        pub enum $name {}
+        impl $sealed for $name {
+            const INDEX: usize = $index;
+        }
+        impl $trait for $name {}
+    };
+}
+
+impl_marker!(Ch0, SealedChannel, ChannelInstance, 0, "Channel 0.");
+impl_marker!(Ch1, SealedChannel, ChannelInstance, 1, "Channel 1.");
+impl_marker!(Ch2, SealedChannel, ChannelInstance, 2, "Channel 2.");
+impl_marker!(Ch3, SealedChannel, ChannelInstance, 3, "Channel 3.");
+impl_marker!(Ch4, SealedChannel, ChannelInstance, 4, "Channel 4.");
+impl_marker!(Ch5, SealedChannel, ChannelInstance, 5, "Channel 5.");
+impl_marker!(Ch6, SealedChannel, ChannelInstance, 6, "Channel 6.");
+impl_marker!(Ch7, SealedChannel, ChannelInstance, 7, "Channel 7.");
+impl_marker!(Flt0, SealedFilter, FilterInstance, 0, "Filter 0.");
+impl_marker!(Flt1, SealedFilter, FilterInstance, 1, "Filter 1.");
+impl_marker!(Flt2, SealedFilter, FilterInstance, 2, "Filter 2.");
+impl_marker!(Flt3, SealedFilter, FilterInstance, 3, "Filter 3.");
+
+trait SealedInstance {
+    fn regs() -> crate::pac::dfsdm::Dfsdm;
+}
+
+/// DFSDM instance.
+#[allow(private_bounds)]
+pub trait Instance: Peripheral<P = Self> + SealedInstance + rcc::RccPeripheral + 'static {}
+
+pin_trait!(CkOutPin, Instance);
+pin_trait!(DatinPin, Instance, ChannelInstance);
+
+dma_trait!(FilterDma, Instance, FilterInstance);
+
+foreach_peripheral!(
+    (dfsdm, $inst:ident) => {
+        impl SealedInstance for crate::peripherals::$inst {
+            fn regs() -> crate::pac::dfsdm::Dfsdm {
+                crate::pac::$inst
+            }
+        }
+
+        impl Instance for crate::peripherals::$inst {}
+    };
+);
@@ -140,6 +140,7 @@ mod dma_only {
             match raw {
                 Dir::MemoryToPeripheral => Self::MEMORYTOPERIPHERAL,
                 Dir::PeripheralToMemory => Self::PERIPHERALTOMEMORY,
+                Dir::MemoryToMemory => Self::MEMORYTOMEMORY,
             }
         }
     }
@@ -233,7 +234,8 @@ mod bdma_only {
     impl From<Dir> for vals::Dir {
         fn from(raw: Dir) -> Self {
             match raw {
-                Dir::MemoryToPeripheral => Self::FROMMEMORY,
+                // memory-to-memory reads from MAR and writes to PAR, like memory-to-peripheral
+                Dir::MemoryToPeripheral | Dir::MemoryToMemory => Self::FROMMEMORY,
                 Dir::PeripheralToMemory => Self::FROMPERIPHERAL,
             }
         }
@@ -365,11 +367,23 @@ impl AnyChannel {
 
                 self.clear_irqs();
 
-                ch.par().write_value(peri_addr as u32);
-                ch.m0ar().write_value(mem_addr as u32);
+                // In memory-to-memory mode, the source is PAR and the destination M0AR.
+                let m2m = dir == Dir::MemoryToMemory;
+                if m2m {
+                    ch.par().write_value(mem_addr as u32);
+                    ch.m0ar().write_value(peri_addr as u32);
+                } else {
+                    ch.par().write_value(peri_addr as u32);
+                    ch.m0ar().write_value(mem_addr as u32);
+                }
                 ch.ndtr().write_value(pac::dma::regs::Ndtr(mem_len as _));
                 ch.fcr().write(|w| {
-                    if let Some(fth) = options.fifo_threshold {
+                    // direct mode is not allowed in memory-to-memory mode
+                    let fifo_threshold = match options.fifo_threshold {
+                        None if m2m => Some(FifoThreshold::Half),
+                        fth => fth,
+                    };
+                    if let Some(fth) = fifo_threshold {
                         // FIFO mode
                         w.set_dmdis(pac::dma::vals::Dmdis::DISABLED);
                         w.set_fth(fth.into());
@@ -383,8 +397,8 @@ impl AnyChannel {
                     w.set_msize(data_size.into());
                     w.set_psize(data_size.into());
                     w.set_pl(options.priority.into());
-                    w.set_minc(incr_mem);
-                    w.set_pinc(false);
+                    w.set_minc(incr_mem && !m2m);
+                    w.set_pinc(incr_mem && m2m);
                     w.set_teie(true);
                     w.set_htie(options.half_transfer_ir);
                     w.set_tcie(options.complete_transfer_ir);
@@ -418,6 +432,7 @@ impl AnyChannel {
                     w.set_msize(data_size.into());
                     w.set_minc(incr_mem);
                     w.set_dir(dir.into());
+                    w.set_mem2mem(dir == Dir::MemoryToMemory);
                     w.set_teie(true);
                     w.set_tcie(options.complete_transfer_ir);
                     w.set_htie(options.half_transfer_ir);
@@ -664,6 +679,34 @@ impl<'a> Transfer<'a> {
         )
     }
 
+    /// Create a new memory-to-memory DMA transfer, writing `buf` to the fixed address `dst_addr`.
+    ///
+    /// The transfer runs without waiting for peripheral requests, which is useful to feed
+    /// peripherals without a DMA request line, such as the CRC unit.
+    ///
+    /// On chips with the stream DMA (`DMA1`/`DMA2` with FIFOs), only `DMA2` can do memory-to-memory
+    /// transfers. Circular mode is not available.
+    pub unsafe fn new_write_to_memory<W: Word>(
+        channel: impl Peripheral<P = impl Channel> + 'a,
+        buf: &'a [W],
+        dst_addr: *mut W,
+        options: TransferOptions,
+    ) -> Self {
+        into_ref!(channel);
+
+        Self::new_inner(
+            channel.map_into(),
+            Request::default(),
+            Dir::MemoryToMemory,
+            dst_addr as *const u32,
+            buf.as_ptr() as *mut u32,
+            buf.len(),
+            true,
+            W::size(),
+            options,
+        )
+    }
+
     unsafe fn new_inner(
         channel: PeripheralRef<'a, AnyChannel>,
         _request: Request,
@@ -205,6 +205,31 @@ impl<'a> Transfer<'a> {
         )
     }
 
+    /// Create a new memory-to-memory DMA transfer, writing `buf` to the fixed address `dst_addr`.
+    ///
+    /// The transfer runs without waiting for peripheral requests, which is useful to feed
+    /// peripherals without a DMA request line, such as the CRC unit.
+    pub unsafe fn new_write_to_memory<W: Word>(
+        channel: impl Peripheral<P = impl Channel> + 'a,
+        buf: &'a [W],
+        dst_addr: *mut W,
+        options: TransferOptions,
+    ) -> Self {
+        into_ref!(channel);
+
+        Self::new_inner(
+            channel.map_into(),
+            Request::default(),
+            Dir::MemoryToMemory,
+            dst_addr as *const u32,
+            buf.as_ptr() as *mut u32,
+            buf.len(),
+            true,
+            W::size(),
+            options,
+        )
+    }
+
     unsafe fn new_inner(
         channel: PeripheralRef<'a, AnyChannel>,
         request: Request,
@@ -235,21 +260,22 @@ impl<'a> Transfer<'a> {
         ch.tr1().write(|w| {
             w.set_sdw(data_size.into());
             w.set_ddw(data_size.into());
-            w.set_sinc(dir == Dir::MemoryToPeripheral && incr_mem);
+            w.set_sinc(dir != Dir::PeripheralToMemory && incr_mem);
             w.set_dinc(dir == Dir::PeripheralToMemory && incr_mem);
         });
         ch.tr2().write(|w| {
             w.set_dreq(match dir {
-                Dir::MemoryToPeripheral => vals::Dreq::DESTINATIONPERIPHERAL,
+                Dir::MemoryToPeripheral | Dir::MemoryToMemory => vals::Dreq::DESTINATIONPERIPHERAL,
                 Dir::PeripheralToMemory => vals::Dreq::SOURCEPERIPHERAL,
             });
             w.set_reqsel(request);
+            w.set_swreq(dir == Dir::MemoryToMemory);
         });
         ch.tr3().write(|_| {}); // no address offsets.
         ch.br1().write(|w| w.set_bndt(bndt));
 
         match dir {
-            Dir::MemoryToPeripheral => {
+            Dir::MemoryToPeripheral | Dir::MemoryToMemory => {
                 ch.sar().write_value(mem_addr as _);
                 ch.dar().write_value(peri_addr as _);
             }
@@ -31,6 +31,7 @@ use crate::interrupt;
 enum Dir {
     MemoryToPeripheral,
     PeripheralToMemory,
+    MemoryToMemory,
 }
 
 /// DMA request type alias. (also known as DMA channel number in some chips)
@@ -93,7 +94,7 @@ pub struct AnyChannel {
 impl_peripheral!(AnyChannel);
 
 impl AnyChannel {
-    fn info(&self) -> &ChannelInfo {
+    pub(crate) fn info(&self) -> &ChannelInfo {
         &crate::_generated::DMA_CHANNELS[self.id as usize]
     }
 }
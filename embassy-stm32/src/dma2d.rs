@@ -0,0 +1,534 @@
+//! DMA2D - Chrom-ART Accelerator
+//!
+//! 2D DMA engine for filling rectangles with a color, copying images with pixel format conversion
+//! and alpha blending two images, typically into an LTDC framebuffer.
+//!
+//! The DMA2D accesses memory directly: on chips with a data cache, buffers must be placed in
+//! non-cacheable memory, or the cache must be cleaned before and invalidated after each transfer.
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_internal::drop::OnDrop;
+use embassy_hal_internal::{into_ref, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::interrupt::typelevel::Interrupt;
+use crate::pac::dma2d::vals;
+use crate::{interrupt, peripherals, rcc, Peripheral};
+
+static DMA2D_WAKER: AtomicWaker = AtomicWaker::new();
+
+/// DMA2D error
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// Transfer error. Generated when a bus error occurs
+    TransferError,
+    /// Configuration error. Generated when the transfer parameters are invalid
+    ConfigurationError,
+}
+
+/// Pixel format of a DMA2D surface
+#[repr(u8)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ColorMode {
+    /// ARGB8888
+    ARGB8888 = 0,
+    /// RGB888
+    RGB888 = 1,
+    /// RGB565
+    RGB565 = 2,
+    /// ARGB1555
+    ARGB1555 = 3,
+    /// ARGB4444
+    ARGB4444 = 4,
+    /// L8 (8-bit luminance). Only valid as a source
+    L8 = 5,
+    /// AL44 (4-bit alpha, 4-bit luminance). Only valid as a source
+    AL44 = 6,
+    /// AL88 (8-bit alpha, 8-bit luminance). Only valid as a source
+    AL88 = 7,
+    /// A8 (8-bit alpha). Only valid as a source
+    A8 = 9,
+}
+
+impl ColorMode {
+    /// Number of bytes per pixel
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            ColorMode::ARGB8888 => 4,
+            ColorMode::RGB888 => 3,
+            ColorMode::RGB565 | ColorMode::ARGB1555 | ColorMode::ARGB4444 | ColorMode::AL88 => 2,
+            ColorMode::L8 | ColorMode::AL44 | ColorMode::A8 => 1,
+        }
+    }
+
+    fn is_output(&self) -> bool {
+        (*self as u8) <= ColorMode::ARGB4444 as u8
+    }
+}
+
+/// A rectangle of pixels in memory, used as source or destination of a DMA2D transfer.
+pub struct Surface<'a> {
+    addr: *mut u8,
+    width: u16,
+    height: u16,
+    stride: u16,
+    format: ColorMode,
+    _phantom: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> Surface<'a> {
+    /// Create a surface covering `buf`, which holds `height` lines of `width` pixels.
+    pub fn new(buf: &'a mut [u8], width: u16, height: u16, format: ColorMode) -> Self {
+        assert!(buf.len() >= width as usize * height as usize * format.bytes_per_pixel());
+        Self {
+            addr: buf.as_mut_ptr(),
+            width,
+            height,
+            stride: width,
+            format,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Create a surface from a raw address.
+    ///
+    /// `stride` is the distance between the start of two lines, in pixels.
+    ///
+    /// # Safety
+    ///
+    /// The memory must be valid for reads and writes for `height` lines of `stride` pixels, and not
+    /// be accessed by anything else for the lifetime of the surface.
+    pub unsafe fn from_raw(addr: *mut u8, width: u16, height: u16, stride: u16, format: ColorMode) -> Self {
+        assert!(width <= stride);
+        Self {
+            addr,
+            width,
+            height,
+            stride,
+            format,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Get the sub-rectangle of `width` x `height` pixels with its top left corner at `x`, `y`.
+    pub fn area(&mut self, x: u16, y: u16, width: u16, height: u16) -> Surface<'_> {
+        assert!(x as u32 + width as u32 <= self.width as u32);
+        assert!(y as u32 + height as u32 <= self.height as u32);
+        let offset = (y as usize * self.stride as usize + x as usize) * self.format.bytes_per_pixel();
+        Surface {
+            addr: unsafe { self.addr.add(offset) },
+            width,
+            height,
+            stride: self.stride,
+            format: self.format,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Width in pixels
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    /// Height in pixels
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// Pixel format
+    pub fn format(&self) -> ColorMode {
+        self.format
+    }
+
+    fn line_offset(&self) -> u16 {
+        self.stride - self.width
+    }
+}
+
+/// DMA2D driver.
+pub struct Dma2d<'d, T: Instance> {
+    _peri: PeripheralRef<'d, T>,
+}
+
+/// DMA2D interrupt handler.
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        T::regs().cr().modify(|w| {
+            w.set_tcie(false);
+            w.set_teie(false);
+            w.set_ceie(false);
+        });
+        DMA2D_WAKER.wake();
+    }
+}
+
+impl<'d, T: Instance> Dma2d<'d, T> {
+    /// Create a new DMA2D driver.
+    pub fn new(
+        peri: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+    ) -> Self {
+        into_ref!(peri);
+        rcc::enable_and_reset::<T>();
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        Self { _peri: peri }
+    }
+
+    /// Fill `dst` with `color`, given in the pixel format of `dst`.
+    pub async fn fill(&mut self, dst: &mut Surface<'_>, color: u32) -> Result<(), Error> {
+        self.setup_fill(dst, color);
+        self.run().await
+    }
+
+    /// Copy `src` to `dst`, converting the pixel format if they differ.
+    ///
+    /// Both surfaces must have the same size.
+    pub async fn blit(&mut self, src: &Surface<'_>, dst: &mut Surface<'_>) -> Result<(), Error> {
+        self.setup_blit(src, dst);
+        self.run().await
+    }
+
+    /// Blend `fg` over `bg` into `dst`, with the foreground alpha multiplied by `alpha`.
+    ///
+    /// `bg` and `dst` may be the same memory. All surfaces must have the same size.
+    pub async fn blend(
+        &mut self,
+        fg: &Surface<'_>,
+        bg: &Surface<'_>,
+        dst: &mut Surface<'_>,
+        alpha: u8,
+    ) -> Result<(), Error> {
+        self.setup_blend(fg, bg, dst, alpha);
+        self.run().await
+    }
+
+    /// Blocking version of [`Self::fill`].
+    pub fn blocking_fill(&mut self, dst: &mut Surface<'_>, color: u32) -> Result<(), Error> {
+        self.setup_fill(dst, color);
+        self.blocking_run()
+    }
+
+    /// Blocking version of [`Self::blit`].
+    pub fn blocking_blit(&mut self, src: &Surface<'_>, dst: &mut Surface<'_>) -> Result<(), Error> {
+        self.setup_blit(src, dst);
+        self.blocking_run()
+    }
+
+    /// Blocking version of [`Self::blend`].
+    pub fn blocking_blend(
+        &mut self,
+        fg: &Surface<'_>,
+        bg: &Surface<'_>,
+        dst: &mut Surface<'_>,
+        alpha: u8,
+    ) -> Result<(), Error> {
+        self.setup_blend(fg, bg, dst, alpha);
+        self.blocking_run()
+    }
+
+    fn setup_fill(&mut self, dst: &mut Surface<'_>, color: u32) {
+        let r = T::regs();
+        r.cr().modify(|w| w.set_mode(vals::Mode::REGISTERTOMEMORY));
+        r.ocolr().write_value(crate::pac::dma2d::regs::Ocolr(color));
+        self.setup_output(dst);
+    }
+
+    fn setup_blit(&mut self, src: &Surface<'_>, dst: &mut Surface<'_>) {
+        assert!(src.width == dst.width && src.height == dst.height);
+        let r = T::regs();
+        let mode = if src.format == dst.format {
+            vals::Mode::MEMORYTOMEMORY
+        } else {
+            vals::Mode::MEMORYTOMEMORYPFC
+        };
+        r.cr().modify(|w| w.set_mode(mode));
+        r.fgmar().write(|w| w.set_ma(src.addr as u32));
+        r.fgor().write(|w| w.set_lo(src.line_offset()));
+        r.fgpfccr().write(|w| {
+            w.set_cm(src.format as u8);
+            w.set_am(0);
+            w.set_alpha(0xFF);
+        });
+        self.setup_output(dst);
+    }
+
+    fn setup_blend(&mut self, fg: &Surface<'_>, bg: &Surface<'_>, dst: &mut Surface<'_>, alpha: u8) {
+        assert!(fg.width == dst.width && fg.height == dst.height);
+        assert!(bg.width == dst.width && bg.height == dst.height);
+        let r = T::regs();
+        r.cr().modify(|w| w.set_mode(vals::Mode::MEMORYTOMEMORYPFCBLENDING));
+        r.fgmar().write(|w| w.set_ma(fg.addr as u32));
+        r.fgor().write(|w| w.set_lo(fg.line_offset()));
+        r.fgpfccr().write(|w| {
+            w.set_cm(fg.format as u8);
+            // multiply the pixel alpha by the constant alpha
+            w.set_am(2);
+            w.set_alpha(alpha);
+        });
+        r.bgmar().write(|w| w.set_ma(bg.addr as u32));
+        r.bgor().write(|w| w.set_lo(bg.line_offset()));
+        r.bgpfccr().write(|w| {
+            w.set_cm(bg.format as u8);
+            w.set_am(0);
+            w.set_alpha(0xFF);
+        });
+        self.setup_output(dst);
+    }
+
+    fn setup_output(&mut self, dst: &mut Surface<'_>) {
+        assert!(dst.format.is_output(), "color mode is only valid as a source");
+        let r = T::regs();
+        r.opfccr().write(|w| w.set_cm(dst.format as u8));
+        r.omar().write(|w| w.set_ma(dst.addr as u32));
+        r.oor().write(|w| w.set_lo(dst.line_offset()));
+        r.nlr().write(|w| {
+            w.set_pl(dst.width);
+            w.set_nl(dst.height);
+        });
+    }
+
+    async fn run(&mut self) -> Result<(), Error> {
+        let r = T::regs();
+
+        // abort the transfer if the future is dropped
+        let on_drop = OnDrop::new(|| {
+            r.cr().modify(|w| w.set_abort(true));
+            while r.cr().read().start() {}
+        });
+
+        Self::clear_interrupt_flags();
+        r.cr().modify(|w| {
+            w.set_tcie(true);
+            w.set_teie(true);
+            w.set_ceie(true);
+            w.set_start(true);
+        });
+
+        poll_fn(|cx| {
+            DMA2D_WAKER.register(cx.waker());
+            let isr = r.isr().read();
+            if isr.tcif() || isr.teif() || isr.ceif() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+
+        on_drop.defuse();
+        Self::result()
+    }
+
+    fn blocking_run(&mut self) -> Result<(), Error> {
+        let r = T::regs();
+        Self::clear_interrupt_flags();
+        r.cr().modify(|w| w.set_start(true));
+        loop {
+            let isr = r.isr().read();
+            if isr.tcif() || isr.teif() || isr.ceif() {
+                break;
+            }
+        }
+        Self::result()
+    }
+
+    fn result() -> Result<(), Error> {
+        let isr = T::regs().isr().read();
+        Self::clear_interrupt_flags();
+        if isr.ceif() {
+            Err(Error::ConfigurationError)
+        } else if isr.teif() {
+            Err(Error::TransferError)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn clear_interrupt_flags() {
+        T::regs().ifcr().write(|w| {
+            w.set_ctcif(true);
+            w.set_cteif(true);
+            w.set_cceif(true);
+        });
+    }
+}
+
+impl<'d, T: Instance> Drop for Dma2d<'d, T> {
+    fn drop(&mut self) {
+        T::Interrupt::disable();
+        rcc::disable::<T>();
+    }
+}
+
+#[cfg(feature = "embedded-graphics-core")]
+pub use framebuffer::{Dma2dColor, Framebuffer};
+
+#[cfg(feature = "embedded-graphics-core")]
+mod framebuffer {
+    use embedded_graphics_core::draw_target::DrawTarget;
+    use embedded_graphics_core::geometry::{OriginDimensions, Point, Size};
+    use embedded_graphics_core::pixelcolor::{IntoStorage, PixelColor, Rgb565, Rgb888};
+    use embedded_graphics_core::primitives::Rectangle;
+    use embedded_graphics_core::Pixel;
+
+    use super::{ColorMode, Dma2d, Error, Instance, Surface};
+
+    trait SealedDma2dColor {}
+
+    /// Color type that can be used in a DMA2D accelerated [`Framebuffer`].
+    #[allow(private_bounds)]
+    pub trait Dma2dColor: SealedDma2dColor + PixelColor {
+        /// Storage of one pixel in the framebuffer
+        type Word: Copy;
+        /// Pixel format of the framebuffer
+        const FORMAT: ColorMode;
+        /// Convert the color to its raw value in the pixel format
+        fn to_word(self) -> Self::Word;
+        /// Convert the color to the value of the DMA2D output color register
+        fn to_register(self) -> u32;
+    }
+
+    impl SealedDma2dColor for Rgb565 {}
+    impl Dma2dColor for Rgb565 {
+        type Word = u16;
+        const FORMAT: ColorMode = ColorMode::RGB565;
+        fn to_word(self) -> u16 {
+            self.into_storage()
+        }
+        fn to_register(self) -> u32 {
+            self.into_storage() as u32
+        }
+    }
+
+    impl SealedDma2dColor for Rgb888 {}
+    impl Dma2dColor for Rgb888 {
+        type Word = u32;
+        const FORMAT: ColorMode = ColorMode::ARGB8888;
+        fn to_word(self) -> u32 {
+            // opaque, so that the LTDC does not blend it away
+            0xFF00_0000 | self.into_storage()
+        }
+        fn to_register(self) -> u32 {
+            self.to_word()
+        }
+    }
+
+    /// Framebuffer implementing `embedded-graphics` [`DrawTarget`], with rectangle fills done by the DMA2D.
+    ///
+    /// [`Rgb888`] framebuffers are stored as opaque ARGB8888, [`Rgb565`] framebuffers as RGB565. Drawing
+    /// blocks until the DMA2D is done.
+    pub struct Framebuffer<'a, 'd, T: Instance, C: Dma2dColor> {
+        dma2d: &'a mut Dma2d<'d, T>,
+        buf: &'a mut [C::Word],
+        width: u16,
+        height: u16,
+    }
+
+    impl<'a, 'd, T: Instance, C: Dma2dColor> Framebuffer<'a, 'd, T, C> {
+        /// Create a framebuffer of `width` x `height` pixels backed by `buf`.
+        pub fn new(dma2d: &'a mut Dma2d<'d, T>, buf: &'a mut [C::Word], width: u16, height: u16) -> Self {
+            assert!(buf.len() >= width as usize * height as usize);
+            Self {
+                dma2d,
+                buf,
+                width,
+                height,
+            }
+        }
+
+        /// Get the underlying buffer, e.g. to hand it to the LTDC.
+        pub fn buffer(&self) -> &[C::Word] {
+            self.buf
+        }
+
+        /// Copy `src` into the area of the framebuffer with its top left corner at `x`, `y`, converting the pixel format.
+        pub async fn blit(&mut self, src: &Surface<'_>, x: u16, y: u16) -> Result<(), Error> {
+            let mut surface = surface::<C>(self.buf, self.width, self.height);
+            let mut dst = surface.area(x, y, src.width(), src.height());
+            self.dma2d.blit(src, &mut dst).await
+        }
+    }
+
+    fn surface<C: Dma2dColor>(buf: &mut [C::Word], width: u16, height: u16) -> Surface<'_> {
+        // SAFETY: the buffer is exclusively borrowed by the returned surface and was checked to be large enough.
+        unsafe { Surface::from_raw(buf.as_mut_ptr() as *mut u8, width, height, width, C::FORMAT) }
+    }
+
+    impl<'a, 'd, T: Instance, C: Dma2dColor> OriginDimensions for Framebuffer<'a, 'd, T, C> {
+        fn size(&self) -> Size {
+            Size::new(self.width as u32, self.height as u32)
+        }
+    }
+
+    impl<'a, 'd, T: Instance, C: Dma2dColor> DrawTarget for Framebuffer<'a, 'd, T, C> {
+        type Color = C;
+        type Error = Error;
+
+        fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+        where
+            I: IntoIterator<Item = Pixel<Self::Color>>,
+        {
+            let (width, height) = (self.width as i32, self.height as i32);
+            for Pixel(Point { x, y }, color) in pixels {
+                if x >= 0 && y >= 0 && x < width && y < height {
+                    self.buf[(y * width + x) as usize] = color.to_word();
+                }
+            }
+            Ok(())
+        }
+
+        fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+            let area = area.intersection(&self.bounding_box());
+            let Some(bottom_right) = area.bottom_right() else {
+                return Ok(());
+            };
+            let (x, y) = (area.top_left.x as u16, area.top_left.y as u16);
+            let (w, h) = ((bottom_right.x + 1) as u16 - x, (bottom_right.y + 1) as u16 - y);
+
+            let mut surface = surface::<C>(self.buf, self.width, self.height);
+            let mut dst = surface.area(x, y, w, h);
+            self.dma2d.blocking_fill(&mut dst, color.to_register())
+        }
+
+        fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+            self.fill_solid(&self.bounding_box(), color)
+        }
+    }
+}
+
+trait SealedInstance: crate::rcc::SealedRccPeripheral {
+    fn regs() -> crate::pac::dma2d::Dma2d;
+}
+
+/// DMA2D instance trait.
+#[allow(private_bounds)]
+pub trait Instance: SealedInstance + Peripheral<P = Self> + crate::rcc::RccPeripheral + 'static + Send {
+    /// Interrupt for this DMA2D instance.
+    type Interrupt: interrupt::typelevel::Interrupt;
+}
+
+foreach_interrupt!(
+    ($inst:ident, dma2d, DMA2D, GLOBAL, $irq:ident) => {
+        impl Instance for peripherals::$inst {
+            type Interrupt = crate::interrupt::typelevel::$irq;
+        }
+
+        impl SealedInstance for peripherals::$inst {
+            fn regs() -> crate::pac::dma2d::Dma2d {
+                crate::pac::$inst
+            }
+        }
+    };
+);
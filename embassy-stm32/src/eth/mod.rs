@@ -157,6 +157,16 @@ impl<'a, 'd> embassy_net_driver::TxToken for TxToken<'a, 'd> {
     }
 }
 
+/// PTP system time, as used for frame timestamps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PtpTimestamp {
+    /// Seconds.
+    pub seconds: u32,
+    /// Nanoseconds, below 1_000_000_000.
+    pub nanoseconds: u32,
+}
+
 /// Station Management Interface (SMI) on an ethernet PHY
 ///
 /// # Safety
@@ -228,3 +238,4 @@ pin_trait!(TXD1Pin, Instance);
 pin_trait!(TXD2Pin, Instance);
 pin_trait!(TXD3Pin, Instance);
 pin_trait!(TXEnPin, Instance);
+pin_trait!(PPSOutPin, Instance);
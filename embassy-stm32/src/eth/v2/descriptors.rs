@@ -1,8 +1,8 @@
-use core::sync::atomic::{fence, Ordering};
+use core::sync::atomic::{fence, AtomicU32, Ordering};
 
 use vcell::VolatileCell;
 
-use crate::eth::{Packet, RX_BUFFER_SIZE, TX_BUFFER_SIZE};
+use crate::eth::{Packet, PtpTimestamp, RX_BUFFER_SIZE, TX_BUFFER_SIZE};
 use crate::pac::ETH;
 
 /// Transmit and Receive Descriptor fields
@@ -16,7 +16,11 @@ mod emac_consts {
     pub const EMAC_DES0_BUF1AP: u32 = 0xFFFF_FFFF;
 
     pub const EMAC_TDES2_IOC: u32 = 0x8000_0000;
+    pub const EMAC_TDES2_TTSE: u32 = 0x4000_0000;
     pub const EMAC_TDES2_B1L: u32 = 0x0000_3FFF;
+    pub const EMAC_TDES3_TTSS: u32 = 0x0002_0000;
+
+    pub const EMAC_RDES1_TSA: u32 = 0x0000_4000;
 
     pub const EMAC_RDES3_IOC: u32 = 0x4000_0000;
     pub const EMAC_RDES3_PL: u32 = 0x0000_7FFF;
@@ -53,14 +57,36 @@ impl TDes {
     fn available(&self) -> bool {
         self.tdes3.get() & EMAC_DES3_OWN == 0
     }
+
+    /// Return the transmit timestamp written back by the DMA, if any
+    fn timestamp(&self) -> Option<PtpTimestamp> {
+        if self.tdes3.get() & EMAC_TDES3_TTSS != 0 {
+            Some(PtpTimestamp {
+                seconds: self.tdes1.get(),
+                nanoseconds: self.tdes0.get(),
+            })
+        } else {
+            None
+        }
+    }
 }
 
+/// Number of frames transmitted so far, wrapping. Frame `n` uses descriptor `n % len`.
+///
+/// This is shared with [`TxTimestamps`], so it lives outside of the ring. There is a single
+/// Ethernet peripheral, so a single counter.
+static TX_FRAMES_SENT: AtomicU32 = AtomicU32::new(0);
+
 pub(crate) struct TDesRing<'a> {
-    descriptors: &'a mut [TDes],
+    descriptors: &'a [TDes],
     buffers: &'a mut [Packet<TX_BUFFER_SIZE>],
     index: usize,
 }
 
+// Safety: the descriptors are shared with `TxTimestamps`, which only reads them. They are
+// only written by the ring.
+unsafe impl<'a> Send for TDesRing<'a> {}
+
 impl<'a> TDesRing<'a> {
     /// Initialise this TDesRing. Assume TDesRing is corrupt.
     pub fn new(descriptors: &'a mut [TDes], buffers: &'a mut [Packet<TX_BUFFER_SIZE>]) -> Self {
@@ -70,6 +96,7 @@ impl<'a> TDesRing<'a> {
         for td in descriptors.iter_mut() {
             *td = TDes::new();
         }
+        TX_FRAMES_SENT.store(0, Ordering::Relaxed);
 
         // Initialize the pointers in the DMA engine. (There will be a memory barrier later
         // before the DMA engine is enabled.)
@@ -91,7 +118,7 @@ impl<'a> TDesRing<'a> {
 
     /// Return the next available packet buffer for transmitting, or None
     pub(crate) fn available(&mut self) -> Option<&mut [u8]> {
-        let d = &mut self.descriptors[self.index];
+        let d = &self.descriptors[self.index];
         if d.available() {
             Some(&mut self.buffers[self.index].0)
         } else {
@@ -101,13 +128,39 @@ impl<'a> TDesRing<'a> {
 
     /// Transmit the packet written in a buffer returned by `available`.
     pub(crate) fn transmit(&mut self, len: usize) {
-        let td = &mut self.descriptors[self.index];
+        self.transmit_inner(len, false);
+    }
+
+    /// Transmit the packet written in a buffer returned by `available`, asking the MAC to
+    /// timestamp it. Returns the frame number to look the timestamp up with.
+    pub(crate) fn transmit_timestamped(&mut self, len: usize) -> u32 {
+        self.transmit_inner(len, true)
+    }
+
+    /// Return a handle to look transmit timestamps up, independently of the ring.
+    pub(crate) fn timestamps(&self) -> TxTimestamps<'a> {
+        TxTimestamps {
+            descriptors: self.descriptors,
+        }
+    }
+
+    fn transmit_inner(&mut self, len: usize, timestamp: bool) -> u32 {
+        let td = &self.descriptors[self.index];
         assert!(td.available());
         assert!(len as u32 <= EMAC_TDES2_B1L);
 
+        // Retire the previous frame of this descriptor before reusing it, so `TxTimestamps` never
+        // mixes its write-back with the new frame.
+        td.tdes3.set(0);
+        fence(Ordering::Release);
+        let frame = TX_FRAMES_SENT.fetch_add(1, Ordering::Relaxed);
+        fence(Ordering::Release);
+
         // Read format
         td.tdes0.set(self.buffers[self.index].0.as_ptr() as u32);
-        td.tdes2.set(len as u32 & EMAC_TDES2_B1L | EMAC_TDES2_IOC);
+        td.tdes1.set(0);
+        let ttse = if timestamp { EMAC_TDES2_TTSE } else { 0 };
+        td.tdes2.set(len as u32 & EMAC_TDES2_B1L | EMAC_TDES2_IOC | ttse);
 
         // FD: Contains first buffer of packet
         // LD: Contains last buffer of packet
@@ -124,6 +177,40 @@ impl<'a> TDesRing<'a> {
         ETH.ethernet_dma().dmactx_dtpr().write(|w| w.0 = &td as *const _ as u32);
 
         self.index = (self.index + 1) % self.descriptors.len();
+        frame
+    }
+}
+
+/// Read-only view of the transmit descriptors, to fetch transmit timestamps while the ring is
+/// owned by someone else.
+pub(crate) struct TxTimestamps<'a> {
+    descriptors: &'a [TDes],
+}
+
+// Safety: the descriptors are only read, with volatile accesses. The ring bumps the frame counter
+// before rewriting a descriptor, so a read racing a reuse is detected and discarded.
+unsafe impl<'a> Send for TxTimestamps<'a> {}
+
+impl<'a> TxTimestamps<'a> {
+    /// Return the transmit timestamp of frame `frame`, if it has been sent and its descriptor
+    /// hasn't been reused since.
+    pub(crate) fn get(&self, frame: u32) -> Option<PtpTimestamp> {
+        let len = self.descriptors.len();
+        let in_ring = || {
+            // Frame numbers not handed out yet, or whose descriptor was reused by a newer frame.
+            let age = TX_FRAMES_SENT.load(Ordering::Acquire).wrapping_sub(frame) as usize;
+            age != 0 && age <= len
+        };
+        if !in_ring() {
+            return None;
+        }
+
+        let d = &self.descriptors[frame as usize % len];
+        let timestamp = if d.available() { d.timestamp() } else { None };
+
+        // The descriptor may have been reused for a newer frame while it was read.
+        fence(Ordering::Acquire);
+        timestamp.filter(|_| in_ring())
     }
 }
 
@@ -168,6 +255,18 @@ impl RDes {
         self.rdes3.get() & EMAC_DES3_OWN == 0 // Owned by us
     }
 
+    /// Return true if this is a context descriptor, holding the timestamp of the previous packet
+    #[inline(always)]
+    fn is_context(&self) -> bool {
+        self.rdes3.get() & EMAC_DES3_CTXT != 0
+    }
+
+    /// Return true if the timestamp of this packet is in the following context descriptor
+    #[inline(always)]
+    fn has_timestamp(&self) -> bool {
+        self.rdes1.get() & EMAC_RDES1_TSA != 0
+    }
+
     #[inline(always)]
     fn set_ready(&mut self, buf: *mut u8) {
         self.rdes0.set(buf as u32);
@@ -218,6 +317,12 @@ impl<'a> RDesRing<'a> {
                 return None;
             }
 
+            // Skip context descriptors whose packet was already popped.
+            if descriptor.is_context() {
+                self.pop_packet();
+                continue;
+            }
+
             // If packet is invalid, pop it and try again.
             if !descriptor.valid() {
                 warn!("invalid packet: {:08x}", descriptor.rdes0.get());
@@ -225,6 +330,11 @@ impl<'a> RDesRing<'a> {
                 continue;
             }
 
+            // Wait for the context descriptor holding the timestamp to be written back.
+            if descriptor.has_timestamp() && !self.descriptors[self.next_index()].available() {
+                return None;
+            }
+
             break;
         }
 
@@ -233,11 +343,42 @@ impl<'a> RDesRing<'a> {
         return Some(&mut self.buffers[self.index].0[..len]);
     }
 
+    fn next_index(&self) -> usize {
+        (self.index + 1) % self.descriptors.len()
+    }
+
+    /// Return the receive timestamp of the packet returned by `available`, if it has one.
+    pub(crate) fn timestamp(&self) -> Option<PtpTimestamp> {
+        let rd = &self.descriptors[self.index];
+        let ctx = &self.descriptors[self.next_index()];
+        if rd.available() && rd.valid() && rd.has_timestamp() && ctx.available() && ctx.is_context() {
+            Some(PtpTimestamp {
+                seconds: ctx.rdes1.get(),
+                nanoseconds: ctx.rdes0.get(),
+            })
+        } else {
+            None
+        }
+    }
+
     /// Pop the packet previously returned by `available`.
     pub(crate) fn pop_packet(&mut self) {
-        let rd = &mut self.descriptors[self.index];
+        let rd = &self.descriptors[self.index];
         assert!(rd.available());
 
+        // Release the context descriptor holding the timestamp along with the packet.
+        let pop_context = self.timestamp().is_some();
+
+        self.release();
+        if pop_context {
+            self.release();
+        }
+    }
+
+    /// Give the current descriptor back to the DMA and move to the next one.
+    fn release(&mut self) {
+        let rd = &mut self.descriptors[self.index];
+
         rd.set_ready(self.buffers[self.index].0.as_mut_ptr());
 
         // "Preceding reads and writes cannot be moved past subsequent writes."
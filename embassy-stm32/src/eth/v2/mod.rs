@@ -1,4 +1,5 @@
 mod descriptors;
+mod ptp;
 
 use core::marker::PhantomData;
 use core::sync::atomic::{fence, Ordering};
@@ -7,6 +8,7 @@ use embassy_hal_internal::{into_ref, PeripheralRef};
 use stm32_metapac::syscfg::vals::EthSelPhy;
 
 pub(crate) use self::descriptors::{RDes, RDesRing, TDes, TDesRing};
+pub use self::ptp::{Ptp, PtpConfig, TxTimestampId};
 use super::*;
 use crate::gpio::{AfType, AnyPin, OutputType, SealedPin as _, Speed};
 use crate::interrupt::InterruptExt;
@@ -41,6 +43,7 @@ pub struct Ethernet<'d, T: Instance, P: PHY> {
     pub(crate) tx: TDesRing<'d>,
     pub(crate) rx: RDesRing<'d>,
     pins: Pins<'d>,
+    pps: Option<PeripheralRef<'d, AnyPin>>,
     pub(crate) phy: P,
     pub(crate) station_management: EthernetStationManagement<T>,
     pub(crate) mac_addr: [u8; 6],
@@ -258,6 +261,7 @@ impl<'d, T: Instance, P: PHY> Ethernet<'d, T, P> {
             tx: TDesRing::new(&mut queue.tx_desc, &mut queue.tx_buf),
             rx: RDesRing::new(&mut queue.rx_desc, &mut queue.rx_buf),
             pins,
+            pps: None,
             phy,
             station_management: EthernetStationManagement {
                 peri: PhantomData,
@@ -367,6 +371,9 @@ impl<'d, T: Instance, P: PHY> Drop for Ethernet<'d, T, P> {
             } {
                 pin.set_as_disconnected();
             }
+            if let Some(pin) = self.pps.as_mut() {
+                pin.set_as_disconnected();
+            }
         })
     }
 }
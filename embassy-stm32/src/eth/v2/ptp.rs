@@ -0,0 +1,233 @@
+//! Precision Time Protocol (IEEE 1588) hardware clock and timestamping.
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use embassy_hal_internal::into_ref;
+
+use super::descriptors::TxTimestamps;
+use super::Ethernet;
+use crate::eth::{Instance, PPSOutPin, PtpTimestamp, RxToken, TxToken, PHY};
+use crate::gpio::{AfType, OutputType, Speed};
+use crate::rcc::SealedRccPeripheral;
+use crate::Peripheral;
+
+/// Frequency the system time is advanced at, after fine correction by the addend.
+const PTP_CLOCK_HZ: u32 = 50_000_000;
+/// Sub-second increment, in nanoseconds, added every `PTP_CLOCK_HZ` tick.
+const SUB_SECOND_INCREMENT: u8 = (1_000_000_000 / PTP_CLOCK_HZ) as u8;
+
+const NANOS_PER_SECOND: u32 = 1_000_000_000;
+
+/// Addend matching the nominal frequency, used as the reference for frequency adjustments.
+static BASE_ADDEND: AtomicU32 = AtomicU32::new(0);
+
+/// PTP configuration.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug)]
+pub struct PtpConfig {
+    /// Timestamp all received frames instead of only PTP event messages (Sync, Delay_Req,
+    /// Pdelay_Req and Pdelay_Resp), over both Ethernet and UDP/IPv4/IPv6.
+    pub timestamp_all_frames: bool,
+    /// Initial value of the system time.
+    pub initial_time: PtpTimestamp,
+}
+
+impl Default for PtpConfig {
+    fn default() -> Self {
+        Self {
+            timestamp_all_frames: false,
+            initial_time: PtpTimestamp {
+                seconds: 0,
+                nanoseconds: 0,
+            },
+        }
+    }
+}
+
+impl<'d, T: Instance, P: PHY> Ethernet<'d, T, P> {
+    /// Enable the PTP block: start the system time and timestamp frames.
+    ///
+    /// The system time is clocked from HCLK, which must be at least 50 MHz, and is corrected
+    /// with the fine update method so its frequency can be adjusted with [`Ptp::adjust_frequency`].
+    ///
+    /// Once enabled, received frames selected by [`PtpConfig::timestamp_all_frames`] are
+    /// timestamped, see [`RxToken::timestamp`]. Transmitted frames are only timestamped when sent
+    /// with [`TxToken::consume_timestamped`]. The returned [`Ptp`] handle controls the clock and
+    /// can be kept after the driver has been handed over to `embassy-net`.
+    pub fn enable_ptp(&mut self, config: PtpConfig) -> Ptp<'d, T> {
+        let mac = T::regs().ethernet_mac();
+        let hclk = <T as SealedRccPeripheral>::frequency().0;
+        assert!(hclk >= PTP_CLOCK_HZ, "HCLK must be at least 50 MHz for PTP");
+
+        // Mask the timestamp trigger interrupt, it is not used.
+        mac.macier().modify(|w| w.set_tsie(false));
+
+        mac.mactscr().write(|w| {
+            w.set_tsena(true);
+            w.set_tscfupdt(true);
+            // Sub-second register rolls over at 999_999_999 ns
+            w.set_tsctrlssr(true);
+            w.set_tsver2ena(true);
+            if config.timestamp_all_frames {
+                w.set_tsenall(true);
+            } else {
+                // Event messages only
+                w.set_tsevntena(true);
+                w.set_tsipena(true);
+                w.set_tsipv4ena(true);
+                w.set_tsipv6ena(true);
+            }
+        });
+
+        mac.macssir().write(|w| w.set_ssinc(SUB_SECOND_INCREMENT));
+
+        // addend = 2^32 * PTP_CLOCK_HZ / HCLK
+        let addend = ((PTP_CLOCK_HZ as u64) << 32) / hclk as u64;
+        BASE_ADDEND.store(addend as u32, Ordering::Relaxed);
+        let ptp = Ptp {
+            tx_timestamps: self.tx.timestamps(),
+            _phantom: PhantomData,
+        };
+        ptp.set_addend(addend as u32);
+        ptp.set_time(config.initial_time);
+
+        ptp
+    }
+
+    /// Output the PPS (pulse per second) signal of the system time on `pin`.
+    ///
+    /// `log2_hz` selects the output: 0 is a 1 Hz pulse, `n` in `1..=15` is a `2^n` Hz clock derived
+    /// from the system time.
+    pub fn enable_pps_output(&mut self, pin: impl Peripheral<P = impl PPSOutPin<T>> + 'd, log2_hz: u8) {
+        assert!(log2_hz <= 15);
+        into_ref!(pin);
+        critical_section::with(|_| {
+            pin.set_as_af(pin.af_num(), AfType::output(OutputType::PushPull, Speed::VeryHigh));
+        });
+        self.pps = Some(pin.map_into());
+
+        T::regs().ethernet_mac().macppscr().modify(|w| w.set_ppsctrl(log2_hz));
+    }
+}
+
+/// Handle to the PTP system time and transmit timestamps, created by [`Ethernet::enable_ptp`].
+pub struct Ptp<'d, T: Instance> {
+    tx_timestamps: TxTimestamps<'d>,
+    _phantom: PhantomData<T>,
+}
+
+impl<'d, T: Instance> Ptp<'d, T> {
+    /// Get the current system time.
+    pub fn now(&self) -> PtpTimestamp {
+        let mac = T::regs().ethernet_mac();
+        // Re-read the seconds in case the nanoseconds rolled over in between.
+        loop {
+            let seconds = mac.macstsr().read().tss();
+            let nanoseconds = mac.macstnr().read().tsss();
+            if mac.macstsr().read().tss() == seconds {
+                return PtpTimestamp { seconds, nanoseconds };
+            }
+        }
+    }
+
+    /// Set the system time.
+    pub fn set_time(&self, time: PtpTimestamp) {
+        let mac = T::regs().ethernet_mac();
+        while mac.mactscr().read().tsinit() {}
+
+        mac.macstsur().write(|w| w.set_tss(time.seconds));
+        mac.macstnur().write(|w| w.set_tsss(time.nanoseconds));
+        mac.mactscr().modify(|w| w.set_tsinit(true));
+    }
+
+    /// Step the system time by `offset_ns` nanoseconds.
+    pub fn adjust_offset(&self, offset_ns: i64) {
+        let mac = T::regs().ethernet_mac();
+        while mac.mactscr().read().tsupdt() {}
+
+        let magnitude = offset_ns.unsigned_abs();
+        let mut seconds = (magnitude / NANOS_PER_SECOND as u64) as u32;
+        let mut nanoseconds = (magnitude % NANOS_PER_SECOND as u64) as u32;
+        if offset_ns < 0 {
+            // Subtraction takes the two's complement of the seconds, 2^32 - value.
+            seconds = seconds.wrapping_neg();
+            // With digital rollover, subtraction uses 10^9 - value in the sub-seconds field.
+            // A whole number of seconds is subtracted with a zero sub-seconds field, as 10^9
+            // doesn't fit it.
+            if nanoseconds != 0 {
+                nanoseconds = NANOS_PER_SECOND - nanoseconds;
+            }
+        }
+
+        mac.macstsur().write(|w| w.set_tss(seconds));
+        mac.macstnur().write(|w| {
+            w.set_addsub(offset_ns < 0);
+            w.set_tsss(nanoseconds);
+        });
+        mac.mactscr().modify(|w| w.set_tsupdt(true));
+    }
+
+    /// Get the current frequency correction addend.
+    pub fn addend(&self) -> u32 {
+        T::regs().ethernet_mac().mactsar().read().tsar()
+    }
+
+    /// Set the frequency correction addend.
+    pub fn set_addend(&self, addend: u32) {
+        let mac = T::regs().ethernet_mac();
+        while mac.mactscr().read().tsaddreg() {}
+
+        mac.mactsar().write(|w| w.set_tsar(addend));
+        mac.mactscr().modify(|w| w.set_tsaddreg(true));
+    }
+
+    /// Adjust the system time frequency by `ppb` parts per billion relative to nominal.
+    pub fn adjust_frequency(&self, ppb: i32) {
+        let base = BASE_ADDEND.load(Ordering::Relaxed) as i64;
+        let addend = base + base * ppb as i64 / NANOS_PER_SECOND as i64;
+        self.set_addend(addend.clamp(0, u32::MAX as i64) as u32);
+    }
+
+    /// Get the transmit timestamp of a frame sent with [`TxToken::consume_timestamped`].
+    ///
+    /// Returns `None` while the frame is still being sent. The timestamp is held in the frame's
+    /// descriptor, so it must be fetched before as many frames as the transmit queue is long
+    /// have been sent after it; once the descriptor is reused, `None` is returned too.
+    pub fn tx_timestamp(&self, id: TxTimestampId) -> Option<PtpTimestamp> {
+        self.tx_timestamps.get(id.0)
+    }
+}
+
+/// Identifies a frame sent with [`TxToken::consume_timestamped`], to look its transmit timestamp
+/// up with [`Ptp::tx_timestamp`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TxTimestampId(u32);
+
+impl<'a, 'd> RxToken<'a, 'd> {
+    /// Get the receive timestamp of the frame this token consumes.
+    ///
+    /// Only frames selected by [`PtpConfig::timestamp_all_frames`] are timestamped, and only once
+    /// PTP has been enabled with [`Ethernet::enable_ptp`].
+    pub fn timestamp(&self) -> Option<PtpTimestamp> {
+        self.rx.timestamp()
+    }
+}
+
+impl<'a, 'd> TxToken<'a, 'd> {
+    /// Like [`consume`](embassy_net_driver::TxToken::consume), and have the MAC timestamp the
+    /// frame when it is sent.
+    ///
+    /// The timestamp is fetched with [`Ptp::tx_timestamp`]. PTP must have been enabled with
+    /// [`Ethernet::enable_ptp`].
+    pub fn consume_timestamped<R, F>(self, len: usize, f: F) -> (R, TxTimestampId)
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        // NOTE(unwrap): we checked the queue wasn't full when creating the token.
+        let pkt = unwrap!(self.tx.available());
+        let r = f(&mut pkt[..len]);
+        let frame = self.tx.transmit_timestamped(len);
+        (r, TxTimestampId(frame))
+    }
+}
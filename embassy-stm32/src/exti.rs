@@ -227,13 +227,18 @@ impl<'d> embedded_hal_async::digital::Wait for ExtiInput<'d> {
 }
 
 #[must_use = "futures do nothing unless you `.await` or poll them"]
-struct ExtiInputFuture<'a> {
+pub(crate) struct ExtiInputFuture<'a> {
     pin: u8,
     phantom: PhantomData<&'a mut AnyPin>,
 }
 
 impl<'a> ExtiInputFuture<'a> {
-    fn new(pin: u8, port: u8, rising: bool, falling: bool) -> Self {
+    /// Arm the EXTI line of `pin` on `port` for the given edges.
+    ///
+    /// The edge is latched by the hardware as soon as this returns, so it is not missed even if
+    /// the future is polled later. This works regardless of the pin mode, e.g. for a pin used by
+    /// a peripheral as an alternate function.
+    pub(crate) fn new(pin: u8, port: u8, rising: bool, falling: bool) -> Self {
         critical_section::with(|_| {
             let pin = pin as usize;
             exticr_regs().exticr(pin / 4).modify(|w| w.set_exti(pin % 4, port));
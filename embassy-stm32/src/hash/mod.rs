@@ -19,6 +19,11 @@ use crate::interrupt::typelevel::Interrupt;
 use crate::peripherals::HASH;
 use crate::{interrupt, pac, peripherals, rcc, Peripheral};
 
+#[cfg(feature = "digest")]
+mod rustcrypto;
+#[cfg(feature = "digest")]
+pub use rustcrypto::*;
+
 #[cfg(hash_v1)]
 const NUM_CONTEXT_REGS: usize = 51;
 #[cfg(hash_v3)]
@@ -0,0 +1,102 @@
+//! [`digest`] trait implementations.
+use core::marker::PhantomData;
+
+#[cfg(any(hash_v1, hash_v2, hash_v4))]
+use digest::consts::U16;
+use digest::consts::{U20, U28, U32};
+use digest::generic_array::ArrayLength;
+use digest::{FixedOutput, FixedOutputReset, HashMarker, Output, OutputSizeUser, Reset, Update};
+
+use super::{Algorithm, Context, DataType, Hash, Instance};
+
+trait SealedDigestAlgorithm {}
+
+/// Hash algorithm usable with [`Hasher`].
+#[allow(private_bounds)]
+pub trait DigestAlgorithm: SealedDigestAlgorithm {
+    /// Algorithm of the HASH peripheral.
+    const ALGORITHM: Algorithm;
+    /// Size of the digest in bytes.
+    type OutputSize: ArrayLength<u8> + 'static;
+}
+
+macro_rules! impl_algorithm {
+    ($doc:literal, $name:ident, $algo:ident, $size:ty) => {
+        #[doc = $doc]
+        pub enum $name {}
+
+        impl SealedDigestAlgorithm for $name {}
+
+        impl DigestAlgorithm for $name {
+            const ALGORITHM: Algorithm = Algorithm::$algo;
+            type OutputSize = $size;
+        }
+    };
+}
+
+impl_algorithm!("SHA-1", Sha1, SHA1, U20);
+impl_algorithm!("SHA-224", Sha224, SHA224, U28);
+impl_algorithm!("SHA-256", Sha256, SHA256, U32);
+#[cfg(any(hash_v1, hash_v2, hash_v4))]
+impl_algorithm!("MD5", Md5, MD5, U16);
+
+/// Hash computation implementing the [`digest`] traits, created with [`Hash::hasher`].
+///
+/// Data is fed to the peripheral with the blocking methods. The `Digest` trait itself is not
+/// implemented as it requires `Default`, which a borrowed peripheral cannot provide; use the
+/// [`Update`] and [`FixedOutput`] traits instead.
+pub struct Hasher<'a, 'd, T: Instance, D, A: DigestAlgorithm> {
+    hash: &'a mut Hash<'d, T, D>,
+    ctx: Context<'static>,
+    _algorithm: PhantomData<A>,
+}
+
+impl<'d, T: Instance, D> Hash<'d, T, D> {
+    /// Start a hash computation implementing the [`digest`] traits.
+    pub fn hasher<A: DigestAlgorithm>(&mut self) -> Hasher<'_, 'd, T, D, A> {
+        let ctx = self.start(A::ALGORITHM, DataType::Width8, None);
+        Hasher {
+            hash: self,
+            ctx,
+            _algorithm: PhantomData,
+        }
+    }
+}
+
+impl<'a, 'd, T: Instance, D, A: DigestAlgorithm> Hasher<'a, 'd, T, D, A> {
+    fn restart(&mut self) -> Context<'static> {
+        let ctx = self.hash.start(A::ALGORITHM, DataType::Width8, None);
+        core::mem::replace(&mut self.ctx, ctx)
+    }
+}
+
+impl<'a, 'd, T: Instance, D, A: DigestAlgorithm> HashMarker for Hasher<'a, 'd, T, D, A> {}
+
+impl<'a, 'd, T: Instance, D, A: DigestAlgorithm> OutputSizeUser for Hasher<'a, 'd, T, D, A> {
+    type OutputSize = A::OutputSize;
+}
+
+impl<'a, 'd, T: Instance, D, A: DigestAlgorithm> Update for Hasher<'a, 'd, T, D, A> {
+    fn update(&mut self, data: &[u8]) {
+        self.hash.update_blocking(&mut self.ctx, data);
+    }
+}
+
+impl<'a, 'd, T: Instance, D, A: DigestAlgorithm> FixedOutput for Hasher<'a, 'd, T, D, A> {
+    fn finalize_into(self, out: &mut Output<Self>) {
+        self.hash.finish_blocking(self.ctx, out);
+    }
+}
+
+impl<'a, 'd, T: Instance, D, A: DigestAlgorithm> Reset for Hasher<'a, 'd, T, D, A> {
+    fn reset(&mut self) {
+        self.restart();
+    }
+}
+
+impl<'a, 'd, T: Instance, D, A: DigestAlgorithm> FixedOutputReset for Hasher<'a, 'd, T, D, A> {
+    fn finalize_into_reset(&mut self, out: &mut Output<Self>) {
+        let ctx = self.restart();
+        self.hash.finish_blocking(ctx, out);
+    }
+}
@@ -22,7 +22,7 @@ use crate::interrupt::typelevel::Interrupt;
 use crate::mode::{Async, Blocking, Mode};
 use crate::rcc::{RccInfo, SealedRccPeripheral};
 use crate::time::Hertz;
-use crate::{interrupt, peripherals};
+use crate::{blocking_delay_us, interrupt, peripherals};
 
 /// I2C error.
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
@@ -108,11 +108,61 @@ impl Config {
     }
 }
 
+/// I2C slave (target) configuration.
+#[cfg(any(i2c_v2, i2c_v3))]
+#[derive(Copy, Clone)]
+pub struct SlaveConfig {
+    /// Own 7-bit address.
+    pub address: u8,
+    /// Also acknowledge the general call address (0x00).
+    pub general_call: bool,
+    /// Stretch SCL until the driver is ready to handle the next byte.
+    ///
+    /// Only disable this if the bus master does not support clock stretching. Without it the
+    /// driver must keep up with the bus, otherwise bytes are lost or duplicated.
+    pub clock_stretching: bool,
+}
+
+#[cfg(any(i2c_v2, i2c_v3))]
+impl SlaveConfig {
+    /// Create a slave configuration for the given 7-bit address.
+    pub const fn new(address: u8) -> Self {
+        Self {
+            address,
+            general_call: false,
+            clock_stretching: true,
+        }
+    }
+}
+
+/// Transfer direction requested by the bus master.
+#[cfg(any(i2c_v2, i2c_v3))]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SlaveCommandKind {
+    /// The master writes to us, answer with [`I2c::respond_to_write`].
+    Write,
+    /// The master reads from us, answer with [`I2c::respond_to_read`].
+    Read,
+}
+
+/// Transfer addressed to us by the bus master.
+#[cfg(any(i2c_v2, i2c_v3))]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SlaveCommand {
+    /// Transfer direction.
+    pub kind: SlaveCommandKind,
+    /// Address that was matched, either our own or the general call address.
+    pub address: u8,
+}
+
 /// I2C driver.
 pub struct I2c<'d, M: Mode> {
     info: &'static Info,
     state: &'static State,
     kernel_clock: Hertz,
+    freq: Hertz,
     scl: Option<PeripheralRef<'d, AnyPin>>,
     sda: Option<PeripheralRef<'d, AnyPin>>,
     tx_dma: Option<ChannelAndRequest<'d>>,
@@ -187,6 +237,7 @@ impl<'d, M: Mode> I2c<'d, M> {
             info: T::info(),
             state: T::state(),
             kernel_clock: T::frequency(),
+            freq,
             scl,
             sda,
             tx_dma,
@@ -210,6 +261,117 @@ impl<'d, M: Mode> I2c<'d, M> {
             deadline: Instant::now() + self.timeout,
         }
     }
+
+    /// Recover a bus that is stuck because a slave holds SDA low, e.g. after the master was
+    /// reset in the middle of a read.
+    ///
+    /// The pins are temporarily switched to open-drain GPIO outputs and SCL is pulsed up to 9
+    /// times until the slave releases SDA, followed by a STOP condition. The peripheral is
+    /// reinitialized afterwards. Returns [`Error::Bus`] if SDA is still held low.
+    ///
+    /// This is called automatically when a transfer times out while the bus stays busy.
+    pub fn recover_bus(&mut self) -> Result<(), Error> {
+        let (Some(scl), Some(sda)) = (self.scl.as_ref(), self.sda.as_ref()) else {
+            return Err(Error::Bus);
+        };
+
+        // Half of a bus clock period, at least 1us.
+        let half_period_us = (500_000 / self.freq.0).max(1);
+
+        self.info.regs.cr1().modify(|w| w.set_pe(false));
+
+        scl.set_high();
+        sda.set_high();
+        set_pin_gpio(scl, true);
+        set_pin_gpio(sda, true);
+        blocking_delay_us(half_period_us);
+
+        for _ in 0..9 {
+            if pin_is_high(sda) {
+                break;
+            }
+            scl.set_low();
+            blocking_delay_us(half_period_us);
+            scl.set_high();
+            // Give a slave stretching the clock some time to release SCL.
+            for _ in 0..10 {
+                if pin_is_high(scl) {
+                    break;
+                }
+                blocking_delay_us(half_period_us);
+            }
+            blocking_delay_us(half_period_us);
+        }
+
+        // STOP condition: SDA rising while SCL is high.
+        scl.set_low();
+        blocking_delay_us(half_period_us);
+        sda.set_low();
+        blocking_delay_us(half_period_us);
+        scl.set_high();
+        blocking_delay_us(half_period_us);
+        sda.set_high();
+        blocking_delay_us(half_period_us);
+
+        let released = pin_is_high(sda) && pin_is_high(scl);
+
+        set_pin_gpio(scl, false);
+        set_pin_gpio(sda, false);
+
+        let freq = self.freq;
+        self.init(freq, Config::default());
+
+        if released {
+            Ok(())
+        } else {
+            Err(Error::Bus)
+        }
+    }
+
+    /// Recover the bus if `result` is a timeout and the bus is still busy.
+    fn recover_if_stuck(&mut self, result: Result<(), Error>) -> Result<(), Error> {
+        if result == Err(Error::Timeout) && self.is_bus_busy() {
+            let _ = self.recover_bus();
+        }
+        result
+    }
+}
+
+/// Switch an I2C pin between its alternate function and open-drain GPIO output, leaving the rest
+/// of its configuration untouched.
+fn set_pin_gpio(pin: &AnyPin, gpio: bool) {
+    let r = pin.block();
+    let n = pin._pin() as usize;
+
+    critical_section::with(|_| {
+        #[cfg(gpio_v1)]
+        r.cr(n / 8).modify(|w| {
+            w.set_cnf_out(
+                n % 8,
+                if gpio {
+                    crate::pac::gpio::vals::CnfOut::OPENDRAIN
+                } else {
+                    crate::pac::gpio::vals::CnfOut::ALTOPENDRAIN
+                },
+            )
+        });
+
+        #[cfg(gpio_v2)]
+        r.moder().modify(|w| {
+            w.set_moder(
+                n,
+                if gpio {
+                    crate::pac::gpio::vals::Moder::OUTPUT
+                } else {
+                    crate::pac::gpio::vals::Moder::ALTERNATE
+                },
+            )
+        });
+    });
+}
+
+fn pin_is_high(pin: &AnyPin) -> bool {
+    pin.block().idr().read().idr(pin._pin() as _) == crate::pac::gpio::vals::Idr::HIGH
 }
 
 impl<'d, M: Mode> Drop for I2c<'d, M> {
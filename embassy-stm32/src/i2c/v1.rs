@@ -93,6 +93,10 @@ impl<'d, M: PeriMode> I2c<'d, M> {
         });
     }
 
+    fn is_bus_busy(&self) -> bool {
+        self.info.regs.sr2().read().busy()
+    }
+
     fn check_and_clear_error_flags(info: &'static Info) -> Result<i2c::regs::Sr1, Error> {
         // Note that flags should only be cleared once they have been registered. If flags are
         // cleared otherwise, there may be an inherent race condition and flags may be missed.
@@ -298,15 +302,14 @@ impl<'d, M: PeriMode> I2c<'d, M> {
 
     /// Blocking read.
     pub fn blocking_read(&mut self, addr: u8, read: &mut [u8]) -> Result<(), Error> {
-        self.blocking_read_timeout(addr, read, self.timeout(), FrameOptions::FirstAndLastFrame)
+        let result = self.blocking_read_timeout(addr, read, self.timeout(), FrameOptions::FirstAndLastFrame);
+        self.recover_if_stuck(result)
     }
 
     /// Blocking write.
     pub fn blocking_write(&mut self, addr: u8, write: &[u8]) -> Result<(), Error> {
-        self.write_bytes(addr, write, self.timeout(), FrameOptions::FirstAndLastFrame)?;
-
-        // Fallthrough is success
-        Ok(())
+        let result = self.write_bytes(addr, write, self.timeout(), FrameOptions::FirstAndLastFrame);
+        self.recover_if_stuck(result)
     }
 
     /// Blocking write, restart, read.
@@ -319,10 +322,10 @@ impl<'d, M: PeriMode> I2c<'d, M> {
 
         let timeout = self.timeout();
 
-        self.write_bytes(addr, write, timeout, FrameOptions::FirstFrame)?;
-        self.blocking_read_timeout(addr, read, timeout, FrameOptions::FirstAndLastFrame)?;
-
-        Ok(())
+        let result = self
+            .write_bytes(addr, write, timeout, FrameOptions::FirstFrame)
+            .and_then(|_| self.blocking_read_timeout(addr, read, timeout, FrameOptions::FirstAndLastFrame));
+        self.recover_if_stuck(result)
     }
 
     /// Blocking transaction with operations.
@@ -501,18 +504,14 @@ impl<'d> I2c<'d, Async> {
 
     /// Write.
     pub async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Error> {
-        self.write_frame(address, write, FrameOptions::FirstAndLastFrame)
-            .await?;
-
-        Ok(())
+        let result = self.write_frame(address, write, FrameOptions::FirstAndLastFrame).await;
+        self.recover_if_stuck(result)
     }
 
     /// Read.
     pub async fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error> {
-        self.read_frame(address, buffer, FrameOptions::FirstAndLastFrame)
-            .await?;
-
-        Ok(())
+        let result = self.read_frame(address, buffer, FrameOptions::FirstAndLastFrame).await;
+        self.recover_if_stuck(result)
     }
 
     async fn read_frame(&mut self, address: u8, buffer: &mut [u8], frame: FrameOptions) -> Result<(), Error> {
@@ -679,8 +678,11 @@ impl<'d> I2c<'d, Async> {
             return Err(Error::Overrun);
         }
 
-        self.write_frame(address, write, FrameOptions::FirstFrame).await?;
-        self.read_frame(address, read, FrameOptions::FirstAndLastFrame).await
+        let mut result = self.write_frame(address, write, FrameOptions::FirstFrame).await;
+        if result.is_ok() {
+            result = self.read_frame(address, read, FrameOptions::FirstAndLastFrame).await;
+        }
+        self.recover_if_stuck(result)
     }
 
     /// Transaction with operations.
@@ -804,6 +806,7 @@ impl<'d, M: PeriMode> SetConfig for I2c<'d, M> {
     type Config = Hertz;
     type ConfigError = ();
     fn set_config(&mut self, config: &Self::Config) -> Result<(), ()> {
+        self.freq = *config;
         let timings = Timings::new(self.kernel_clock, *config);
         self.info.regs.cr2().modify(|reg| {
             reg.set_freq(timings.freq);
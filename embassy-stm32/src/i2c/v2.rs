@@ -16,10 +16,21 @@ pub(crate) unsafe fn on_interrupt<T: Instance>() {
     if isr.tcr() || isr.tc() {
         T::state().waker.wake();
     }
+    if isr.addr() || isr.rxne() || isr.txis() || isr.stopf() || isr.nackf() || isr.berr() || isr.arlo() || isr.ovr() {
+        T::state().waker.wake();
+    }
     // The flag can only be cleared by writting to nbytes, we won't do that here, so disable
-    // the interrupt
+    // the interrupt. Slave event interrupts are re-enabled by the task waiting for them.
     critical_section::with(|_| {
-        regs.cr1().modify(|w| w.set_tcie(false));
+        regs.cr1().modify(|w| {
+            w.set_tcie(false);
+            w.set_addrie(false);
+            w.set_rxie(false);
+            w.set_txie(false);
+            w.set_stopie(false);
+            w.set_nackie(false);
+            w.set_errie(false);
+        });
     });
 }
 
@@ -49,6 +60,10 @@ impl<'d, M: Mode> I2c<'d, M> {
         self.info.regs.cr2().write(|w| w.set_stop(true));
     }
 
+    fn is_bus_busy(&self) -> bool {
+        self.info.regs.isr().read().busy()
+    }
+
     fn master_read(
         info: &'static Info,
         address: u8,
@@ -321,21 +336,25 @@ impl<'d, M: Mode> I2c<'d, M> {
 
     /// Blocking read.
     pub fn blocking_read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Error> {
-        self.read_internal(address, read, false, self.timeout())
+        let result = self.read_internal(address, read, false, self.timeout());
         // Automatic Stop
+        self.recover_if_stuck(result)
     }
 
     /// Blocking write.
     pub fn blocking_write(&mut self, address: u8, write: &[u8]) -> Result<(), Error> {
-        self.write_internal(address, write, true, self.timeout())
+        let result = self.write_internal(address, write, true, self.timeout());
+        self.recover_if_stuck(result)
     }
 
     /// Blocking write, restart, read.
     pub fn blocking_write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Error> {
         let timeout = self.timeout();
-        self.write_internal(address, write, false, timeout)?;
-        self.read_internal(address, read, true, timeout)
+        let result = self
+            .write_internal(address, write, false, timeout)
+            .and_then(|_| self.read_internal(address, read, true, timeout));
         // Automatic Stop
+        self.recover_if_stuck(result)
     }
 
     /// Blocking transaction with operations.
@@ -597,13 +616,14 @@ impl<'d> I2c<'d, Async> {
     /// Write.
     pub async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Error> {
         let timeout = self.timeout();
-        if write.is_empty() {
+        let result = if write.is_empty() {
             self.write_internal(address, write, true, timeout)
         } else {
             timeout
                 .with(self.write_dma_internal(address, write, true, true, timeout))
                 .await
-        }
+        };
+        self.recover_if_stuck(result)
     }
 
     /// Write multiple buffers.
@@ -635,16 +655,22 @@ impl<'d> I2c<'d, Async> {
     pub async fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error> {
         let timeout = self.timeout();
 
-        if buffer.is_empty() {
+        let result = if buffer.is_empty() {
             self.read_internal(address, buffer, false, timeout)
         } else {
             let fut = self.read_dma_internal(address, buffer, false, timeout);
             timeout.with(fut).await
-        }
+        };
+        self.recover_if_stuck(result)
     }
 
     /// Write, restart, read.
     pub async fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Error> {
+        let result = self.write_read_inner(address, write, read).await;
+        self.recover_if_stuck(result)
+    }
+
+    async fn write_read_inner(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Error> {
         let timeout = self.timeout();
 
         if write.is_empty() {
@@ -676,6 +702,196 @@ impl<'d> I2c<'d, Async> {
     }
 }
 
+impl<'d> I2c<'d, Async> {
+    // =========================
+    //  Slave (target) mode
+
+    /// Enable slave mode and start acknowledging `config.address`.
+    ///
+    /// Master transfers can still be started in between slave transfers.
+    pub fn set_slave_config(&mut self, config: SlaveConfig) {
+        let regs = self.info.regs;
+
+        regs.cr1().modify(|w| w.set_pe(false));
+
+        regs.oar1().modify(|w| w.set_oa1en(false));
+        regs.oar1().write(|w| {
+            w.set_oa1((config.address << 1) as u16);
+            w.set_oa1mode(i2c::vals::Addmode::BIT7);
+            w.set_oa1en(true);
+        });
+
+        regs.cr1().modify(|w| {
+            w.set_gcen(config.general_call);
+            w.set_nostretch(!config.clock_stretching);
+            w.set_pe(true);
+        });
+    }
+
+    /// Wait for a slave event.
+    ///
+    /// `enable` enables the interrupts of the events `ready` checks for, bus errors are always
+    /// reported.
+    async fn wait_slave_event(
+        &mut self,
+        enable: impl Fn(&mut i2c::regs::Cr1),
+        ready: impl Fn(i2c::regs::Isr) -> bool,
+    ) -> Result<i2c::regs::Isr, Error> {
+        let regs = self.info.regs;
+
+        poll_fn(|cx| {
+            self.state.waker.register(cx.waker());
+
+            let isr = regs.isr().read();
+            if isr.berr() {
+                regs.icr().write(|w| w.set_berrcf(true));
+                return Poll::Ready(Err(Error::Bus));
+            }
+            if isr.arlo() {
+                regs.icr().write(|w| w.set_arlocf(true));
+                return Poll::Ready(Err(Error::Arbitration));
+            }
+            if isr.ovr() {
+                regs.icr().write(|w| w.set_ovrcf(true));
+                return Poll::Ready(Err(Error::Overrun));
+            }
+            if ready(isr) {
+                return Poll::Ready(Ok(isr));
+            }
+
+            critical_section::with(|_| {
+                regs.cr1().modify(|w| {
+                    w.set_errie(true);
+                    enable(w);
+                });
+            });
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Wait until the bus master addresses us.
+    ///
+    /// The clock is stretched until the command is answered with [`respond_to_write`] or
+    /// [`respond_to_read`].
+    ///
+    /// [`respond_to_write`]: Self::respond_to_write
+    /// [`respond_to_read`]: Self::respond_to_read
+    pub async fn listen(&mut self) -> Result<SlaveCommand, Error> {
+        let isr = self.wait_slave_event(|w| w.set_addrie(true), |isr| isr.addr()).await?;
+
+        let kind = match isr.dir() {
+            i2c::vals::Dir::READ => SlaveCommandKind::Read,
+            i2c::vals::Dir::WRITE => SlaveCommandKind::Write,
+        };
+
+        Ok(SlaveCommand {
+            kind,
+            address: isr.addcode(),
+        })
+    }
+
+    /// Receive the bytes written by the bus master after [`listen`](Self::listen) returned
+    /// [`SlaveCommandKind::Write`].
+    ///
+    /// Returns the number of bytes received once the master sends a stop or a repeated start.
+    /// A repeated start is returned by the next call to [`listen`](Self::listen). If the master
+    /// writes more than `buffer.len()` bytes, the excess is discarded and [`Error::Overrun`] is
+    /// returned.
+    pub async fn respond_to_write(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        let regs = self.info.regs;
+
+        regs.icr().write(|w| w.set_addrcf(true));
+
+        let mut len = 0;
+        let mut overrun = false;
+        loop {
+            let isr = self
+                .wait_slave_event(
+                    |w| {
+                        w.set_rxie(true);
+                        w.set_stopie(true);
+                        w.set_addrie(true);
+                    },
+                    |isr| isr.rxne() || isr.stopf() || isr.addr(),
+                )
+                .await?;
+
+            if isr.rxne() {
+                let byte = regs.rxdr().read().rxdata();
+                match buffer.get_mut(len) {
+                    Some(slot) => {
+                        *slot = byte;
+                        len += 1;
+                    }
+                    None => overrun = true,
+                }
+                continue;
+            }
+
+            if isr.stopf() {
+                regs.icr().write(|w| w.set_stopcf(true));
+            }
+            break;
+        }
+
+        if overrun {
+            Err(Error::Overrun)
+        } else {
+            Ok(len)
+        }
+    }
+
+    /// Send `data` to the bus master after [`listen`](Self::listen) returned
+    /// [`SlaveCommandKind::Read`].
+    ///
+    /// If the master reads more than `data.len()` bytes, `0xFF` is sent. Returns the number of
+    /// bytes of `data` handed to the peripheral once the master ends the transfer.
+    pub async fn respond_to_read(&mut self, data: &[u8]) -> Result<usize, Error> {
+        let regs = self.info.regs;
+
+        // Flush a byte left over from a previous transfer
+        regs.isr().modify(|w| w.set_txe(true));
+        regs.icr().write(|w| w.set_addrcf(true));
+
+        let mut sent = 0;
+        loop {
+            let isr = self
+                .wait_slave_event(
+                    |w| {
+                        w.set_txie(true);
+                        w.set_nackie(true);
+                        w.set_stopie(true);
+                        w.set_addrie(true);
+                    },
+                    |isr| isr.txis() || isr.nackf() || isr.stopf() || isr.addr(),
+                )
+                .await?;
+
+            if isr.txis() {
+                let byte = data.get(sent).copied().unwrap_or(0xFF);
+                regs.txdr().write(|w| w.set_txdata(byte));
+                sent += 1;
+                continue;
+            }
+
+            if isr.nackf() {
+                // The master NACKs the last byte it wants, the stop follows.
+                regs.icr().write(|w| w.set_nackcf(true));
+                continue;
+            }
+
+            if isr.stopf() {
+                regs.icr().write(|w| w.set_stopcf(true));
+            }
+            break;
+        }
+
+        // The last byte loaded into TXDR was not clocked out
+        Ok(sent.saturating_sub(1).min(data.len()))
+    }
+}
+
 /// I2C Stop Configuration
 ///
 /// Peripheral options for generating the STOP condition
@@ -804,6 +1020,7 @@ impl<'d, M: Mode> SetConfig for I2c<'d, M> {
     type Config = Hertz;
     type ConfigError = ();
     fn set_config(&mut self, config: &Self::Config) -> Result<(), ()> {
+        self.freq = *config;
         let timings = Timings::new(self.kernel_clock, *config);
         self.info.regs.timingr().write(|reg| {
             reg.set_presc(timings.prescale);
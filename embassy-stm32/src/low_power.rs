@@ -50,6 +50,20 @@
 //!     // your application here...
 //! }
 //! ```
+//!
+//! Alternatively, with the `time-driver-lptim1` feature, time is kept by an LPTIM that keeps
+//! running in `STOP2`. Time then never has to be paused, the `RTC` does not need to be given to the
+//! executor and the next timer event only has to be further away than the stop mode wakeup latency.
+//! The LPTIM must be clocked from LSE or LSI:
+//!
+//! ```rust,ignore
+//! let mut config = embassy_stm32::Config::default();
+//! config.rcc.ls = LsConfig::default_lse();
+//! config.rcc.mux.lptim1sel = mux::Lptim1sel::LSE;
+//! let p = embassy_stm32::init(config);
+//!
+//! // your application here, no call to `stop_with_rtc`...
+//! ```
 
 // TODO: Usage of `static mut` here is unsound. Fix then remove this `allow`.`
 #![allow(static_mut_refs)]
@@ -62,16 +76,21 @@ use cortex_m::peripheral::SCB;
 use embassy_executor::*;
 
 use crate::interrupt;
-use crate::time_driver::{get_driver, RtcDriver};
+#[cfg(time_driver_lptim)]
+use crate::time_driver::{get_driver, LptimDriver as TimeDriver};
+#[cfg(not(time_driver_lptim))]
+use crate::time_driver::{get_driver, RtcDriver as TimeDriver};
 
 const THREAD_PENDER: usize = usize::MAX;
 
+#[cfg(not(time_driver_lptim))]
 use crate::rtc::Rtc;
 
 static mut EXECUTOR: Option<Executor> = None;
 
 foreach_interrupt! {
     (RTC, rtc, $block:ident, WKUP, $irq:ident) => {
+        #[cfg(not(time_driver_lptim))]
         #[interrupt]
         #[allow(non_snake_case)]
         unsafe fn $irq() {
@@ -86,6 +105,9 @@ pub(crate) unsafe fn on_wakeup_irq() {
 }
 
 /// Configure STOP mode with RTC.
+///
+/// Not needed, and not available, with the LPTIM time driver.
+#[cfg(not(time_driver_lptim))]
 pub fn stop_with_rtc(rtc: &'static Rtc) {
     unsafe { EXECUTOR.as_mut().unwrap() }.stop_with_rtc(rtc)
 }
@@ -139,7 +161,7 @@ pub struct Executor {
     inner: raw::Executor,
     not_send: PhantomData<*mut ()>,
     scb: SCB,
-    time_driver: &'static RtcDriver,
+    time_driver: &'static TimeDriver,
 }
 
 impl Executor {
@@ -164,6 +186,7 @@ impl Executor {
         trace!("low power: resume");
     }
 
+    #[cfg(not(time_driver_lptim))]
     pub(self) fn stop_with_rtc(&mut self, rtc: &'static Rtc) {
         self.time_driver.set_rtc(rtc);
 
@@ -9,7 +9,7 @@ use core::task::Poll;
 use embassy_hal_internal::{into_ref, PeripheralRef};
 use embassy_sync::waitqueue::AtomicWaker;
 use stm32_metapac::ltdc::regs::Dccr;
-use stm32_metapac::ltdc::vals::{Bf1, Bf2, Cfuif, Clif, Crrif, Cterrif, Pf, Vbr};
+use stm32_metapac::ltdc::vals::{Bf1, Bf2, Cfuif, Clif, Crrif, Cterrif, Imr, Pf, Vbr};
 
 use crate::gpio::{AfType, OutputType, Speed};
 use crate::interrupt::typelevel::Interrupt;
@@ -156,6 +156,16 @@ impl PixelFormat {
     }
 }
 
+/// Layer blending mode
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BlendingMode {
+    /// Blend using the per-pixel alpha multiplied by the layer constant alpha
+    PixelAlpha,
+    /// Blend using the layer constant alpha only, ignoring the per-pixel alpha
+    ConstantAlpha,
+}
+
 /// Ltdc Blending Layer
 #[repr(usize)]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -473,6 +483,102 @@ impl<'d, T: Instance> Ltdc<'d, T> {
         result
     }
 
+    /// Enable or disable a layer
+    pub fn enable_layer(&mut self, layer: LtdcLayer, enable: bool) {
+        T::regs().layer(layer as usize).cr().modify(|w| w.set_len(enable));
+        Self::reload_now();
+    }
+
+    /// Set the constant alpha of a layer, from 0x00 (transparent) to 0xFF (opaque)
+    pub fn set_layer_alpha(&mut self, layer: LtdcLayer, alpha: u8) {
+        T::regs().layer(layer as usize).cacr().write(|w| w.set_consta(alpha));
+        Self::reload_now();
+    }
+
+    /// Set how a layer is blended with the layers below it
+    pub fn set_layer_blending(&mut self, layer: LtdcLayer, mode: BlendingMode) {
+        T::regs().layer(layer as usize).bfcr().modify(|w| match mode {
+            BlendingMode::PixelAlpha => {
+                w.set_bf1(Bf1::PIXEL);
+                w.set_bf2(Bf2::PIXEL);
+            }
+            BlendingMode::ConstantAlpha => {
+                w.set_bf1(Bf1::CONSTANT);
+                w.set_bf2(Bf2::CONSTANT);
+            }
+        });
+        Self::reload_now();
+    }
+
+    /// Set the color of a layer outside of its window, or when it is disabled
+    pub fn set_layer_default_color(&mut self, layer: LtdcLayer, color: RgbColor, alpha: u8) {
+        T::regs().layer(layer as usize).dccr().write(|w| {
+            w.set_dcred(color.red);
+            w.set_dcgreen(color.green);
+            w.set_dcblue(color.blue);
+            w.set_dcalpha(alpha);
+        });
+        Self::reload_now();
+    }
+
+    /// Set the background color, visible where no layer is drawn
+    pub fn set_background_color(&mut self, color: RgbColor) {
+        T::regs().bccr().write(|w| {
+            w.set_bcred(color.red);
+            w.set_bcgreen(color.green);
+            w.set_bcblue(color.blue);
+        });
+    }
+
+    /// Wait for the start of the vertical blanking period, right after the last active line has been sent to the display
+    ///
+    /// This is the time to update a framebuffer that is being displayed without tearing
+    pub async fn wait_vsync(&mut self) {
+        let line = T::regs().awcr().read().aah() + 1;
+        self.wait_for_line(line).await
+    }
+
+    /// Wait until the display controller reaches `line`
+    ///
+    /// Lines are counted from the start of the vertical synchronization pulse, so the first active line is
+    /// `v_sync + v_back_porch`
+    pub async fn wait_for_line(&mut self, line: u16) {
+        let ltdc = T::regs();
+        ltdc.lipcr().write(|w| w.set_lipos(line));
+        ltdc.icr().write(|w| w.set_clif(Clif::CLEAR));
+
+        poll_fn(|cx| {
+            LTDC_WAKER.register(cx.waker());
+            if ltdc.isr().read().lif() {
+                return Poll::Ready(());
+            }
+
+            ltdc.ier().modify(|w| w.set_lie(true));
+            T::Interrupt::unpend();
+            unsafe { T::Interrupt::enable() };
+
+            // need to check condition after register to avoid a race
+            // condition that would result in lost notifications.
+            if ltdc.isr().read().lif() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+
+        ltdc.ier().modify(|w| w.set_lie(false));
+        ltdc.icr().write(|w| w.set_clif(Clif::CLEAR));
+    }
+
+    /// Apply the shadow registers immediately, so that the register reload flag is not left pending for [`Self::set_buffer`]
+    fn reload_now() {
+        let ltdc = T::regs();
+        ltdc.srcr().write(|w| w.set_imr(Imr::RELOAD));
+        while ltdc.srcr().read().imr() == Imr::RELOAD {}
+        ltdc.icr().write(|w| w.set_crrif(Crrif::CLEAR));
+    }
+
     fn setup_clocks() {
         critical_section::with(|_cs| {
             // RM says the pllsaidivr should only be changed when pllsai is off. But this could have other unintended side effects. So let's just give it a try like this.
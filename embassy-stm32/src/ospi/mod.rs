@@ -6,6 +6,7 @@
 pub mod enums;
 
 use core::marker::PhantomData;
+use core::ops::Deref;
 
 use embassy_embedded_hal::{GetConfig, SetConfig};
 use embassy_hal_internal::{into_ref, PeripheralRef};
@@ -244,6 +245,23 @@ impl<'d, T: Instance, M: PeriMode> Ospi<'d, T, M> {
         });
     }
 
+    /// Enter memory mapped mode for reads, e.g. to execute in place or read assets directly.
+    ///
+    /// `read_config` is the read command sent by the peripheral for each access, including its
+    /// dummy cycles. Writes to the mapped memory are not allowed.
+    ///
+    /// The returned guard gives access to the mapped memory, and exits memory mapped mode when
+    /// dropped.
+    pub fn memory_mapped(&mut self, read_config: TransferConfig) -> Result<MemoryMapped<'_, 'd, T, M>, OspiError> {
+        self.enable_memory_mapped_mode(read_config, TransferConfig::default())?;
+
+        // DEVSIZE + 1 address bits, within the 256MiB window of the peripheral.
+        let devsize: u8 = self.config.device_size.into();
+        let len = 1usize << (devsize as u32 + 1).min(28);
+
+        Ok(MemoryMapped { ospi: self, len })
+    }
+
     fn new_inner(
         peri: impl Peripheral<P = T> + 'd,
         d0: Option<PeripheralRef<'d, AnyPin>>,
@@ -1173,6 +1191,51 @@ impl<'d, T: Instance> Ospi<'d, T, Async> {
     }
 }
 
+/// Memory mapped mode guard, returned by [`Ospi::memory_mapped`].
+///
+/// Derefs to the mapped memory. Memory mapped mode is exited when dropped, and the caches
+/// covering the memory are invalidated so that the content can be changed by indirect commands.
+pub struct MemoryMapped<'a, 'd, T: Instance, M: PeriMode> {
+    ospi: &'a mut Ospi<'d, T, M>,
+    len: usize,
+}
+
+impl<'a, 'd, T: Instance, M: PeriMode> MemoryMapped<'a, 'd, T, M> {
+    /// Stay in memory mapped mode for as long as the driver is borrowed.
+    ///
+    /// With a driver borrowed for `'static`, this gives a `&'static [u8]`.
+    pub fn leak(self) -> &'a [u8] {
+        let ptr = T::MEMORY_BASE as *const u8;
+        let len = self.len;
+        core::mem::forget(self);
+        // Safety: the memory stays mapped, and can't be changed, as long as the driver is borrowed.
+        unsafe { core::slice::from_raw_parts(ptr, len) }
+    }
+}
+
+impl<'a, 'd, T: Instance, M: PeriMode> Deref for MemoryMapped<'a, 'd, T, M> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // Safety: the memory is mapped, and can't be changed, while the guard is alive.
+        unsafe { core::slice::from_raw_parts(T::MEMORY_BASE as *const u8, self.len) }
+    }
+}
+
+impl<'a, 'd, T: Instance, M: PeriMode> Drop for MemoryMapped<'a, 'd, T, M> {
+    fn drop(&mut self) {
+        self.ospi.disable_memory_mapped_mode();
+
+        // Don't keep stale data or instructions for the next time the memory is mapped.
+        #[cfg(stm32h7)]
+        {
+            let mut scb = unsafe { cortex_m::Peripherals::steal() }.SCB;
+            unsafe { scb.invalidate_dcache_by_address(T::MEMORY_BASE, self.len) };
+            scb.invalidate_icache();
+        }
+    }
+}
+
 impl<'d, T: Instance, M: PeriMode> Drop for Ospi<'d, T, M> {
     fn drop(&mut self) {
         self.sck.as_ref().map(|x| x.set_as_disconnected());
@@ -1210,6 +1273,17 @@ pub(crate) trait SealedOctospimInstance {
 /// OctoSPI instance trait.
 pub(crate) trait SealedInstance {
     const REGS: Regs;
+    /// Start of the memory mapped region.
+    const MEMORY_BASE: usize;
+}
+
+macro_rules! memory_base {
+    (OCTOSPI2) => {
+        0x7000_0000
+    };
+    ($inst:ident) => {
+        0x9000_0000
+    };
 }
 
 /// OSPI instance trait.
@@ -1254,6 +1328,7 @@ foreach_peripheral!(
     (octospi, $inst:ident) => {
         impl SealedInstance for peripherals::$inst {
             const REGS: Regs = crate::pac::$inst;
+            const MEMORY_BASE: usize = memory_base!($inst);
         }
 
         impl Instance for peripherals::$inst {}
@@ -1265,6 +1340,7 @@ foreach_peripheral!(
     (octospi, $inst:ident) => {
         impl SealedInstance for peripherals::$inst {
             const REGS: Regs = crate::pac::$inst;
+            const MEMORY_BASE: usize = memory_base!($inst);
         }
 
         impl Instance for peripherals::$inst {}
@@ -3,6 +3,9 @@
 
 use core::future::poll_fn;
 use core::marker::PhantomData;
+use core::num::NonZeroU32;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
 use core::task::Poll;
 
 use embassy_hal_internal::{into_ref, PeripheralRef};
@@ -14,9 +17,13 @@ use crate::{interrupt, pac, peripherals, rcc, Peripheral};
 
 static RNG_WAKER: AtomicWaker = AtomicWaker::new();
 
+/// Registers of the RNG registered with [`Rng::register_global`], null if none.
+static GLOBAL_RNG: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
 /// RNG error
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
 pub enum Error {
     /// Seed error.
     SeedError,
@@ -24,6 +31,26 @@ pub enum Error {
     /// see the Reference Manual for details on restrictions
     /// on RNG clocks.
     ClockError,
+    /// No global entropy source has been registered with [`Rng::register_global`].
+    NotRegistered,
+    /// The RNG produced no data in time.
+    Timeout,
+}
+
+impl Error {
+    /// Error code in the custom range shared by `rand_core` and `getrandom`.
+    ///
+    /// This lets a `getrandom` custom backend built on [`fill_entropy`] report the failure, with
+    /// `getrandom::Error::from(e.code())`.
+    pub fn code(self) -> NonZeroU32 {
+        unwrap!(NonZeroU32::new(rand_core::Error::CUSTOM_START + self as u32))
+    }
+}
+
+impl From<Error> for rand_core::Error {
+    fn from(e: Error) -> Self {
+        rand_core::Error::from(e.code())
+    }
 }
 
 /// RNG interrupt handler.
@@ -64,69 +91,34 @@ impl<'d, T: Instance> Rng<'d, T> {
     }
 
     /// Reset the RNG.
-    #[cfg(rng_v1)]
     pub fn reset(&mut self) {
-        T::regs().cr().write(|reg| {
-            reg.set_rngen(false);
-        });
-        T::regs().sr().modify(|reg| {
-            reg.set_seis(false);
-            reg.set_ceis(false);
-        });
-        T::regs().cr().modify(|reg| {
-            reg.set_rngen(true);
-        });
-        // Reference manual says to discard the first.
-        let _ = self.next_u32();
-    }
-
-    /// Reset the RNG.
-    #[cfg(not(rng_v1))]
-    pub fn reset(&mut self) {
-        T::regs().cr().write(|reg| {
-            reg.set_condrst(true);
-            reg.set_nistc(pac::rng::vals::Nistc::CUSTOM);
-            // set RNG config "A" according to reference manual
-            // this has to be written within the same write access as setting the CONDRST bit
-            reg.set_rng_config1(pac::rng::vals::RngConfig1::CONFIGA);
-            reg.set_clkdiv(pac::rng::vals::Clkdiv::NODIV);
-            reg.set_rng_config2(pac::rng::vals::RngConfig2::CONFIGA_B);
-            reg.set_rng_config3(pac::rng::vals::RngConfig3::CONFIGA);
-            reg.set_ced(true);
-            reg.set_ie(false);
-            reg.set_rngen(true);
-        });
-        T::regs().cr().modify(|reg| {
-            reg.set_ced(false);
-        });
-        // wait for CONDRST to be set
-        while !T::regs().cr().read().condrst() {}
-        // magic number must be written immediately before every read or write access to HTCR
-        T::regs().htcr().write(|w| w.set_htcfg(pac::rng::vals::Htcfg::MAGIC));
-        // write recommended value according to reference manual
-        // note: HTCR can only be written during conditioning
-        T::regs()
-            .htcr()
-            .write(|w| w.set_htcfg(pac::rng::vals::Htcfg::RECOMMENDED));
-        // finish conditioning
-        T::regs().cr().modify(|reg| {
-            reg.set_rngen(true);
-            reg.set_condrst(false);
-        });
-        // wait for CONDRST to be reset
-        while T::regs().cr().read().condrst() {}
+        reset(T::regs());
     }
 
     /// Try to recover from a seed error.
     pub fn recover_seed_error(&mut self) {
-        self.reset();
-        // reset should also clear the SEIS flag
-        if T::regs().sr().read().seis() {
+        if recover_seed_error(T::regs()).is_err() {
             warn!("recovering from seed error failed");
-            return;
         }
-        // wait for SECS to be cleared by RNG
-        while T::regs().sr().read().secs() {}
+    }
+
+    /// Read a random word, blocking until one is available.
+    ///
+    /// On a seed error (failed health test of the noise source), the RNG is recovered and
+    /// [`Error::SeedError`] is returned so the caller can retry.
+    pub fn try_next_u32(&mut self) -> Result<u32, Error> {
+        read_word(T::regs())
+    }
+
+    /// Make this RNG the global entropy source, available through [`fill_entropy`] and [`GlobalRng`].
+    ///
+    /// This gives up ownership of the peripheral, so that other crates can pull randomness
+    /// without having to be handed the driver.
+    pub fn register_global(self)
+    where
+        'd: 'static,
+    {
+        GLOBAL_RNG.store(T::regs().as_ptr(), Ordering::Release);
     }
 
     /// Fill the given slice with random values.
@@ -190,11 +182,8 @@ impl<'d, T: Instance> Rng<'d, T> {
 impl<'d, T: Instance> RngCore for Rng<'d, T> {
     fn next_u32(&mut self) -> u32 {
         loop {
-            let sr = T::regs().sr().read();
-            if sr.seis() | sr.ceis() {
-                self.reset();
-            } else if sr.drdy() {
-                return T::regs().dr().read();
+            if let Ok(word) = self.try_next_u32() {
+                return word;
             }
         }
     }
@@ -215,13 +204,220 @@ impl<'d, T: Instance> RngCore for Rng<'d, T> {
     }
 
     fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
-        self.fill_bytes(dest);
+        for chunk in dest.chunks_mut(4) {
+            let rand = self.try_next_u32()?;
+            for (slot, num) in chunk.iter_mut().zip(rand.to_ne_bytes().iter()) {
+                *slot = *num
+            }
+        }
         Ok(())
     }
 }
 
 impl<'d, T: Instance> CryptoRng for Rng<'d, T> {}
 
+/// Number of status register polls [`fill_entropy`] waits for a word before giving up. A word
+/// normally takes a few tens of RNG clock cycles.
+const FILL_ENTROPY_POLLS: u32 = 100_000;
+/// Number of seed errors [`fill_entropy`] recovers from before giving up.
+const FILL_ENTROPY_SEED_RETRIES: u32 = 3;
+
+/// Fill `dest` from the global entropy source registered with [`Rng::register_global`].
+///
+/// This can be called from any context, including interrupts. Only the read of each word is
+/// done in a critical section, so that concurrent callers never get the same value; waiting for
+/// data and recovering from seed errors happen outside of it, and are bounded. On failure, the
+/// error can be forwarded to `getrandom` with [`Error::code`].
+pub fn fill_entropy(dest: &mut [u8]) -> Result<(), Error> {
+    let regs = GLOBAL_RNG.load(Ordering::Acquire);
+    if regs.is_null() {
+        return Err(Error::NotRegistered);
+    }
+    let r = unsafe { pac::rng::Rng::from_ptr(regs) };
+
+    for chunk in dest.chunks_mut(4) {
+        let rand = fill_entropy_word(r)?;
+        for (slot, num) in chunk.iter_mut().zip(rand.to_ne_bytes().iter()) {
+            *slot = *num
+        }
+    }
+    Ok(())
+}
+
+fn fill_entropy_word(r: pac::rng::Rng) -> Result<u32, Error> {
+    let mut seed_errors = 0;
+    let mut polls = 0;
+    loop {
+        match critical_section::with(|_| try_read_word(r)) {
+            Ok(Some(word)) => return Ok(word),
+            Ok(None) => {
+                polls += 1;
+                if polls == FILL_ENTROPY_POLLS {
+                    return Err(Error::Timeout);
+                }
+            }
+            Err(Error::SeedError) => {
+                seed_errors += 1;
+                if seed_errors > FILL_ENTROPY_SEED_RETRIES {
+                    return Err(Error::SeedError);
+                }
+                if recover_seed_error(r).is_err() {
+                    warn!("recovering from seed error failed");
+                }
+                polls = 0;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Handle to the global entropy source registered with [`Rng::register_global`].
+///
+/// The infallible [`RngCore`] methods panic if no RNG has been registered, or if it keeps failing
+/// after the bounded retries of [`fill_entropy`].
+#[derive(Clone, Copy, Default)]
+pub struct GlobalRng;
+
+impl RngCore for GlobalRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_ne_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_ne_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match fill_entropy(dest) {
+            Ok(()) => {}
+            Err(Error::NotRegistered) => panic!("no global RNG registered"),
+            Err(e) => panic!("global RNG failed: {:?}", e),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        Ok(fill_entropy(dest)?)
+    }
+}
+
+impl CryptoRng for GlobalRng {}
+
+#[cfg(rng_v1)]
+fn reset(r: pac::rng::Rng) {
+    r.cr().write(|reg| {
+        reg.set_rngen(false);
+    });
+    r.sr().modify(|reg| {
+        reg.set_seis(false);
+        reg.set_ceis(false);
+    });
+    r.cr().modify(|reg| {
+        reg.set_rngen(true);
+    });
+    // Reference manual says to discard the first.
+    loop {
+        let sr = r.sr().read();
+        if sr.seis() || sr.ceis() {
+            break;
+        } else if sr.drdy() {
+            let _ = r.dr().read();
+            break;
+        }
+    }
+}
+
+#[cfg(not(rng_v1))]
+fn reset(r: pac::rng::Rng) {
+    r.cr().write(|reg| {
+        reg.set_condrst(true);
+        reg.set_nistc(pac::rng::vals::Nistc::CUSTOM);
+        // set RNG config "A" according to reference manual
+        // this has to be written within the same write access as setting the CONDRST bit
+        reg.set_rng_config1(pac::rng::vals::RngConfig1::CONFIGA);
+        reg.set_clkdiv(pac::rng::vals::Clkdiv::NODIV);
+        reg.set_rng_config2(pac::rng::vals::RngConfig2::CONFIGA_B);
+        reg.set_rng_config3(pac::rng::vals::RngConfig3::CONFIGA);
+        reg.set_ced(true);
+        reg.set_ie(false);
+        reg.set_rngen(true);
+    });
+    r.cr().modify(|reg| {
+        reg.set_ced(false);
+    });
+    // wait for CONDRST to be set
+    while !r.cr().read().condrst() {}
+    // magic number must be written immediately before every read or write access to HTCR
+    r.htcr().write(|w| w.set_htcfg(pac::rng::vals::Htcfg::MAGIC));
+    // write recommended value according to reference manual
+    // note: HTCR can only be written during conditioning
+    r.htcr().write(|w| w.set_htcfg(pac::rng::vals::Htcfg::RECOMMENDED));
+    // finish conditioning
+    r.cr().modify(|reg| {
+        reg.set_rngen(true);
+        reg.set_condrst(false);
+    });
+    // wait for CONDRST to be reset
+    while r.cr().read().condrst() {}
+}
+
+fn recover_seed_error(r: pac::rng::Rng) -> Result<(), Error> {
+    reset(r);
+    // reset should also clear the SEIS flag
+    if r.sr().read().seis() {
+        return Err(Error::SeedError);
+    }
+    // wait for SECS to be cleared by RNG
+    while r.sr().read().secs() {}
+    Ok(())
+}
+
+/// Read a random word if one is available, without waiting or recovering from errors.
+fn try_read_word(r: pac::rng::Rng) -> Result<Option<u32>, Error> {
+    let sr = r.sr().read();
+    if sr.seis() {
+        Err(Error::SeedError)
+    } else if sr.ceis() {
+        r.sr().modify(|sr| sr.set_ceis(false));
+        Err(Error::ClockError)
+    } else if sr.drdy() {
+        // reference manual: always check if DR is zero
+        match r.dr().read() {
+            0 => Err(Error::SeedError),
+            word => Ok(Some(word)),
+        }
+    } else {
+        Ok(None)
+    }
+}
+
+/// Read a random word, blocking until one is available, and recover from errors.
+fn read_word(r: pac::rng::Rng) -> Result<u32, Error> {
+    loop {
+        let sr = r.sr().read();
+        if sr.seis() {
+            // the data in DR must not be used after a seed error
+            if recover_seed_error(r).is_err() {
+                warn!("recovering from seed error failed");
+            }
+            return Err(Error::SeedError);
+        } else if sr.ceis() {
+            r.sr().modify(|sr| sr.set_ceis(false));
+            return Err(Error::ClockError);
+        } else if sr.drdy() {
+            let word = r.dr().read();
+            // reference manual: always check if DR is zero
+            if word == 0 {
+                return Err(Error::SeedError);
+            }
+            return Ok(word);
+        }
+    }
+}
+
 trait SealedInstance {
     fn regs() -> pac::rng::Rng;
 }
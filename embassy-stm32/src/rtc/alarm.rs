@@ -0,0 +1,532 @@
+//! Alarms, periodic wakeup and timestamp capture.
+use core::cell::Cell;
+use core::future::poll_fn;
+use core::sync::atomic::{AtomicU8, Ordering};
+use core::task::Poll;
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::waitqueue::AtomicWaker;
+
+use super::datetime::day_of_week_from_u8;
+use super::{bcd2_to_byte, DateTime, DayOfWeek, Rtc, RtcError, SealedInstance};
+use crate::interrupt;
+use crate::interrupt::typelevel::Interrupt;
+#[cfg(any(rtc_v3, rtc_v3u5, rtc_v3l5))]
+use crate::pac::rtc::vals::Calrf;
+use crate::peripherals::RTC;
+
+// EXTI lines of the alarm, timestamp and wakeup events, on chips where the RTC interrupts go
+// through configurable EXTI lines. On the other chips, the RTC is connected to direct lines.
+#[cfg(stm32f4)]
+const EXTI_LINES: [usize; 3] = [17, 21, 22];
+#[cfg(stm32l4)]
+const EXTI_LINES: [usize; 3] = [18, 19, 20];
+#[cfg(any(stm32l0, stm32g4))]
+const EXTI_LINES: [usize; 3] = [17, 19, 20];
+
+const PENDING_ALARM_A: u8 = 1 << 0;
+const PENDING_ALARM_B: u8 = 1 << 1;
+const PENDING_WAKEUP: u8 = 1 << 2;
+
+static PENDING: AtomicU8 = AtomicU8::new(0);
+static WAKER: AtomicWaker = AtomicWaker::new();
+static TIMESTAMP: Mutex<CriticalSectionRawMutex, Cell<Option<Timestamp>>> = Mutex::new(Cell::new(None));
+
+/// RTC interrupt handler.
+///
+/// Bind it to every RTC interrupt of the chip that carries alarm, wakeup or timestamp events, e.g.
+/// `RTC_ALARM`, `RTC_WKUP` and `TAMP_STAMP` on STM32F4, `RTC` on STM32L0/L5/H5/U5 or `RTC_TAMP` on
+/// STM32G0. On chips where these events share the `RTC_WKUP`/`RTC` interrupt, this can't be combined
+/// with the RTC-based time keeping of the `low-power` executor.
+pub struct InterruptHandler {
+    _private: (),
+}
+
+impl<I: Interrupt> interrupt::typelevel::Handler<I> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        let r = RTC::regs();
+
+        // The flags are not write protected, so they can be cleared without unlocking the RTC.
+        #[cfg(not(any(rtc_v3, rtc_v3u5, rtc_v3l5)))]
+        let (alarm, wakeup, timestamp) = {
+            let isr = r.isr().read();
+            let cr = r.cr().read();
+            let alarm = [isr.alrf(0) && cr.alrie(0), isr.alrf(1) && cr.alrie(1)];
+            let wakeup = isr.wutf() && cr.wutie();
+            let timestamp = isr.tsf() && cr.tsie();
+
+            let ts = timestamp.then(read_timestamp);
+            r.isr().modify(|w| {
+                for n in 0..2 {
+                    if alarm[n] {
+                        w.set_alrf(n, false);
+                    }
+                }
+                if wakeup {
+                    w.set_wutf(false);
+                }
+                if timestamp {
+                    w.set_tsf(false);
+                    w.set_tsovf(false);
+                }
+            });
+            (alarm, wakeup, ts)
+        };
+
+        #[cfg(any(rtc_v3, rtc_v3u5, rtc_v3l5))]
+        let (alarm, wakeup, timestamp) = {
+            let sr = r.sr().read();
+            let cr = r.cr().read();
+            let alarm = [sr.alrf(0) && cr.alrie(0), sr.alrf(1) && cr.alrie(1)];
+            let wakeup = sr.wutf() && cr.wutie();
+            let timestamp = sr.tsf() && cr.tsie();
+
+            let ts = timestamp.then(read_timestamp);
+            r.scr().write(|w| {
+                for n in 0..2 {
+                    if alarm[n] {
+                        w.set_calrf(n, Calrf::CLEAR);
+                    }
+                }
+                if wakeup {
+                    w.set_cwutf(Calrf::CLEAR);
+                }
+                if timestamp {
+                    w.set_ctsf(Calrf::CLEAR);
+                    w.set_ctsovf(Calrf::CLEAR);
+                }
+            });
+            (alarm, wakeup, ts)
+        };
+
+        #[cfg(any(stm32f4, stm32l4, stm32l0, stm32g4))]
+        for line in EXTI_LINES {
+            crate::pac::EXTI.pr(0).write(|w| w.set_line(line, true));
+        }
+
+        let mut pending = 0;
+        if alarm[0] {
+            pending |= PENDING_ALARM_A;
+        }
+        if alarm[1] {
+            pending |= PENDING_ALARM_B;
+        }
+        if wakeup {
+            pending |= PENDING_WAKEUP;
+        }
+        PENDING.fetch_or(pending, Ordering::SeqCst);
+
+        if let Some(ts) = timestamp {
+            TIMESTAMP.lock(|t| t.set(ts));
+        }
+
+        WAKER.wake();
+    }
+}
+
+/// RTC alarm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Alarm {
+    /// Alarm A
+    A,
+    /// Alarm B
+    B,
+}
+
+impl Alarm {
+    fn index(self) -> usize {
+        match self {
+            Alarm::A => 0,
+            Alarm::B => 1,
+        }
+    }
+
+    fn pending_bit(self) -> u8 {
+        match self {
+            Alarm::A => PENDING_ALARM_A,
+            Alarm::B => PENDING_ALARM_B,
+        }
+    }
+}
+
+/// Day matched by an alarm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlarmDay {
+    /// Day of the month, 1..=31
+    Date(u8),
+    /// Day of the week
+    Weekday(DayOfWeek),
+}
+
+/// Alarm configuration.
+///
+/// Every field set to `None` is ignored when comparing against the calendar, so an alarm with only
+/// `second: Some(0)` fires every minute.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AlarmConfig {
+    /// Day to match
+    pub day: Option<AlarmDay>,
+    /// Hour to match, 0..=23
+    pub hour: Option<u8>,
+    /// Minute to match, 0..=59
+    pub minute: Option<u8>,
+    /// Second to match, 0..=59
+    pub second: Option<u8>,
+}
+
+impl AlarmConfig {
+    /// Match the date and time of `datetime`.
+    ///
+    /// The month and year are not compared, so the alarm fires once a month. Use
+    /// [`Rtc::wait_until`] to wait for a date further in the future.
+    pub fn at(datetime: &DateTime) -> Self {
+        Self {
+            day: Some(AlarmDay::Date(datetime.day())),
+            hour: Some(datetime.hour()),
+            minute: Some(datetime.minute()),
+            second: Some(datetime.second()),
+        }
+    }
+
+    /// Match the given time of every day.
+    pub fn daily(hour: u8, minute: u8, second: u8) -> Self {
+        Self {
+            day: None,
+            hour: Some(hour),
+            minute: Some(minute),
+            second: Some(second),
+        }
+    }
+
+    /// Value of the ALRMxR register.
+    fn to_bits(self) -> Result<u32, RtcError> {
+        use super::DateTimeError;
+
+        fn bcd(v: u8) -> u32 {
+            (((v / 10) << 4) | (v % 10)) as u32
+        }
+
+        // Each field is masked (MSKx set) when it is not compared.
+        let mut bits = 0;
+        match self.second {
+            Some(s) if s > 59 => return Err(RtcError::InvalidDateTime(DateTimeError::InvalidSecond)),
+            Some(s) => bits |= bcd(s),
+            None => bits |= 1 << 7,
+        }
+        match self.minute {
+            Some(m) if m > 59 => return Err(RtcError::InvalidDateTime(DateTimeError::InvalidMinute)),
+            Some(m) => bits |= bcd(m) << 8,
+            None => bits |= 1 << 15,
+        }
+        match self.hour {
+            Some(h) if h > 23 => return Err(RtcError::InvalidDateTime(DateTimeError::InvalidHour)),
+            Some(h) => bits |= bcd(h) << 16,
+            None => bits |= 1 << 23,
+        }
+        match self.day {
+            Some(AlarmDay::Date(d)) if !(1..=31).contains(&d) => {
+                return Err(RtcError::InvalidDateTime(DateTimeError::InvalidDay))
+            }
+            Some(AlarmDay::Date(d)) => bits |= bcd(d) << 24,
+            // WDSEL: the units of the date are the day of the week
+            Some(AlarmDay::Weekday(wd)) => bits |= (1 << 30) | ((wd as u32) << 24),
+            None => bits |= 1 << 31,
+        }
+        Ok(bits)
+    }
+}
+
+/// Date and time of a timestamp event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timestamp {
+    /// Date and time of the event.
+    ///
+    /// The hardware doesn't record the year, it is taken from the calendar when the event is read.
+    pub datetime: DateTime,
+    /// Sub-second counter at the event. It counts down from the synchronous prescaler value.
+    pub subsecond: u16,
+}
+
+/// Timestamp event edge.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TimestampEdge {
+    /// Capture on the rising edge of the RTC_TS pin
+    Rising,
+    /// Capture on the falling edge of the RTC_TS pin
+    Falling,
+}
+
+fn read_timestamp() -> Timestamp {
+    let r = RTC::regs();
+    let tr = r.tstr().read();
+    let dr = r.tsdr().read();
+    let subsecond = r.tsssr().read().ss() as u16;
+
+    let second = bcd2_to_byte((tr.st(), tr.su()));
+    let minute = bcd2_to_byte((tr.mnt(), tr.mnu()));
+    let hour = bcd2_to_byte((tr.ht(), tr.hu()));
+    let day = bcd2_to_byte((dr.dt(), dr.du()));
+    let month = bcd2_to_byte((dr.mt() as u8, dr.mu()));
+    let weekday = day_of_week_from_u8(dr.wdu()).unwrap_or(DayOfWeek::Monday);
+
+    // The year is filled in by `Rtc::wait_timestamp`.
+    Timestamp {
+        datetime: DateTime::from(2000, month.max(1), day.max(1), weekday, hour, minute, second)
+            .unwrap_or(DateTime::from(2000, 1, 1, DayOfWeek::Monday, 0, 0, 0).unwrap()),
+        subsecond,
+    }
+}
+
+/// Wait for the interrupt handler to report `bit`.
+async fn wait_pending(bit: u8) {
+    poll_fn(|cx| {
+        WAKER.register(cx.waker());
+        if PENDING.fetch_and(!bit, Ordering::SeqCst) & bit != 0 {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+impl Rtc {
+    /// Enable the RTC interrupt `I` in the NVIC, and the EXTI lines of the RTC events.
+    ///
+    /// Call it for each interrupt [`InterruptHandler`] is bound to before using the async methods
+    /// of this module.
+    pub fn enable_interrupt<I: Interrupt>(&mut self, _irq: impl interrupt::typelevel::Binding<I, InterruptHandler>) {
+        #[cfg(any(stm32f4, stm32l4, stm32l0, stm32g4))]
+        critical_section::with(|_| {
+            use crate::pac::EXTI;
+            for line in EXTI_LINES {
+                EXTI.rtsr(0).modify(|w| w.set_line(line, true));
+                EXTI.imr(0).modify(|w| w.set_line(line, true));
+            }
+        });
+
+        I::unpend();
+        unsafe { I::enable() };
+    }
+
+    /// Configure and enable `alarm`.
+    ///
+    /// The alarm fires every time the calendar matches `config`; wait for it with
+    /// [`wait_alarm`](Self::wait_alarm).
+    pub fn set_alarm(&mut self, alarm: Alarm, config: AlarmConfig) -> Result<(), RtcError> {
+        let n = alarm.index();
+        let bits = config.to_bits()?;
+
+        PENDING.fetch_and(!alarm.pending_bit(), Ordering::SeqCst);
+        self.write(false, |r| {
+            r.cr().modify(|w| {
+                w.set_alre(n, false);
+                w.set_alrie(n, false);
+            });
+
+            // The alarm registers can only be written once the alarm is disabled, which takes a
+            // couple of RTCCLK cycles on RTC v2.
+            #[cfg(not(any(rtc_v3, rtc_v3u5, rtc_v3l5)))]
+            {
+                while !r.isr().read().alrwf(n) {}
+                r.isr().modify(|w| w.set_alrf(n, false));
+            }
+            #[cfg(any(rtc_v3, rtc_v3u5, rtc_v3l5))]
+            r.scr().write(|w| w.set_calrf(n, Calrf::CLEAR));
+
+            r.alrmr(n).write_value(crate::pac::rtc::regs::Alrmr(bits));
+            r.alrmssr(n).write(|w| w.set_maskss(0));
+
+            r.cr().modify(|w| {
+                w.set_alre(n, true);
+                w.set_alrie(n, true);
+            });
+        });
+
+        Ok(())
+    }
+
+    /// Disable `alarm`.
+    pub fn disable_alarm(&mut self, alarm: Alarm) {
+        let n = alarm.index();
+        self.write(false, |r| {
+            r.cr().modify(|w| {
+                w.set_alre(n, false);
+                w.set_alrie(n, false);
+            });
+        });
+        PENDING.fetch_and(!alarm.pending_bit(), Ordering::SeqCst);
+    }
+
+    /// Wait for the next time `alarm` fires.
+    pub async fn wait_alarm(&mut self, alarm: Alarm) {
+        wait_pending(alarm.pending_bit()).await
+    }
+
+    /// Wait until the calendar reaches `target`, which can be any number of days in the future.
+    ///
+    /// This uses `alarm`, which is disabled on return. With the `chrono` feature, the target is
+    /// easily computed from the current date, e.g. `(now + chrono::Duration::days(3)).into()`.
+    pub async fn wait_until(&mut self, alarm: Alarm, target: DateTime) -> Result<(), RtcError> {
+        // The alarm only compares the day of the month, so it may fire up to once a month before
+        // the target is actually reached.
+        self.set_alarm(alarm, AlarmConfig::at(&target))?;
+        let res = loop {
+            match self.now() {
+                Ok(now) if sort_key(&now) >= sort_key(&target) => break Ok(()),
+                Ok(_) => self.wait_alarm(alarm).await,
+                Err(e) => break Err(e),
+            }
+        };
+        self.disable_alarm(alarm);
+        res
+    }
+
+    /// Enable the wakeup timer to fire every `period`.
+    ///
+    /// Periods up to 32 s are counted from RTCCLK / 16 with a resolution of 16 RTCCLK cycles, longer
+    /// periods are counted in seconds from the calendar clock, up to about 36 hours.
+    ///
+    /// With the `low-power` feature, the wakeup timer is used for time keeping in stop modes unless
+    /// the LPTIM time driver is selected, so it is not available.
+    #[cfg(all(feature = "time", any(not(feature = "low-power"), time_driver_lptim)))]
+    pub fn enable_periodic_wakeup(&mut self, period: embassy_time::Duration) {
+        use crate::pac::rtc::vals::Wucksel;
+
+        let rtc_hz = Self::frequency().0 as u64;
+        let div16_ticks = period.as_micros() * rtc_hz / 16 / 1_000_000;
+
+        // WUCKSEL: RTCCLK / 16, or ck_spre (1 Hz) with the 2^16 offset for the longest periods
+        let (wucksel, ticks) = if div16_ticks <= 0x1_0000 {
+            (Wucksel::DIV16, div16_ticks.max(1))
+        } else {
+            let secs = period.as_secs().clamp(1, 0x2_0000);
+            if secs <= 0x1_0000 {
+                (Wucksel::from_bits(0b100), secs)
+            } else {
+                (Wucksel::from_bits(0b110), secs - 0x1_0000)
+            }
+        };
+
+        PENDING.fetch_and(!PENDING_WAKEUP, Ordering::SeqCst);
+        self.write(false, |r| {
+            r.cr().modify(|w| {
+                w.set_wute(false);
+                w.set_wutie(false);
+            });
+
+            #[cfg(not(any(rtc_v3, rtc_v3u5, rtc_v3l5)))]
+            {
+                while !r.isr().read().wutwf() {}
+                r.isr().modify(|w| w.set_wutf(false));
+            }
+            #[cfg(any(rtc_v3, rtc_v3u5, rtc_v3l5))]
+            {
+                while !r.icsr().read().wutwf() {}
+                r.scr().write(|w| w.set_cwutf(Calrf::CLEAR));
+            }
+
+            r.cr().modify(|w| w.set_wucksel(wucksel));
+            // The timer fires every WUT + 1 ticks.
+            r.wutr().write(|w| w.set_wut((ticks - 1) as u16));
+            r.cr().modify(|w| {
+                w.set_wute(true);
+                w.set_wutie(true);
+            });
+        });
+    }
+
+    /// Disable the wakeup timer.
+    #[cfg(all(feature = "time", any(not(feature = "low-power"), time_driver_lptim)))]
+    pub fn disable_periodic_wakeup(&mut self) {
+        self.write(false, |r| {
+            r.cr().modify(|w| {
+                w.set_wute(false);
+                w.set_wutie(false);
+            });
+        });
+        PENDING.fetch_and(!PENDING_WAKEUP, Ordering::SeqCst);
+    }
+
+    /// Wait for the next period of the wakeup timer.
+    ///
+    /// Periods elapsed while nobody was waiting are coalesced into one.
+    #[cfg(all(feature = "time", any(not(feature = "low-power"), time_driver_lptim)))]
+    pub async fn wait_wakeup(&mut self) {
+        wait_pending(PENDING_WAKEUP).await
+    }
+
+    /// Enable timestamp capture on `edge` of the RTC_TS pin.
+    ///
+    /// The pin must be configured by the application, it is an additional function of the GPIO
+    /// that doesn't need an alternate function.
+    pub fn enable_timestamp(&mut self, edge: TimestampEdge) {
+        TIMESTAMP.lock(|t| t.set(None));
+        self.write(false, |r| {
+            r.cr().modify(|w| {
+                w.set_tse(false);
+                w.set_tsie(false);
+            });
+            // TSEDGE is bit 3 of CR on all RTC versions; its PAC type differs between them.
+            r.cr().modify(|w| match edge {
+                TimestampEdge::Rising => w.0 &= !(1 << 3),
+                TimestampEdge::Falling => w.0 |= 1 << 3,
+            });
+            r.cr().modify(|w| {
+                w.set_tse(true);
+                w.set_tsie(true);
+            });
+        });
+    }
+
+    /// Disable timestamp capture.
+    pub fn disable_timestamp(&mut self) {
+        self.write(false, |r| {
+            r.cr().modify(|w| {
+                w.set_tse(false);
+                w.set_tsie(false);
+            });
+        });
+        TIMESTAMP.lock(|t| t.set(None));
+    }
+
+    /// Wait for the next timestamp event and return its date and time.
+    pub async fn wait_timestamp(&mut self) -> Result<Timestamp, RtcError> {
+        let mut ts = poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            match TIMESTAMP.lock(|t| t.take()) {
+                Some(ts) => Poll::Ready(ts),
+                None => Poll::Pending,
+            }
+        })
+        .await;
+
+        // The event happened in the current year, unless the calendar has wrapped to a new year since.
+        let now = self.now()?;
+        let d = ts.datetime;
+        let year = if (d.month(), d.day()) > (now.month(), now.day()) {
+            now.year() - 1
+        } else {
+            now.year()
+        };
+        ts.datetime = DateTime::from(
+            year,
+            d.month(),
+            d.day(),
+            d.day_of_week(),
+            d.hour(),
+            d.minute(),
+            d.second(),
+        )
+        .map_err(RtcError::InvalidDateTime)?;
+
+        Ok(ts)
+    }
+}
+
+fn sort_key(d: &DateTime) -> (u16, u8, u8, u8, u8, u8) {
+    (d.year(), d.month(), d.day(), d.hour(), d.minute(), d.second())
+}
@@ -24,6 +24,7 @@ pub enum Error {
 }
 
 /// Structure containing date and time information
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct DateTime {
     /// 0..4095
     year: u16,
@@ -1,4 +1,6 @@
 //! Real Time Clock (RTC)
+#[cfg(any(stm32f4, stm32l0, stm32l4, stm32g0, stm32g4, stm32l5, stm32h5, stm32u5))]
+mod alarm;
 mod datetime;
 
 #[cfg(feature = "low-power")]
@@ -12,6 +14,8 @@ use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 #[cfg(feature = "low-power")]
 use embassy_sync::blocking_mutex::Mutex;
 
+#[cfg(any(stm32f4, stm32l0, stm32l4, stm32g0, stm32g4, stm32l5, stm32h5, stm32u5))]
+pub use self::alarm::{Alarm, AlarmConfig, AlarmDay, InterruptHandler, Timestamp, TimestampEdge};
 use self::datetime::{day_of_week_from_u8, day_of_week_to_u8};
 pub use self::datetime::{DateTime, DayOfWeek, Error as DateTimeError};
 use crate::pac::rtc::regs::{Dr, Tr};
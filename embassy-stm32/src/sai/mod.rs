@@ -12,6 +12,7 @@ use crate::dma::{ringbuffer, Channel, ReadableRingBuffer, Request, TransferOptio
 use crate::gpio::{AfType, AnyPin, OutputType, Pull, SealedPin as _, Speed};
 use crate::pac::sai::{vals, Sai as Regs};
 use crate::rcc::{self, RccPeripheral};
+use crate::time::Hertz;
 use crate::{peripherals, Peripheral};
 
 /// SAI error
@@ -106,6 +107,20 @@ pub enum DataSize {
 }
 
 impl DataSize {
+    const fn slot_bits(&self) -> u8 {
+        match self {
+            DataSize::Data8 | DataSize::Data10 | DataSize::Data16 => 16,
+            DataSize::Data20 | DataSize::Data24 | DataSize::Data32 => 32,
+        }
+    }
+
+    const fn slot_size(&self) -> SlotSize {
+        match self.slot_bits() {
+            16 => SlotSize::Channel16,
+            _ => SlotSize::Channel32,
+        }
+    }
+
     #[cfg(any(sai_v1, sai_v2, sai_v3_2pdm, sai_v3_4pdm, sai_v4_2pdm, sai_v4_4pdm))]
     const fn ds(&self) -> vals::Ds {
         match self {
@@ -575,6 +590,133 @@ impl MasterClockDivider {
             MasterClockDivider::Div63 => 63,
         }
     }
+
+    /// Get the master clock divider dividing the kernel clock by `divisor`, if there is one.
+    #[cfg(any(sai_v1, sai_v2))]
+    pub const fn from_divisor(divisor: u32) -> Option<Self> {
+        match divisor {
+            1 => Some(MasterClockDivider::Div1),
+            2 => Some(MasterClockDivider::Div2),
+            4 => Some(MasterClockDivider::Div4),
+            6 => Some(MasterClockDivider::Div6),
+            8 => Some(MasterClockDivider::Div8),
+            10 => Some(MasterClockDivider::Div10),
+            12 => Some(MasterClockDivider::Div12),
+            14 => Some(MasterClockDivider::Div14),
+            16 => Some(MasterClockDivider::Div16),
+            18 => Some(MasterClockDivider::Div18),
+            20 => Some(MasterClockDivider::Div20),
+            22 => Some(MasterClockDivider::Div22),
+            24 => Some(MasterClockDivider::Div24),
+            26 => Some(MasterClockDivider::Div26),
+            28 => Some(MasterClockDivider::Div28),
+            30 => Some(MasterClockDivider::Div30),
+            _ => None,
+        }
+    }
+
+    /// Get the master clock divider dividing the kernel clock by `divisor`, if there is one.
+    #[cfg(any(sai_v3_2pdm, sai_v3_4pdm, sai_v4_2pdm, sai_v4_4pdm))]
+    pub const fn from_divisor(divisor: u32) -> Option<Self> {
+        match divisor {
+            1 => Some(MasterClockDivider::Div1),
+            2 => Some(MasterClockDivider::Div2),
+            3 => Some(MasterClockDivider::Div3),
+            4 => Some(MasterClockDivider::Div4),
+            5 => Some(MasterClockDivider::Div5),
+            6 => Some(MasterClockDivider::Div6),
+            7 => Some(MasterClockDivider::Div7),
+            8 => Some(MasterClockDivider::Div8),
+            9 => Some(MasterClockDivider::Div9),
+            10 => Some(MasterClockDivider::Div10),
+            11 => Some(MasterClockDivider::Div11),
+            12 => Some(MasterClockDivider::Div12),
+            13 => Some(MasterClockDivider::Div13),
+            14 => Some(MasterClockDivider::Div14),
+            15 => Some(MasterClockDivider::Div15),
+            16 => Some(MasterClockDivider::Div16),
+            17 => Some(MasterClockDivider::Div17),
+            18 => Some(MasterClockDivider::Div18),
+            19 => Some(MasterClockDivider::Div19),
+            20 => Some(MasterClockDivider::Div20),
+            21 => Some(MasterClockDivider::Div21),
+            22 => Some(MasterClockDivider::Div22),
+            23 => Some(MasterClockDivider::Div23),
+            24 => Some(MasterClockDivider::Div24),
+            25 => Some(MasterClockDivider::Div25),
+            26 => Some(MasterClockDivider::Div26),
+            27 => Some(MasterClockDivider::Div27),
+            28 => Some(MasterClockDivider::Div28),
+            29 => Some(MasterClockDivider::Div29),
+            30 => Some(MasterClockDivider::Div30),
+            31 => Some(MasterClockDivider::Div31),
+            32 => Some(MasterClockDivider::Div32),
+            33 => Some(MasterClockDivider::Div33),
+            34 => Some(MasterClockDivider::Div34),
+            35 => Some(MasterClockDivider::Div35),
+            36 => Some(MasterClockDivider::Div36),
+            37 => Some(MasterClockDivider::Div37),
+            38 => Some(MasterClockDivider::Div38),
+            39 => Some(MasterClockDivider::Div39),
+            40 => Some(MasterClockDivider::Div40),
+            41 => Some(MasterClockDivider::Div41),
+            42 => Some(MasterClockDivider::Div42),
+            43 => Some(MasterClockDivider::Div43),
+            44 => Some(MasterClockDivider::Div44),
+            45 => Some(MasterClockDivider::Div45),
+            46 => Some(MasterClockDivider::Div46),
+            47 => Some(MasterClockDivider::Div47),
+            48 => Some(MasterClockDivider::Div48),
+            49 => Some(MasterClockDivider::Div49),
+            50 => Some(MasterClockDivider::Div50),
+            51 => Some(MasterClockDivider::Div51),
+            52 => Some(MasterClockDivider::Div52),
+            53 => Some(MasterClockDivider::Div53),
+            54 => Some(MasterClockDivider::Div54),
+            55 => Some(MasterClockDivider::Div55),
+            56 => Some(MasterClockDivider::Div56),
+            57 => Some(MasterClockDivider::Div57),
+            58 => Some(MasterClockDivider::Div58),
+            59 => Some(MasterClockDivider::Div59),
+            60 => Some(MasterClockDivider::Div60),
+            61 => Some(MasterClockDivider::Div61),
+            62 => Some(MasterClockDivider::Div62),
+            63 => Some(MasterClockDivider::Div63),
+            _ => None,
+        }
+    }
+
+    /// Get the master clock divider producing the sample rate closest to `sample_rate` from
+    /// `kernel_clock`.
+    ///
+    /// With the master clock enabled, the SAI runs MCLK at 256 times the sample rate, so the
+    /// actual rate is only exact when `kernel_clock` is a multiple of `256 * sample_rate`.
+    /// Returns `None` if the required division is out of range.
+    pub const fn from_sample_rate(kernel_clock: Hertz, sample_rate: Hertz) -> Option<Self> {
+        let mclk = sample_rate.0 as u64 * 256;
+        if mclk == 0 {
+            return None;
+        }
+
+        let divisor = ((kernel_clock.0 as u64 + mclk / 2) / mclk) as u32;
+        if divisor == 0 {
+            return None;
+        }
+        if let Some(div) = Self::from_divisor(divisor) {
+            return Some(div);
+        }
+
+        // Only even divisors exist on some SAI versions, pick the closest one.
+        let below = divisor.saturating_sub(1);
+        let above = divisor + 1;
+        let below_err = (below as u64 * mclk).abs_diff(kernel_clock.0 as u64);
+        let above_err = (above as u64 * mclk).abs_diff(kernel_clock.0 as u64);
+        match (Self::from_divisor(below), Self::from_divisor(above)) {
+            (Some(b), Some(a)) => Some(if below_err <= above_err { b } else { a }),
+            (Some(b), None) => Some(b),
+            (None, a) => a,
+        }
+    }
 }
 
 /// [`SAI`] configuration.
@@ -648,6 +790,65 @@ impl Config {
     pub fn new() -> Self {
         return Default::default();
     }
+
+    /// Create a config for a standard (Philips) I2S stereo stream.
+    ///
+    /// Samples of up to 16 bits use 16 bit slots, larger samples use 32 bit slots.
+    pub fn i2s(mode: Mode, tx_rx: TxRx, data_size: DataSize) -> Self {
+        let slot_bits = data_size.slot_bits();
+
+        Self {
+            mode,
+            tx_rx,
+            data_size,
+            slot_size: data_size.slot_size(),
+            slot_count: word::U4(2),
+            slot_enable: 0b11,
+            bit_order: BitOrder::MsbFirst,
+            frame_sync_offset: FrameSyncOffset::BeforeFirstBit,
+            frame_sync_polarity: FrameSyncPolarity::ActiveLow,
+            frame_sync_definition: FrameSyncDefinition::ChannelIdentification,
+            frame_sync_active_level_length: word::U7(slot_bits),
+            frame_length: 2 * slot_bits,
+            clock_strobe: Self::clock_strobe_for(tx_rx),
+            ..Default::default()
+        }
+    }
+
+    /// Create a config for a TDM stream of `slots` slots, all enabled.
+    ///
+    /// Slots are sized as in [`Config::i2s`], and the frame sync is a one bit clock pulse before
+    /// the first slot, as used by most multichannel codecs. A frame is at most 255 bits, so up to
+    /// 15 slots of 16 bits or 7 slots of 32 bits fit.
+    pub fn tdm(mode: Mode, tx_rx: TxRx, data_size: DataSize, slots: u8) -> Self {
+        let slot_bits = data_size.slot_bits();
+        assert!(slots >= 1 && slots as u16 * slot_bits as u16 <= 255);
+
+        Self {
+            mode,
+            tx_rx,
+            data_size,
+            slot_size: data_size.slot_size(),
+            slot_count: word::U4(slots),
+            slot_enable: (1 << slots) - 1,
+            bit_order: BitOrder::MsbFirst,
+            frame_sync_offset: FrameSyncOffset::BeforeFirstBit,
+            frame_sync_polarity: FrameSyncPolarity::ActiveHigh,
+            frame_sync_definition: FrameSyncDefinition::StartOfFrame,
+            frame_sync_active_level_length: word::U7(1),
+            frame_length: slots * slot_bits,
+            clock_strobe: Self::clock_strobe_for(tx_rx),
+            ..Default::default()
+        }
+    }
+
+    // Transmitters drive data on the falling edge, so that receivers sample it on the rising edge.
+    const fn clock_strobe_for(tx_rx: TxRx) -> ClockStrobe {
+        match tx_rx {
+            TxRx::Transmitter => ClockStrobe::Falling,
+            TxRx::Receiver => ClockStrobe::Rising,
+        }
+    }
 }
 
 #[cfg(not(gpdma))]
@@ -742,6 +943,15 @@ pub fn split_subblocks<'d, T: Instance>(peri: impl Peripheral<P = T> + 'd) -> (S
     )
 }
 
+impl<'d, T: Instance, S: SubBlockInstance> SubBlock<'d, T, S> {
+    /// Get the master clock divider producing `sample_rate` from the SAI kernel clock.
+    ///
+    /// See [`MasterClockDivider::from_sample_rate`].
+    pub fn master_clock_divider(&self, sample_rate: Hertz) -> Option<MasterClockDivider> {
+        MasterClockDivider::from_sample_rate(rcc::frequency::<T>(), sample_rate)
+    }
+}
+
 /// SAI sub-block driver.
 pub struct Sai<'d, T: Instance, W: word::Word> {
     _peri: PeripheralRef<'d, T>,
@@ -956,6 +1166,9 @@ impl<'d, T: Instance, W: word::Word> Sai<'d, T, W> {
     }
 
     /// Start the SAI driver.
+    ///
+    /// A transmitter starts with whatever is in its DMA buffer, so fill it first with
+    /// [`write_immediate`](Self::write_immediate) to avoid an initial burst of silence.
     pub fn start(&mut self) {
         match self.ring_buffer {
             RingBuffer::Writable(ref mut rb) => {
@@ -1039,6 +1252,39 @@ impl<'d, T: Instance, W: word::Word> Sai<'d, T, W> {
             _ => Err(Error::NotAReceiver),
         }
     }
+
+    /// Write data directly into the DMA buffer, without waiting.
+    ///
+    /// This is meant to prime the buffer before [`start`](Self::start). Returns the number of
+    /// words written.
+    pub fn write_immediate(&mut self, data: &[W]) -> Result<usize, Error> {
+        match &mut self.ring_buffer {
+            RingBuffer::Writable(buffer) => Ok(buffer.write_immediate(data)?.0),
+            _ => Err(Error::NotATransmitter),
+        }
+    }
+
+    /// Stop streaming once the current buffer has been transferred.
+    ///
+    /// A transmitter plays out the data already written, a receiver fills the rest of its
+    /// buffer.
+    pub async fn stop(&mut self) {
+        match &mut self.ring_buffer {
+            RingBuffer::Writable(buffer) => buffer.stop().await,
+            RingBuffer::Readable(buffer) => buffer.stop().await,
+        }
+    }
+
+    /// Size of the DMA buffer, in words.
+    ///
+    /// Reading or writing half of it at a time makes each call complete exactly when the DMA
+    /// finishes a half, which turns the ring buffer into a classic double buffer.
+    pub fn buffer_len(&self) -> usize {
+        match &self.ring_buffer {
+            RingBuffer::Writable(buffer) => buffer.capacity(),
+            RingBuffer::Readable(buffer) => buffer.capacity(),
+        }
+    }
 }
 
 impl<'d, T: Instance, W: word::Word> Drop for Sai<'d, T, W> {
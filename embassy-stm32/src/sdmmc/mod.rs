@@ -146,6 +146,8 @@ pub enum Error {
     /// ST bit error.
     #[cfg(sdmmc_v1)]
     StBitErr,
+    /// The SDIO card reported an error in its response flags.
+    SdioError,
 }
 
 /// A SD command
@@ -182,6 +184,41 @@ impl Card {
     }
 }
 
+/// SDIO card
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SdioCard {
+    /// Relative Card Address
+    pub rca: u32,
+    /// I/O Operation Conditions Register, as returned by CMD5
+    pub ocr: u32,
+    /// Number of I/O functions, not counting function 0
+    pub num_functions: u8,
+    /// Whether the card also contains SD memory (combo card)
+    pub memory_present: bool,
+    /// Standard SDIO function interface codes from the FBRs, for functions 1 to 7
+    pub function_codes: [u8; 7],
+    /// Block size of each function, in bytes
+    block_sizes: [u16; 8],
+}
+
+// Card Common Control Registers (CCCR) addresses, in function 0.
+const CCCR_IO_ENABLE: u32 = 0x02;
+const CCCR_IO_READY: u32 = 0x03;
+const CCCR_INT_ENABLE: u32 = 0x04;
+const CCCR_INT_PENDING: u32 = 0x05;
+const CCCR_BUS_INTERFACE_CONTROL: u32 = 0x07;
+const CCCR_CARD_CAPABILITY: u32 = 0x08;
+const CCCR_BUS_SPEED_SELECT: u32 = 0x13;
+
+/// Address of the Function Basic Registers (FBR) of `function`.
+const fn fbr(function: u8) -> u32 {
+    function as u32 * 0x100
+}
+
+/// R5 response flags signalling an error: COM_CRC_ERROR, ILLEGAL_COMMAND, ERROR,
+/// FUNCTION_NUMBER and OUT_OF_RANGE.
+const R5_ERROR_FLAGS: u32 = 0xcb00;
+
 #[repr(u8)]
 enum PowerCtrl {
     Off = 0b00,
@@ -286,16 +323,21 @@ const DMA_TRANSFER_OPTIONS: crate::dma::TransferOptions = crate::dma::TransferOp
 ///
 /// Default values:
 /// data_transfer_timeout: 5_000_000
+/// sdio_enable_retries: 100_000
 #[non_exhaustive]
 pub struct Config {
     /// The timeout to be set for data transfers, in card bus clock periods
     pub data_transfer_timeout: u32,
+    /// How many times the I/O ready register is read while waiting for an SDIO function to be
+    /// ready, before giving up with [`Error::Timeout`]
+    pub sdio_enable_retries: u32,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             data_transfer_timeout: 5_000_000,
+            sdio_enable_retries: 100_000,
         }
     }
 }
@@ -320,6 +362,8 @@ pub struct Sdmmc<'d, T: Instance, Dma: SdmmcDma<T> = NoDma> {
     signalling: Signalling,
     /// Card
     card: Option<Card>,
+    /// SDIO card
+    sdio_card: Option<SdioCard>,
 
     /// An optional buffer to be used for commands
     /// This should be used if there are special memory location requirements for dma
@@ -527,6 +571,7 @@ impl<'d, T: Instance, Dma: SdmmcDma<T> + 'd> Sdmmc<'d, T, Dma> {
             clock: SD_INIT_FREQ,
             signalling: Default::default(),
             card: None,
+            sdio_card: None,
             cmd_block: None,
         }
     }
@@ -1322,6 +1367,367 @@ impl<'d, T: Instance, Dma: SdmmcDma<T> + 'd> Sdmmc<'d, T, Dma> {
         }
     }
 
+    /// Waits for the end of a data transfer started by a command.
+    async fn wait_data_transfer() -> Result<(), Error> {
+        let regs = T::regs();
+        let res = poll_fn(|cx| {
+            T::state().register(cx.waker());
+            let status = regs.star().read();
+
+            if status.dcrcfail() {
+                return Poll::Ready(Err(Error::Crc));
+            }
+            if status.dtimeout() {
+                return Poll::Ready(Err(Error::Timeout));
+            }
+            #[cfg(sdmmc_v1)]
+            if status.stbiterr() {
+                return Poll::Ready(Err(Error::StBitErr));
+            }
+            if status.dataend() {
+                return Poll::Ready(Ok(()));
+            }
+            Poll::Pending
+        })
+        .await;
+        Self::clear_interrupt_flags();
+        res
+    }
+
+    /// Initializes an SDIO card (if present), enumerates its functions and sets the bus at the
+    /// specified frequency.
+    ///
+    /// The I/O functions are left disabled, see [`sdio_enable_function`](Self::sdio_enable_function).
+    pub async fn init_sdio(&mut self, freq: Hertz) -> Result<(), Error> {
+        let regs = T::regs();
+        let ker_ck = T::frequency();
+
+        let bus_width = match self.d3.is_some() {
+            true => BusWidth::Four,
+            false => BusWidth::One,
+        };
+
+        // While the card is in identification mode, the SDMMC_CK frequency must be no more
+        // than 400 kHz.
+        let (_bypass, clkdiv, init_clock) = unwrap!(clk_div(ker_ck, SD_INIT_FREQ.0));
+        self.clock = init_clock;
+        self.card = None;
+        self.sdio_card = None;
+
+        // CPSMACT and DPSMACT must be 0 to set WIDBUS
+        Self::wait_idle();
+
+        regs.clkcr().modify(|w| {
+            w.set_widbus(0);
+            w.set_clkdiv(clkdiv);
+            #[cfg(sdmmc_v1)]
+            w.set_bypass(_bypass);
+        });
+
+        regs.power().modify(|w| w.set_pwrctrl(PowerCtrl::On as u8));
+        Self::cmd(Cmd::idle(), false)?;
+
+        // The R4 response to CMD5 has no CRC, so CRC failures are expected.
+        let ocr = match Self::cmd(Cmd::io_send_op_cond(0), false) {
+            Ok(_) | Err(Error::Crc) => regs.respr(0).read().cardstatus(),
+            Err(Error::Timeout) => return Err(Error::UnsupportedCardType),
+            Err(e) => return Err(e),
+        };
+
+        let mut card = SdioCard {
+            num_functions: ((ocr >> 28) & 0x7) as u8,
+            memory_present: ocr & (1 << 27) != 0,
+            ..Default::default()
+        };
+        if card.num_functions == 0 {
+            return Err(Error::UnsupportedCardType);
+        }
+
+        card.ocr = loop {
+            // Request the voltage window supported by the card.
+            match Self::cmd(Cmd::io_send_op_cond(ocr & 0x00ff_ffff), false) {
+                Ok(_) | Err(Error::Crc) => (),
+                Err(e) => return Err(e),
+            }
+            let ocr = regs.respr(0).read().cardstatus();
+            if ocr & (1 << 31) != 0 {
+                // Power up done
+                break ocr;
+            }
+        };
+
+        Self::cmd(Cmd::send_rel_addr(), false)?;
+        card.rca = regs.respr(0).read().cardstatus() >> 16;
+
+        Self::cmd(Cmd::sel_desel_card(card.rca << 16), false)?;
+        self.sdio_card = Some(card);
+
+        // Set bus width. Low-speed cards only support 4 bits if they say so.
+        let caps = self.sdio_read_byte(0, CCCR_CARD_CAPABILITY)?;
+        let low_speed = caps & 0x40 != 0;
+        let width = match bus_width {
+            BusWidth::Four if !low_speed || caps & 0x80 != 0 => {
+                let bic = self.sdio_read_byte(0, CCCR_BUS_INTERFACE_CONTROL)?;
+                self.sdio_write_byte(0, CCCR_BUS_INTERFACE_CONTROL, (bic & !0x03) | 0x02)?;
+                BusWidth::Four
+            }
+            _ => BusWidth::One,
+        };
+
+        // CPSMACT and DPSMACT must be 0 to set WIDBUS
+        Self::wait_idle();
+
+        regs.clkcr().modify(|w| {
+            w.set_widbus(match width {
+                BusWidth::Four => 1,
+                _ => 0,
+            })
+        });
+        regs.dctrl().modify(|w| w.set_sdioen(true));
+
+        // Set Clock. Low-speed cards are limited to 400 kHz, high speed needs to be enabled.
+        let max_freq = if low_speed {
+            SD_INIT_FREQ.0
+        } else if freq.0 > 25_000_000 {
+            let bss = self.sdio_read_byte(0, CCCR_BUS_SPEED_SELECT)?;
+            // Support High Speed
+            if bss & 0x01 != 0 {
+                self.sdio_write_byte(0, CCCR_BUS_SPEED_SELECT, bss | 0x02)?;
+                50_000_000
+            } else {
+                25_000_000
+            }
+        } else {
+            25_000_000
+        };
+        self.clkcr_set_clkdiv(freq.0.min(max_freq), width)?;
+
+        // Enumerate the functions.
+        for function in 1..=card.num_functions {
+            let code = self.sdio_read_byte(0, fbr(function))? & 0x0f;
+            let code = match code {
+                // Extended interface code
+                0x0f => self.sdio_read_byte(0, fbr(function) + 1)?,
+                _ => code,
+            };
+            unwrap!(self.sdio_card.as_mut()).function_codes[function as usize - 1] = code;
+        }
+
+        Ok(())
+    }
+
+    /// Sends CMD52 and returns the data byte of the R5 response.
+    fn io_rw_direct(&self, write: bool, function: u8, address: u32, value: u8) -> Result<u8, Error> {
+        assert!(function <= 7, "SDIO function number up to 7");
+        assert!(address < 1 << 17, "SDIO register address up to 17 bits");
+        self.sdio_card()?;
+
+        let arg = (write as u32) << 31 | (function as u32) << 28 | address << 9 | value as u32;
+        Self::cmd(Cmd::io_rw_direct(arg), false)?; // CMD52
+
+        let r5 = T::regs().respr(0).read().cardstatus();
+        if r5 & R5_ERROR_FLAGS != 0 {
+            return Err(Error::SdioError);
+        }
+        Ok(r5 as u8)
+    }
+
+    /// Reads a register of an SDIO function with CMD52.
+    ///
+    /// Function 0 holds the CCCR and FBR registers.
+    pub fn sdio_read_byte(&mut self, function: u8, address: u32) -> Result<u8, Error> {
+        self.io_rw_direct(false, function, address, 0)
+    }
+
+    /// Writes a register of an SDIO function with CMD52.
+    pub fn sdio_write_byte(&mut self, function: u8, address: u32, value: u8) -> Result<(), Error> {
+        self.io_rw_direct(true, function, address, value).map(|_| ())
+    }
+
+    /// Enables an SDIO function, and waits for it to be ready.
+    ///
+    /// The I/O ready register is read up to [`Config::sdio_enable_retries`] times, yielding in
+    /// between, before [`Error::Timeout`] is returned.
+    pub async fn sdio_enable_function(&mut self, function: u8) -> Result<(), Error> {
+        assert!((1..=7).contains(&function), "SDIO function number from 1 to 7");
+        let mask = 1 << function;
+
+        let enabled = self.sdio_read_byte(0, CCCR_IO_ENABLE)?;
+        self.sdio_write_byte(0, CCCR_IO_ENABLE, enabled | mask)?;
+
+        for _ in 0..self.config.sdio_enable_retries {
+            if self.sdio_read_byte(0, CCCR_IO_READY)? & mask != 0 {
+                return Ok(());
+            }
+            embassy_futures::yield_now().await;
+        }
+        Err(Error::Timeout)
+    }
+
+    /// Sets the block size used by [`sdio_read`](Self::sdio_read) and
+    /// [`sdio_write`](Self::sdio_write) block transfers for a function.
+    ///
+    /// `size` must be a power of two, up to 2048 bytes.
+    pub fn sdio_set_block_size(&mut self, function: u8, size: u16) -> Result<(), Error> {
+        assert!(function <= 7, "SDIO function number up to 7");
+        assert!(size.is_power_of_two() && size <= 2048);
+
+        let [lo, hi] = size.to_le_bytes();
+        // FN0 block size lives in the CCCR, at the same offsets as in the FBRs.
+        self.sdio_write_byte(0, fbr(function) + 0x10, lo)?;
+        self.sdio_write_byte(0, fbr(function) + 0x11, hi)?;
+
+        self.sdio_card.as_mut().ok_or(Error::NoCard)?.block_sizes[function as usize] = size;
+        Ok(())
+    }
+
+    /// Returns the CMD53 argument and the DPSM block size for a transfer of `len` bytes.
+    ///
+    /// Transfers that are a multiple of the function block size use block mode, others byte mode.
+    fn io_rw_extended_arg(
+        &self,
+        write: bool,
+        function: u8,
+        address: u32,
+        increment: bool,
+        len: usize,
+    ) -> Result<(u32, u8), Error> {
+        assert!(function <= 7, "SDIO function number up to 7");
+        assert!(address < 1 << 17, "SDIO register address up to 17 bits");
+        assert!(len > 0);
+
+        let block_size = self.sdio_card()?.block_sizes[function as usize] as usize;
+        let arg = (write as u32) << 31 | (function as u32) << 28 | (increment as u32) << 26 | address << 9;
+
+        if block_size != 0 && len % block_size == 0 && len / block_size < 512 {
+            let blocks = (len / block_size) as u32;
+            Ok((arg | 1 << 27 | blocks, block_size.trailing_zeros() as u8))
+        } else {
+            // The DPSM can only transfer power of two sized blocks.
+            assert!(
+                len.is_power_of_two() && len <= 512,
+                "SDIO byte mode transfer must be a power of two up to 512 bytes"
+            );
+            // A byte count of 0 means 512 bytes.
+            Ok((arg | (len as u32 & 0x1ff), len.trailing_zeros() as u8))
+        }
+    }
+
+    /// Reads data from an SDIO function with CMD53, using DMA.
+    ///
+    /// `address` is incremented for every byte if `increment` is set, otherwise all the data is
+    /// read from the same register (FIFO).
+    pub async fn sdio_read(
+        &mut self,
+        function: u8,
+        address: u32,
+        increment: bool,
+        buffer: &mut [u32],
+    ) -> Result<(), Error> {
+        let len = buffer.len() * 4;
+        let (arg, block_size) = self.io_rw_extended_arg(false, function, address, increment, len)?;
+
+        let on_drop = OnDrop::new(|| Self::on_drop());
+
+        let transfer = Self::prepare_datapath_read(&self.config, &mut self.dma, buffer, len as u32, block_size);
+        InterruptHandler::<T>::data_interrupts(true);
+        Self::cmd(Cmd::io_rw_extended(arg), true)?; // CMD53
+
+        let res = Self::wait_data_transfer().await;
+        if res.is_ok() {
+            on_drop.defuse();
+            Self::stop_datapath();
+            drop(transfer);
+        }
+        res
+    }
+
+    /// Writes data to an SDIO function with CMD53, using DMA.
+    ///
+    /// `address` is incremented for every byte if `increment` is set, otherwise all the data is
+    /// written to the same register (FIFO).
+    pub async fn sdio_write(
+        &mut self,
+        function: u8,
+        address: u32,
+        increment: bool,
+        buffer: &[u32],
+    ) -> Result<(), Error> {
+        let len = buffer.len() * 4;
+        let (arg, block_size) = self.io_rw_extended_arg(true, function, address, increment, len)?;
+
+        let on_drop = OnDrop::new(|| Self::on_drop());
+
+        // sdmmc_v1 uses different cmd/dma order than v2, but only for writes
+        #[cfg(sdmmc_v1)]
+        Self::cmd(Cmd::io_rw_extended(arg), true)?; // CMD53
+
+        let transfer = self.prepare_datapath_write(buffer, len as u32, block_size);
+        InterruptHandler::<T>::data_interrupts(true);
+
+        #[cfg(sdmmc_v2)]
+        Self::cmd(Cmd::io_rw_extended(arg), true)?; // CMD53
+
+        let res = Self::wait_data_transfer().await;
+        if res.is_ok() {
+            on_drop.defuse();
+            Self::stop_datapath();
+            drop(transfer);
+        }
+        res
+    }
+
+    /// Enables the card interrupts of the SDIO functions in `functions`, a bitmask with bit `n`
+    /// standing for function `n`.
+    ///
+    /// Passing `0` disables all the card interrupts.
+    pub fn sdio_set_interrupts(&mut self, functions: u8) -> Result<(), Error> {
+        let functions = functions & 0xfe;
+        // Bit 0 is the interrupt enable master.
+        let ien = if functions != 0 { functions | 0x01 } else { 0 };
+        self.sdio_write_byte(0, CCCR_INT_ENABLE, ien)
+    }
+
+    /// Waits for a card interrupt, and returns the bitmask of the functions with a pending
+    /// interrupt.
+    ///
+    /// The interrupts must be enabled with [`sdio_set_interrupts`](Self::sdio_set_interrupts),
+    /// and cleared in each function once handled, as done by its driver.
+    pub async fn sdio_wait_interrupt(&mut self) -> Result<u8, Error> {
+        let regs = T::regs();
+
+        loop {
+            // CMD52 clears SDIOIT: an interrupt signalled after this read sets it again.
+            let pending = self.sdio_read_byte(0, CCCR_INT_PENDING)? & 0xfe;
+            if pending != 0 {
+                return Ok(pending);
+            }
+
+            poll_fn(|cx| {
+                T::state().register(cx.waker());
+                if regs.star().read().sdioit() {
+                    return Poll::Ready(());
+                }
+                // Masked again by the interrupt handler.
+                regs.maskr().modify(|w| w.set_sdioitie(true));
+                Poll::Pending
+            })
+            .await;
+            regs.maskr().modify(|w| w.set_sdioitie(false));
+        }
+    }
+
+    /// Get a reference to the initialized SDIO card
+    ///
+    /// # Errors
+    ///
+    /// Returns Error::NoCard if [`init_sdio`](#method.init_sdio)
+    /// has not previously succeeded
+    #[inline]
+    pub fn sdio_card(&self) -> Result<&SdioCard, Error> {
+        self.sdio_card.as_ref().ok_or(Error::NoCard)
+    }
+
     /// Get a reference to the initialized card
     ///
     /// # Errors
@@ -1396,6 +1802,11 @@ impl Cmd {
         Cmd::new(6, arg, Response::Short)
     }
 
+    /// CMD5: IO Send Operation Condition
+    const fn io_send_op_cond(ocr: u32) -> Cmd {
+        Cmd::new(5, ocr, Response::Short)
+    }
+
     /// CMD7: Select one card and put it into the _Tranfer State_
     const fn sel_desel_card(rca: u32) -> Cmd {
         Cmd::new(7, rca, Response::Short)
@@ -1442,6 +1853,16 @@ impl Cmd {
         Cmd::new(24, addr, Response::Short)
     }
 
+    /// CMD52: IO Read/Write Direct
+    const fn io_rw_direct(arg: u32) -> Cmd {
+        Cmd::new(52, arg, Response::Short)
+    }
+
+    /// CMD53: IO Read/Write Extended
+    const fn io_rw_extended(arg: u32) -> Cmd {
+        Cmd::new(53, arg, Response::Short)
+    }
+
     const fn app_op_cmd(arg: u32) -> Cmd {
         Cmd::new(41, arg, Response::Short)
     }
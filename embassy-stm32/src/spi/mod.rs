@@ -17,6 +17,13 @@ use crate::rcc::{RccInfo, SealedRccPeripheral};
 use crate::time::Hertz;
 use crate::Peripheral;
 
+#[cfg(feature = "exti")]
+mod slave;
+#[cfg(all(feature = "exti", not(gpdma)))]
+pub use slave::RingBufferedSpiSlave;
+#[cfg(feature = "exti")]
+pub use slave::{SlaveConfig, SpiSlave};
+
 /// SPI error.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
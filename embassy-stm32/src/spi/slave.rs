@@ -0,0 +1,365 @@
+#[cfg(not(gpdma))]
+use core::mem;
+
+use embassy_hal_internal::{into_ref, PeripheralRef};
+
+use super::{
+    set_rxdmaen, set_txdmaen, word_impl, BitOrder, CsPin, Error, Info, Instance, MisoPin, MosiPin, RegsExt, RxDma,
+    SckPin, SealedWord, TxDma, Word,
+};
+use crate::dma::ChannelAndRequest;
+#[cfg(not(gpdma))]
+use crate::dma::ReadableRingBuffer;
+use crate::exti::{Channel as _, ExtiInputFuture};
+use crate::gpio::{AfType, AnyPin, OutputType, Pull, SealedPin as _, Speed};
+use crate::pac::spi::vals;
+use crate::spi::{Mode, Phase, Polarity, MODE_0};
+use crate::Peripheral;
+
+/// SPI slave configuration.
+#[non_exhaustive]
+#[derive(Copy, Clone)]
+pub struct SlaveConfig {
+    /// SPI mode.
+    pub mode: Mode,
+    /// Bit order.
+    pub bit_order: BitOrder,
+}
+
+impl Default for SlaveConfig {
+    fn default() -> Self {
+        Self {
+            mode: MODE_0,
+            bit_order: BitOrder::MsbFirst,
+        }
+    }
+}
+
+/// SPI slave driver.
+///
+/// The bus master selects this device with the hardware NSS pin. Transfers are DMA driven and
+/// complete when the master deasserts NSS, which is detected through the EXTI channel of the NSS
+/// pin.
+pub struct SpiSlave<'d> {
+    info: &'static Info,
+    config: SlaveConfig,
+    sck: Option<PeripheralRef<'d, AnyPin>>,
+    mosi: Option<PeripheralRef<'d, AnyPin>>,
+    miso: Option<PeripheralRef<'d, AnyPin>>,
+    nss: Option<PeripheralRef<'d, AnyPin>>,
+    tx_dma: Option<ChannelAndRequest<'d>>,
+    rx_dma: Option<ChannelAndRequest<'d>>,
+}
+
+impl<'d> SpiSlave<'d> {
+    /// Create a new SPI slave driver.
+    ///
+    /// `nss_exti` is the EXTI channel of the `nss` pin, used to wait for the end of a transfer.
+    pub fn new<T: Instance, N: CsPin<T>>(
+        _peri: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = impl SckPin<T>> + 'd,
+        mosi: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        miso: impl Peripheral<P = impl MisoPin<T>> + 'd,
+        nss: impl Peripheral<P = N> + 'd,
+        nss_exti: impl Peripheral<P = N::ExtiChannel> + 'd,
+        tx_dma: impl Peripheral<P = impl TxDma<T>> + 'd,
+        rx_dma: impl Peripheral<P = impl RxDma<T>> + 'd,
+        config: SlaveConfig,
+    ) -> Self {
+        into_ref!(nss_exti);
+        let nss = new_pin!(nss, AfType::input(Pull::Up));
+
+        // Needed if using AnyPin+AnyChannel.
+        assert_eq!(nss.as_ref().unwrap()._pin(), nss_exti.number());
+
+        let mut this = Self {
+            info: T::info(),
+            config,
+            sck: new_pin!(sck, AfType::input(Pull::None)),
+            mosi: new_pin!(mosi, AfType::input(Pull::None)),
+            miso: new_pin!(miso, AfType::output(OutputType::PushPull, Speed::VeryHigh)),
+            nss,
+            tx_dma: new_dma!(tx_dma),
+            rx_dma: new_dma!(rx_dma),
+        };
+
+        this.info.rcc.enable_and_reset();
+        this.init(<u8 as SealedWord>::CONFIG);
+        this
+    }
+
+    /// Reconfigure the driver.
+    pub fn set_config(&mut self, config: &SlaveConfig) {
+        self.config = *config;
+        self.init(<u8 as SealedWord>::CONFIG);
+    }
+
+    fn init(&mut self, word_size: word_impl::Config) {
+        let cpha = match self.config.mode.phase {
+            Phase::CaptureOnSecondTransition => vals::Cpha::SECONDEDGE,
+            Phase::CaptureOnFirstTransition => vals::Cpha::FIRSTEDGE,
+        };
+        let cpol = match self.config.mode.polarity {
+            Polarity::IdleHigh => vals::Cpol::IDLEHIGH,
+            Polarity::IdleLow => vals::Cpol::IDLELOW,
+        };
+        let lsbfirst = match self.config.bit_order {
+            BitOrder::LsbFirst => vals::Lsbfirst::LSBFIRST,
+            BitOrder::MsbFirst => vals::Lsbfirst::MSBFIRST,
+        };
+
+        let regs = self.info.regs;
+        regs.cr1().modify(|w| w.set_spe(false));
+
+        #[cfg(any(spi_v1, spi_f1))]
+        {
+            regs.cr2().modify(|w| {
+                w.set_ssoe(false);
+            });
+            regs.cr1().modify(|w| {
+                w.set_cpha(cpha);
+                w.set_cpol(cpol);
+
+                w.set_mstr(vals::Mstr::SLAVE);
+                w.set_lsbfirst(lsbfirst);
+                // Hardware NSS
+                w.set_ssm(false);
+                w.set_crcen(false);
+                w.set_bidimode(vals::Bidimode::UNIDIRECTIONAL);
+                w.set_rxonly(vals::Rxonly::FULLDUPLEX);
+                w.set_dff(word_size)
+            });
+        }
+        #[cfg(spi_v2)]
+        {
+            regs.cr2().modify(|w| {
+                let (ds, frxth) = word_size;
+                w.set_frxth(frxth);
+                w.set_ds(ds);
+                w.set_ssoe(false);
+            });
+            regs.cr1().modify(|w| {
+                w.set_cpha(cpha);
+                w.set_cpol(cpol);
+
+                w.set_mstr(vals::Mstr::SLAVE);
+                w.set_lsbfirst(lsbfirst);
+                // Hardware NSS
+                w.set_ssm(false);
+                w.set_crcen(false);
+                w.set_bidimode(vals::Bidimode::UNIDIRECTIONAL);
+            });
+        }
+        #[cfg(any(spi_v3, spi_v4, spi_v5))]
+        {
+            regs.ifcr().write(|w| w.0 = 0xffff_ffff);
+            regs.cfg2().modify(|w| {
+                w.set_ssoe(false);
+                w.set_cpha(cpha);
+                w.set_cpol(cpol);
+                w.set_lsbfirst(lsbfirst);
+                // Hardware NSS
+                w.set_ssm(false);
+                w.set_master(vals::Master::SLAVE);
+                w.set_comm(vals::Comm::FULLDUPLEX);
+                w.set_afcntr(true);
+                w.set_ssiop(vals::Ssiop::ACTIVELOW);
+            });
+            regs.cfg1().modify(|w| {
+                w.set_crcen(false);
+                w.set_dsize(word_size);
+                w.set_fthlv(vals::Fthlv::ONEFRAME);
+            });
+            regs.cr2().modify(|w| {
+                w.set_tsize(0);
+            });
+        }
+    }
+
+    async fn transfer_inner<W: Word>(&mut self, read: *mut [W], write: *const [W]) -> Result<usize, Error> {
+        assert_eq!(read.len(), write.len());
+        if read.len() == 0 {
+            return Ok(0);
+        }
+
+        let regs = self.info.regs;
+
+        // Start from an empty TX FIFO, SPIv1/v2 can only flush it through a peripheral reset.
+        self.info.rcc.disable();
+        self.info.rcc.enable_and_reset();
+        self.init(W::CONFIG);
+
+        set_rxdmaen(regs, true);
+
+        let rx_src = regs.rx_ptr();
+        let rx = unsafe { self.rx_dma.as_mut().unwrap().read_raw(rx_src, read, Default::default()) };
+
+        let tx_dst = regs.tx_ptr();
+        let tx = unsafe {
+            self.tx_dma
+                .as_mut()
+                .unwrap()
+                .write_raw(write, tx_dst, Default::default())
+        };
+
+        // Arm the NSS release edge before the master can clock anything, so that even a
+        // transfer shorter than one executor poll is seen.
+        let nss = self.nss.as_ref().unwrap();
+        let released = ExtiInputFuture::new(nss._pin(), nss._port(), true, false);
+
+        set_txdmaen(regs, true);
+        regs.cr1().modify(|w| w.set_spe(true));
+
+        // Wait for the master to select and then release us.
+        released.await;
+
+        let received = read.len() - rx.get_remaining_transfers() as usize;
+        drop(rx);
+        drop(tx);
+
+        let modf = regs.sr().read().modf();
+
+        regs.cr1().modify(|w| w.set_spe(false));
+        set_txdmaen(regs, false);
+        set_rxdmaen(regs, false);
+
+        if modf {
+            return Err(Error::ModeFault);
+        }
+
+        Ok(received)
+    }
+
+    /// Bidirectional transfer, using DMA.
+    ///
+    /// `write` is preloaded for the master to clock out while `read` is filled with the words
+    /// received. The transfer completes when the master deasserts NSS after having selected this
+    /// device, and returns the number of words received. Words clocked beyond `read.len()` are
+    /// discarded, and the master receives undefined data after `write` is exhausted.
+    pub async fn transfer<W: Word>(&mut self, read: &mut [W], write: &[W]) -> Result<usize, Error> {
+        self.transfer_inner(read, write).await
+    }
+
+    /// In-place bidirectional transfer, using DMA.
+    ///
+    /// This sends the contents of `data` and replaces them with the received words, see
+    /// [`transfer`](Self::transfer).
+    pub async fn transfer_in_place<W: Word>(&mut self, data: &mut [W]) -> Result<usize, Error> {
+        self.transfer_inner(data, data).await
+    }
+
+    /// Turn the driver into a receive-only ring-buffered driver.
+    ///
+    /// Words received are continuously written into `dma_buf` regardless of NSS framing, so
+    /// no data is lost between reads. The `dma_buf` must be large enough to prevent overflows.
+    /// MISO is not driven with meaningful data in this mode.
+    #[cfg(not(gpdma))]
+    pub fn into_ring_buffered(mut self, dma_buf: &'d mut [u8]) -> RingBufferedSpiSlave<'d> {
+        assert!(!dma_buf.is_empty() && dma_buf.len() <= 0xFFFF);
+
+        let info = self.info;
+        let regs = info.regs;
+
+        self.init(<u8 as SealedWord>::CONFIG);
+
+        // Safety: we forget the struct before this function returns.
+        let rx_dma = self.rx_dma.as_mut().unwrap();
+        let request = rx_dma.request;
+        let rx_dma = unsafe { rx_dma.channel.clone_unchecked() };
+
+        let ring_buf = unsafe { ReadableRingBuffer::new(rx_dma, request, regs.rx_ptr(), dma_buf, Default::default()) };
+        let sck = unsafe { self.sck.as_ref().map(|x| x.clone_unchecked()) };
+        let mosi = unsafe { self.mosi.as_ref().map(|x| x.clone_unchecked()) };
+        let miso = unsafe { self.miso.as_ref().map(|x| x.clone_unchecked()) };
+        let nss = unsafe { self.nss.as_ref().map(|x| x.clone_unchecked()) };
+
+        // Don't disable the clock
+        mem::forget(self);
+
+        RingBufferedSpiSlave {
+            info,
+            sck,
+            mosi,
+            miso,
+            nss,
+            ring_buf,
+        }
+    }
+}
+
+impl<'d> Drop for SpiSlave<'d> {
+    fn drop(&mut self) {
+        self.sck.as_ref().map(|x| x.set_as_disconnected());
+        self.mosi.as_ref().map(|x| x.set_as_disconnected());
+        self.miso.as_ref().map(|x| x.set_as_disconnected());
+        self.nss.as_ref().map(|x| x.set_as_disconnected());
+
+        self.info.rcc.disable();
+    }
+}
+
+/// Receive-only ring-buffered SPI slave driver.
+///
+/// Created with [`SpiSlave::into_ring_buffered`].
+#[cfg(not(gpdma))]
+pub struct RingBufferedSpiSlave<'d> {
+    info: &'static Info,
+    sck: Option<PeripheralRef<'d, AnyPin>>,
+    mosi: Option<PeripheralRef<'d, AnyPin>>,
+    miso: Option<PeripheralRef<'d, AnyPin>>,
+    nss: Option<PeripheralRef<'d, AnyPin>>,
+    ring_buf: ReadableRingBuffer<'d, u8>,
+}
+
+#[cfg(not(gpdma))]
+impl<'d> RingBufferedSpiSlave<'d> {
+    /// Start receiving in the background into the DMA ring buffer.
+    pub fn start(&mut self) {
+        let regs = self.info.regs;
+
+        self.ring_buf.start();
+        set_rxdmaen(regs, true);
+        regs.cr1().modify(|w| w.set_spe(true));
+    }
+
+    /// Stop receiving in the background.
+    pub fn stop(&mut self) {
+        let regs = self.info.regs;
+
+        self.ring_buf.request_pause();
+        regs.cr1().modify(|w| w.set_spe(false));
+        set_rxdmaen(regs, false);
+    }
+
+    /// Read exactly `buf.len()` bytes from the ring buffer.
+    ///
+    /// Background reception is started if it is not running yet. If the ring buffer overflowed,
+    /// reception is stopped and [`Error::Overrun`] is returned; the next call restarts it.
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if !self.ring_buf.is_running() {
+            self.start();
+        }
+
+        match self.ring_buf.read_exact(buf).await {
+            Ok(_) => Ok(buf.len()),
+            Err(_) => {
+                self.stop();
+                Err(Error::Overrun)
+            }
+        }
+    }
+}
+
+#[cfg(not(gpdma))]
+impl<'d> Drop for RingBufferedSpiSlave<'d> {
+    fn drop(&mut self) {
+        self.stop();
+
+        self.sck.as_ref().map(|x| x.set_as_disconnected());
+        self.mosi.as_ref().map(|x| x.set_as_disconnected());
+        self.miso.as_ref().map(|x| x.set_as_disconnected());
+        self.nss.as_ref().map(|x| x.set_as_disconnected());
+
+        self.info.rcc.disable();
+    }
+}
@@ -0,0 +1,420 @@
+//! Time driver backed by a low-power timer (LPTIM).
+//!
+//! Unlike the general-purpose timers, an LPTIM clocked from LSE or LSI keeps counting in Stop 2,
+//! so time never has to be paused and caught up with the RTC when the low-power executor stops
+//! the core.
+//!
+//! The LPTIM kernel clock must be selected in the RCC config (`mux.lptim1sel` or `mux.lptim2sel`)
+//! and must be an exact power-of-two multiple of the embassy-time tick rate, at most 128 times
+//! faster. With LSE at 32.768 kHz, this means one of the `tick-hz-256` to `tick-hz-32_768`
+//! features of `embassy-time`.
+#![allow(non_snake_case)]
+
+use core::cell::Cell;
+use core::sync::atomic::{compiler_fence, AtomicU32, AtomicU8, Ordering};
+use core::{mem, ptr};
+
+use critical_section::CriticalSection;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time_driver::{AlarmHandle, Driver, TICK_HZ};
+
+use crate::lptim::SealedInstance;
+use crate::pac::lptim::{vals, Lptim};
+use crate::rcc::{self, SealedRccPeripheral};
+use crate::{interrupt, peripherals};
+
+#[cfg(any(lptim_v2a, lptim_v2b))]
+compile_error!("the LPTIM time driver does not support LPTIM v2 yet, use a TIM time driver instead");
+
+// LPTIM2 stops counting in Stop 2, and unlike the TIM drivers there is no RTC to catch up with.
+#[cfg(all(time_driver_lptim2, feature = "low-power"))]
+compile_error!("the LPTIM2 time driver can't be used with low-power, use time-driver-lptim1 instead");
+
+// The LPTIM v1 has a single compare register, so only one alarm can be provided. The overflow is
+// tracked with the autoreload match instead of a second compare like the TIM driver does.
+const ALARM_COUNT: usize = 1;
+
+#[cfg(time_driver_lptim1)]
+type T = peripherals::LPTIM1;
+#[cfg(time_driver_lptim2)]
+type T = peripherals::LPTIM2;
+
+foreach_interrupt! {
+    (LPTIM1, lptim, $block:ident, GLOBAL, $irq:ident) => {
+        #[cfg(time_driver_lptim1)]
+        #[cfg(feature = "rt")]
+        #[interrupt]
+        fn $irq() {
+            #[cfg(feature = "low-power")]
+            unsafe { crate::low_power::on_wakeup_irq() };
+
+            DRIVER.on_interrupt()
+        }
+
+        #[cfg(time_driver_lptim1)]
+        fn enable_irq() {
+            use crate::interrupt::typelevel::Interrupt;
+
+            crate::interrupt::typelevel::$irq::unpend();
+            unsafe { crate::interrupt::typelevel::$irq::enable() };
+        }
+    };
+    (LPTIM2, lptim, $block:ident, GLOBAL, $irq:ident) => {
+        #[cfg(time_driver_lptim2)]
+        #[cfg(feature = "rt")]
+        #[interrupt]
+        fn $irq() {
+            #[cfg(feature = "low-power")]
+            unsafe { crate::low_power::on_wakeup_irq() };
+
+            DRIVER.on_interrupt()
+        }
+
+        #[cfg(time_driver_lptim2)]
+        fn enable_irq() {
+            use crate::interrupt::typelevel::Interrupt;
+
+            crate::interrupt::typelevel::$irq::unpend();
+            unsafe { crate::interrupt::typelevel::$irq::enable() };
+        }
+    };
+}
+
+fn regs() -> Lptim {
+    T::regs()
+}
+
+/// Read the counter.
+///
+/// The counter runs asynchronously to the APB clock, so it is only reliable when two consecutive
+/// reads return the same value.
+fn read_cnt() -> u16 {
+    let r = regs();
+    let mut cnt = r.cnt().read().cnt();
+    loop {
+        let next = r.cnt().read().cnt();
+        if next == cnt {
+            return cnt;
+        }
+        cnt = next;
+    }
+}
+
+// The counter is 16 bits and `period` counts its overflows. ARRM is set when the counter reaches
+// ARR (0xFFFF), one tick before it wraps to 0, so the period boundary is put at the ARR match:
+// `now = period << 16 | (counter + 1) & 0xFFFF`. This way a counter of 0xFFFF is the first tick of
+// the period the overflow interrupt starts.
+//
+// The overflow (autoreload match) interrupt may be pending while `now()` is called, either because
+// interrupts are masked or because it raced the counter read. In that case the new period has
+// started but `period` has not been incremented yet. This is detected by looking at the ARRM flag
+// after reading the counter: if it is set and the shifted counter is in the lower half of its
+// range, the counter belongs to the next period.
+//
+// `period` is a 32bit integer, so it overflows on 2^32 * 2^16 / 32768 seconds of uptime, which is
+// 272 years.
+fn calc_now(period: u32, counter: u16, overflow_pending: bool) -> u64 {
+    let counter = counter.wrapping_add(1);
+    let period = if overflow_pending && counter < 0x8000 {
+        period + 1
+    } else {
+        period
+    };
+    ((period as u64) << 16) + counter as u64
+}
+
+struct AlarmState {
+    timestamp: Cell<u64>,
+
+    // This is really a Option<(fn(*mut ()), *mut ())>
+    // but fn pointers aren't allowed in const yet
+    callback: Cell<*const ()>,
+    ctx: Cell<*mut ()>,
+}
+
+unsafe impl Send for AlarmState {}
+
+impl AlarmState {
+    const fn new() -> Self {
+        Self {
+            timestamp: Cell::new(u64::MAX),
+            callback: Cell::new(ptr::null()),
+            ctx: Cell::new(ptr::null_mut()),
+        }
+    }
+}
+
+pub(crate) struct LptimDriver {
+    /// Number of 2^16 periods elapsed since boot.
+    period: AtomicU32,
+    alarm_count: AtomicU8,
+    /// Timestamp at which to fire alarm. u64::MAX if no alarm is scheduled.
+    alarms: Mutex<CriticalSectionRawMutex, [AlarmState; ALARM_COUNT]>,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ALARM_STATE_NEW: AlarmState = AlarmState::new();
+
+embassy_time_driver::time_driver_impl!(static DRIVER: LptimDriver = LptimDriver {
+    period: AtomicU32::new(0),
+    alarm_count: AtomicU8::new(0),
+    alarms: Mutex::const_new(CriticalSectionRawMutex::new(), [ALARM_STATE_NEW; ALARM_COUNT]),
+});
+
+impl LptimDriver {
+    fn init(&'static self, cs: critical_section::CriticalSection) {
+        let r = regs();
+
+        rcc::enable_and_reset_with_cs::<T>(cs);
+
+        let timer_freq = T::frequency();
+        let div = timer_freq.0 / TICK_HZ as u32;
+        assert!(
+            div.is_power_of_two() && div <= 128 && div as u64 * TICK_HZ == timer_freq.0 as u64,
+            "LPTIM clock {} Hz is not a power-of-two multiple of the tick rate",
+            timer_freq.0
+        );
+
+        // CFGR and IER can only be written while the timer is disabled.
+        r.cr().modify(|w| w.set_enable(false));
+        r.cfgr()
+            .write(|w| w.set_presc(vals::Presc::from_bits(div.trailing_zeros() as u8)));
+        r.ier().write(|w| {
+            w.set_arrmie(true);
+            w.set_cmpmie(true);
+        });
+
+        // ARR and CMP can only be written while the timer is enabled.
+        r.cr().modify(|w| w.set_enable(true));
+        r.icr().write(|w| w.set_arrokcf(true));
+        r.arr().write(|w| w.set_arr(u16::MAX));
+        while !r.isr().read().arrok() {}
+        r.icr().write(|w| w.set_arrokcf(true));
+
+        enable_irq();
+
+        r.cr().modify(|w| w.set_cntstrt(true));
+    }
+
+    fn on_interrupt(&self) {
+        let r = regs();
+
+        critical_section::with(|cs| {
+            let isr = r.isr().read();
+
+            // Overflow
+            if isr.arrm() {
+                r.icr().write(|w| w.set_arrmcf(true));
+                self.next_period(cs);
+            }
+
+            // Compare match. The compare register is only ever programmed for the earliest alarm,
+            // but a match may be left over from a previous alarm, so check the timestamp.
+            if isr.cmpm() {
+                r.icr().write(|w| w.set_cmpmcf(true));
+                for n in 0..ALARM_COUNT {
+                    let alarm = &self.alarms.borrow(cs)[n];
+                    if alarm.timestamp.get() <= self.now() {
+                        self.trigger_alarm(n, cs);
+                    }
+                }
+            }
+        })
+    }
+
+    fn next_period(&self, cs: CriticalSection) {
+        // We only modify the period from the timer interrupt, so we know this can't race.
+        let period = self.period.load(Ordering::Relaxed) + 1;
+        self.period.store(period, Ordering::Relaxed);
+        let t = (period as u64) << 16;
+
+        for n in 0..ALARM_COUNT {
+            let alarm = &self.alarms.borrow(cs)[n];
+            let at = alarm.timestamp.get();
+
+            if at < t + 0x10000 && !self.arm(at) {
+                // The alarm was due before the compare register could be updated.
+                self.trigger_alarm(n, cs);
+            }
+        }
+    }
+
+    /// Program the compare register for `timestamp`, and return false if it has already passed.
+    fn arm(&self, timestamp: u64) -> bool {
+        let r = regs();
+
+        // Writes to CMP are synchronized to the LPTIM clock; wait until this one is effective so
+        // that the check below is not racing it.
+        // The counter is one tick behind `now()`, see `calc_now`. CMP must be strictly lower than
+        // ARR, so an alarm on the first tick of a period is picked up by the overflow interrupt
+        // instead.
+        r.icr().write(|w| w.set_cmpokcf(true));
+        r.cmp()
+            .write(|w| w.set_cmp((timestamp as u16).wrapping_sub(1).min(u16::MAX - 1)));
+        while !r.isr().read().cmpok() {}
+        r.icr().write(|w| w.set_cmpokcf(true));
+
+        timestamp > self.now()
+    }
+
+    fn get_alarm<'a>(&'a self, cs: CriticalSection<'a>, alarm: AlarmHandle) -> &'a AlarmState {
+        // safety: we're allowed to assume the AlarmState is created by us, and
+        // we never create one that's out of bounds.
+        unsafe { self.alarms.borrow(cs).get_unchecked(alarm.id() as usize) }
+    }
+
+    fn trigger_alarm(&self, n: usize, cs: CriticalSection) {
+        let alarm = &self.alarms.borrow(cs)[n];
+        alarm.timestamp.set(u64::MAX);
+
+        // Call after clearing alarm, so the callback can set another alarm.
+
+        // safety:
+        // - we can ignore the possibility of `f` being unset (null) because of the safety contract of `allocate_alarm`.
+        // - other than that we only store valid function pointers into alarm.callback
+        let f: fn(*mut ()) = unsafe { mem::transmute(alarm.callback.get()) };
+        f(alarm.ctx.get());
+    }
+
+    /*
+        Low-power functions: the LPTIM keeps counting in Stop 2, so there is nothing to pause.
+    */
+
+    #[cfg(feature = "low-power")]
+    /// The minimum pause time beyond which the executor will enter a low-power state.
+    ///
+    /// Time keeps running while stopped, so this only has to cover the stop mode wakeup latency.
+    pub(crate) const MIN_STOP_PAUSE: embassy_time::Duration = embassy_time::Duration::from_millis(1);
+
+    #[cfg(feature = "low-power")]
+    /// Check whether the next alarm is far enough away to enter a stop mode
+    pub(crate) fn pause_time(&self) -> Result<(), ()> {
+        critical_section::with(|cs| {
+            let now = self.now();
+            let time_until_next_alarm = self
+                .alarms
+                .borrow(cs)
+                .iter()
+                .map(|alarm: &AlarmState| alarm.timestamp.get().saturating_sub(now))
+                .min()
+                .unwrap_or(u64::MAX);
+
+            if embassy_time::Duration::from_ticks(time_until_next_alarm) < Self::MIN_STOP_PAUSE {
+                Err(())
+            } else {
+                Ok(())
+            }
+        })
+    }
+
+    #[cfg(feature = "low-power")]
+    /// Resume the timer after a stop mode; a no-op as time was never paused
+    pub(crate) fn resume_time(&self) {}
+}
+
+impl Driver for LptimDriver {
+    fn now(&self) -> u64 {
+        let r = regs();
+
+        // The overflow interrupt clears ARRM and increments `period`. If it runs between reading
+        // `period` and the counter, the pair is inconsistent, so read again until `period` is
+        // stable around the counter read.
+        loop {
+            let period = self.period.load(Ordering::Acquire);
+            compiler_fence(Ordering::SeqCst);
+            let counter = read_cnt();
+            let overflow_pending = r.isr().read().arrm();
+            compiler_fence(Ordering::SeqCst);
+            if self.period.load(Ordering::Acquire) == period {
+                return calc_now(period, counter, overflow_pending);
+            }
+        }
+    }
+
+    unsafe fn allocate_alarm(&self) -> Option<AlarmHandle> {
+        critical_section::with(|_| {
+            let id = self.alarm_count.load(Ordering::Relaxed);
+            if id < ALARM_COUNT as u8 {
+                self.alarm_count.store(id + 1, Ordering::Relaxed);
+                Some(AlarmHandle::new(id))
+            } else {
+                None
+            }
+        })
+    }
+
+    fn set_alarm_callback(&self, alarm: AlarmHandle, callback: fn(*mut ()), ctx: *mut ()) {
+        critical_section::with(|cs| {
+            let alarm = self.get_alarm(cs, alarm);
+
+            alarm.callback.set(callback as *const ());
+            alarm.ctx.set(ctx);
+        })
+    }
+
+    fn set_alarm(&self, alarm: AlarmHandle, timestamp: u64) -> bool {
+        critical_section::with(|cs| {
+            let alarm = self.get_alarm(cs, alarm);
+            alarm.timestamp.set(timestamp);
+
+            let t = self.now();
+            if timestamp <= t {
+                // If alarm timestamp has passed the alarm will not fire.
+                // Disarm the alarm and return `false` to indicate that.
+                alarm.timestamp.set(u64::MAX);
+
+                return false;
+            }
+
+            // Only program the compare register if the alarm is due before the counter wraps.
+            // Otherwise, `next_period` will program it.
+            let end_of_period = (t | 0xFFFF) + 1;
+            if timestamp < end_of_period && !self.arm(timestamp) {
+                // If alarm timestamp has passed since we set it, we have a race condition and
+                // the alarm may or may not have fired.
+                // Disarm the alarm and return `false` to indicate that.
+                // It is the caller's responsibility to handle this ambiguity.
+                alarm.timestamp.set(u64::MAX);
+
+                return false;
+            }
+
+            // We're confident the alarm will ring in the future.
+            true
+        })
+    }
+}
+
+#[cfg(feature = "low-power")]
+pub(crate) fn get_driver() -> &'static LptimDriver {
+    &DRIVER
+}
+
+pub(crate) fn init(cs: CriticalSection) {
+    DRIVER.init(cs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calc_now_starts_period_at_arr_match() {
+        assert_eq!(calc_now(0, 0, false), 1);
+        assert_eq!(calc_now(0, 0xFFFE, false), 0xFFFF);
+
+        // ARRM was just set, the overflow interrupt has not run yet.
+        assert_eq!(calc_now(0, 0xFFFF, true), 0x1_0000);
+        // The overflow interrupt already incremented `period`.
+        assert_eq!(calc_now(1, 0xFFFF, false), 0x1_0000);
+        assert_eq!(calc_now(1, 0xFFFF, true), 0x2_0000);
+
+        // The counter wrapped to 0 while ARRM is still pending.
+        assert_eq!(calc_now(0, 0, true), 0x1_0001);
+        assert_eq!(calc_now(0, 0x7FFE, true), 0x1_7FFF);
+
+        // A stale ARRM from the previous overflow does not affect the end of the period.
+        assert_eq!(calc_now(1, 0xFFFE, true), 0x1_FFFF);
+    }
+}
@@ -1,6 +1,8 @@
 //! PWM driver with complementary output support.
 
+use core::future::poll_fn;
 use core::marker::PhantomData;
+use core::task::Poll;
 
 use embassy_hal_internal::{into_ref, PeripheralRef};
 use stm32_metapac::timer::vals::Ckd;
@@ -8,13 +10,69 @@ use stm32_metapac::timer::vals::Ckd;
 use super::low_level::{CountingMode, OutputPolarity, Timer};
 use super::simple_pwm::{Ch1, Ch2, Ch3, Ch4, PwmPin};
 use super::{
-    AdvancedInstance4Channel, Channel, Channel1ComplementaryPin, Channel2ComplementaryPin, Channel3ComplementaryPin,
-    Channel4ComplementaryPin,
+    AdvancedInstance1Channel, AdvancedInstance4Channel, BreakInputPin, Channel, Channel1ComplementaryPin,
+    Channel2ComplementaryPin, Channel3ComplementaryPin, Channel4ComplementaryPin, CoreInstance, SealedInstance,
 };
-use crate::gpio::{AnyPin, OutputType};
+use crate::gpio::{AfType, AnyPin, OutputType, Pull};
+use crate::interrupt::typelevel::{Binding, Interrupt};
 use crate::time::Hertz;
 use crate::timer::low_level::OutputCompareMode;
-use crate::Peripheral;
+use crate::{interrupt, Peripheral};
+
+/// BIF flag of the status register. The other flags are `rc_w0` too, so they are written as 1.
+const SR_BIF: u32 = 1 << 7;
+
+/// Break input interrupt handler.
+pub struct BreakInterruptHandler<T: AdvancedInstance4Channel> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: AdvancedInstance4Channel> interrupt::typelevel::Handler<<T as AdvancedInstance1Channel>::BreakInputInterrupt>
+    for BreakInterruptHandler<T>
+{
+    unsafe fn on_interrupt() {
+        let regs = crate::pac::timer::TimAdv::from_ptr(T::regs());
+
+        if regs.sr().read().bif(0) && regs.dier().read().bie() {
+            regs.dier().modify(|w| w.set_bie(false));
+            T::state().brk_waker.wake();
+        }
+    }
+}
+
+/// Break input polarity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BreakPolarity {
+    /// The break is active when the input is low.
+    ActiveLow,
+    /// The break is active when the input is high.
+    ActiveHigh,
+}
+
+/// Break input configuration.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug)]
+pub struct BreakConfig {
+    /// Polarity of the break input.
+    pub polarity: BreakPolarity,
+    /// Digital filter of the break input, with the same encoding as the input capture filter
+    /// (0 for no filter, up to 15).
+    pub filter: u8,
+    /// Re-enable the outputs automatically at the next update event once the break input is
+    /// inactive. Otherwise they stay off until [`ComplementaryPwm::clear_fault`] is called.
+    pub automatic_output: bool,
+}
+
+impl Default for BreakConfig {
+    fn default() -> Self {
+        Self {
+            polarity: BreakPolarity::ActiveLow,
+            filter: 0,
+            automatic_output: false,
+        }
+    }
+}
 
 /// Complementary PWM pin wrapper.
 ///
@@ -52,8 +110,14 @@ complementary_channel_impl!(new_ch3, Ch3, Channel3ComplementaryPin);
 complementary_channel_impl!(new_ch4, Ch4, Channel4ComplementaryPin);
 
 /// PWM driver with support for standard and complementary outputs.
+///
+/// For motor drive, use a center-aligned [`CountingMode`], set the dead time with
+/// [`set_dead_time_ns`](Self::set_dead_time_ns) and the idle states with
+/// [`set_idle_state`](Self::set_idle_state), and connect the fault signal of the power stage to
+/// the break input with [`enable_break_input`](Self::enable_break_input).
 pub struct ComplementaryPwm<'d, T: AdvancedInstance4Channel> {
     inner: Timer<'d, T>,
+    _break_pin: Option<PeripheralRef<'d, AnyPin>>,
 }
 
 impl<'d, T: AdvancedInstance4Channel> ComplementaryPwm<'d, T> {
@@ -76,7 +140,10 @@ impl<'d, T: AdvancedInstance4Channel> ComplementaryPwm<'d, T> {
     }
 
     fn new_inner(tim: impl Peripheral<P = T> + 'd, freq: Hertz, counting_mode: CountingMode) -> Self {
-        let mut this = Self { inner: Timer::new(tim) };
+        let mut this = Self {
+            inner: Timer::new(tim),
+            _break_pin: None,
+        };
 
         this.inner.set_counting_mode(counting_mode);
         this.set_frequency(freq);
@@ -147,6 +214,97 @@ impl<'d, T: AdvancedInstance4Channel> ComplementaryPwm<'d, T> {
         self.inner.set_dead_time_clock_division(ckd);
         self.inner.set_dead_time_value(value);
     }
+
+    /// Set the dead time inserted between the complementary outputs, in nanoseconds.
+    ///
+    /// The dead time is rounded to the nearest value the timer can generate.
+    pub fn set_dead_time_ns(&mut self, ns: u32) {
+        let clock = self.inner.get_clock_frequency().0 as u64;
+        let ticks = (ns as u64 * clock + 500_000_000) / 1_000_000_000;
+        self.set_dead_time(ticks.min(u16::MAX as u64) as u16);
+    }
+
+    /// Set the level of the outputs of `channel` when the outputs are disabled by a break or by
+    /// clearing MOE, after the dead time.
+    pub fn set_idle_state(&mut self, channel: Channel, output_high: bool, complementary_high: bool) {
+        self.inner.regs_advanced().cr2().modify(|w| {
+            w.set_ois(channel.index(), output_high);
+            w.set_oisn(channel.index(), complementary_high);
+        });
+    }
+
+    /// Enable the break input.
+    ///
+    /// When the break input becomes active, the hardware immediately disables the outputs (clears
+    /// MOE) and puts them in their idle states, without software intervention. Use
+    /// [`wait_for_fault`](Self::wait_for_fault) to be notified.
+    pub fn enable_break_input(
+        &mut self,
+        pin: impl Peripheral<P = impl BreakInputPin<T>> + 'd,
+        _irq: impl Binding<<T as AdvancedInstance1Channel>::BreakInputInterrupt, BreakInterruptHandler<T>> + 'd,
+        config: BreakConfig,
+    ) {
+        into_ref!(pin);
+        let pull = match config.polarity {
+            BreakPolarity::ActiveLow => Pull::Up,
+            BreakPolarity::ActiveHigh => Pull::Down,
+        };
+        critical_section::with(|_| pin.set_as_af(pin.af_num(), AfType::input(pull)));
+        self._break_pin = Some(pin.map_into());
+
+        let r = self.inner.regs_advanced();
+        r.bdtr().modify(|w| {
+            w.set_bkp(0, config.polarity == BreakPolarity::ActiveHigh);
+            w.set_bkf(0, config.filter);
+            w.set_aoe(config.automatic_output);
+            w.set_bke(0, true);
+        });
+        r.sr().write_value(crate::pac::timer::regs::SrAdv(!SR_BIF));
+
+        <T as AdvancedInstance1Channel>::BreakInputInterrupt::unpend();
+        unsafe { <T as AdvancedInstance1Channel>::BreakInputInterrupt::enable() };
+    }
+
+    /// Disable the break input.
+    pub fn disable_break_input(&mut self) {
+        let r = self.inner.regs_advanced();
+        r.bdtr().modify(|w| w.set_bke(0, false));
+        r.dier().modify(|w| w.set_bie(false));
+        self._break_pin = None;
+    }
+
+    /// Returns `true` if the outputs have been disabled by a break.
+    pub fn is_faulted(&self) -> bool {
+        !self.inner.regs_advanced().bdtr().read().moe()
+    }
+
+    /// Wait for a break (fault) event.
+    ///
+    /// Returns immediately if a break occurred since the last [`clear_fault`](Self::clear_fault).
+    pub async fn wait_for_fault(&mut self) {
+        let r = self.inner.regs_advanced();
+
+        poll_fn(|cx| {
+            T::state().brk_waker.register(cx.waker());
+            if r.sr().read().bif(0) {
+                Poll::Ready(())
+            } else {
+                r.dier().modify(|w| w.set_bie(true));
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Acknowledge a break and re-enable the outputs.
+    ///
+    /// If the break input is still active, the outputs stay disabled and the break is reported
+    /// again.
+    pub fn clear_fault(&mut self) {
+        let r = self.inner.regs_advanced();
+        r.sr().write_value(crate::pac::timer::regs::SrAdv(!SR_BIF));
+        self.inner.enable_outputs();
+    }
 }
 
 impl<'d, T: AdvancedInstance4Channel> embedded_hal_02::Pwm for ComplementaryPwm<'d, T> {
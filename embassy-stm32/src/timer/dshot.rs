@@ -0,0 +1,116 @@
+//! DSHOT ESC protocol on top of DMA-driven PWM.
+//!
+//! A DSHOT frame is 16 bits sent as PWM periods: an 11-bit throttle value, a telemetry request
+//! bit and a 4-bit checksum, most significant bit first. A 1 is a period with a 75% duty cycle,
+//! a 0 one with a 37.5% duty cycle.
+
+use embassy_hal_internal::{into_ref, PeripheralRef};
+
+use super::simple_pwm::SimplePwm;
+use super::{Channel, GeneralInstance4Channel, UpDma};
+use crate::time::{khz, Hertz};
+use crate::Peripheral;
+
+/// Number of bits of a frame.
+pub const FRAME_BITS: usize = 16;
+
+/// DSHOT bit rate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DshotSpeed {
+    /// DSHOT150, 150 kbit/s
+    Dshot150,
+    /// DSHOT300, 300 kbit/s
+    Dshot300,
+    /// DSHOT600, 600 kbit/s
+    Dshot600,
+}
+
+impl DshotSpeed {
+    fn bit_rate(self) -> Hertz {
+        match self {
+            DshotSpeed::Dshot150 => khz(150),
+            DshotSpeed::Dshot300 => khz(300),
+            DshotSpeed::Dshot600 => khz(600),
+        }
+    }
+}
+
+/// Build a frame from an 11-bit `value` and the telemetry request bit.
+///
+/// Values 1 to 47 are commands, 48 to 2047 throttle levels and 0 means disarmed.
+pub fn frame(value: u16, telemetry: bool) -> u16 {
+    let v = (value & 0x7FF) << 1 | telemetry as u16;
+    let crc = (v ^ (v >> 4) ^ (v >> 8)) & 0xF;
+    v << 4 | crc
+}
+
+/// Encode `frame` into PWM duty values for a timer whose max duty cycle is `max_duty`.
+///
+/// The last value is a zero duty slot, so the line stays low between frames.
+pub fn encode(frame: u16, max_duty: u16) -> [u16; FRAME_BITS + 1] {
+    let one = (max_duty as u32 * 3 / 4) as u16;
+    let zero = (max_duty as u32 * 3 / 8) as u16;
+
+    let mut buf = [0; FRAME_BITS + 1];
+    for (i, slot) in buf[..FRAME_BITS].iter_mut().enumerate() {
+        *slot = if frame & (1 << (15 - i)) != 0 { one } else { zero };
+    }
+    buf
+}
+
+/// DSHOT ESC driver.
+pub struct Dshot<'d, T: GeneralInstance4Channel, D: UpDma<T>> {
+    pwm: SimplePwm<'d, T>,
+    dma: PeripheralRef<'d, D>,
+    channel: Channel,
+}
+
+impl<'d, T: GeneralInstance4Channel, D: UpDma<T>> Dshot<'d, T, D> {
+    /// Create a new DSHOT driver for the ESC connected to `channel` of `pwm`.
+    pub fn new(
+        mut pwm: SimplePwm<'d, T>,
+        dma: impl Peripheral<P = D> + 'd,
+        channel: Channel,
+        speed: DshotSpeed,
+    ) -> Self {
+        into_ref!(dma);
+
+        pwm.set_frequency(speed.bit_rate());
+        pwm.channel(channel).set_duty_cycle(0);
+
+        Self { pwm, dma, channel }
+    }
+
+    /// Send a throttle value or command, see [`frame`].
+    pub async fn write(&mut self, value: u16, telemetry: bool) {
+        let buf = encode(frame(value, telemetry), self.pwm.max_duty_cycle());
+        self.pwm.waveform_up(self.dma.reborrow(), self.channel, &buf).await;
+    }
+
+    /// Release the PWM driver and the DMA channel.
+    pub fn into_inner(self) -> (SimplePwm<'d, T>, PeripheralRef<'d, D>) {
+        (self.pwm, self.dma)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_build_frame() {
+        // Throttle 1046, no telemetry: 10000010110 0 0110
+        assert_eq!(frame(1046, false), 0b1000_0010_1100_0110);
+        assert_eq!(frame(0, false), 0);
+        assert_eq!(frame(0, true) & 0xF, 1);
+    }
+
+    #[test]
+    fn can_encode_frame() {
+        let buf = encode(0x8000, 800);
+        assert_eq!(buf[0], 600);
+        assert_eq!(buf[1], 300);
+        assert_eq!(buf[FRAME_BITS], 0);
+    }
+}
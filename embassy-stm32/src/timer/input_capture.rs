@@ -90,6 +90,10 @@ impl<'d, T: GeneralInstance4Channel> InputCapture<'d, T> {
         this
     }
 
+    pub(super) fn into_inner(self) -> Timer<'d, T> {
+        self.inner
+    }
+
     /// Enable the given channel.
     pub fn enable(&mut self, channel: Channel) {
         self.inner.enable_channel(channel, true);
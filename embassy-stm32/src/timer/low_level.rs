@@ -10,7 +10,7 @@ use core::mem::ManuallyDrop;
 
 use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
 // Re-export useful enums
-pub use stm32_metapac::timer::vals::{FilterValue, Sms as SlaveMode, Ts as TriggerSource};
+pub use stm32_metapac::timer::vals::{FilterValue, Mms as MasterMode, Sms as SlaveMode, Ts as TriggerSource};
 
 use super::*;
 use crate::pac::timer::vals;
@@ -386,6 +386,13 @@ impl<'d, T: BasicInstance> Timer<'d, T> {
     pub fn regs_basic(&self) -> crate::pac::timer::TimBasic {
         unsafe { crate::pac::timer::TimBasic::from_ptr(T::regs()) }
     }
+
+    /// Set the master mode, i.e. the event sent on TRGO to other peripherals.
+    ///
+    /// Use [`MasterMode::UPDATE`] to trigger e.g. ADC or DAC conversions on every timer update.
+    pub fn set_master_mode(&self, mms: MasterMode) {
+        self.regs_basic().cr2().modify(|r| r.set_mms(mms));
+    }
 }
 
 impl<'d, T: GeneralInstance1Channel> Timer<'d, T> {
@@ -1,17 +1,22 @@
 //! Timers, PWM, quadrature decoder.
 
 use core::marker::PhantomData;
+use core::sync::atomic::AtomicI32;
 
 use embassy_hal_internal::Peripheral;
 use embassy_sync::waitqueue::AtomicWaker;
 
 #[cfg(not(stm32l0))]
 pub mod complementary_pwm;
+pub mod dshot;
 pub mod input_capture;
 pub mod low_level;
 pub mod pwm_input;
 pub mod qei;
+#[cfg(not(gpdma))]
+pub mod ringbuffered_input_capture;
 pub mod simple_pwm;
+pub mod ws2812;
 
 use crate::interrupt;
 use crate::rcc::RccPeripheral;
@@ -55,6 +60,9 @@ pub enum TimerBits {
 struct State {
     up_waker: AtomicWaker,
     cc_waker: [AtomicWaker; 4],
+    /// Counter wraps (overflows minus underflows) counted by the QEI interrupt handler.
+    qei_wraps: AtomicI32,
+    brk_waker: AtomicWaker,
 }
 
 impl State {
@@ -63,6 +71,8 @@ impl State {
         Self {
             up_waker: NEW_AW,
             cc_waker: [NEW_AW; 4],
+            qei_wraps: AtomicI32::new(0),
+            brk_waker: NEW_AW,
         }
     }
 }
@@ -1,14 +1,48 @@
 //! Quadrature decoder using a timer.
 
+use core::future::poll_fn;
 use core::marker::PhantomData;
+use core::sync::atomic::Ordering;
+use core::task::Poll;
 
 use embassy_hal_internal::{into_ref, PeripheralRef};
 use stm32_metapac::timer::vals;
 
 use super::low_level::Timer;
-use super::{Channel1Pin, Channel2Pin, GeneralInstance4Channel};
+use super::{
+    CaptureCompareInterruptHandler, Channel1Pin, Channel2Pin, CoreInstance, GeneralInstance4Channel, SealedInstance,
+};
 use crate::gpio::{AfType, AnyPin, Pull};
-use crate::Peripheral;
+use crate::interrupt::typelevel::{Binding, Interrupt};
+use crate::{interrupt, Peripheral};
+
+/// Capture/compare channel used by [`ExtendedQei::wait_for_position`]. Channels 1 and 2 are the
+/// encoder inputs, channels 3 and 4 are free.
+const COMPARE_CHANNEL: usize = 2;
+
+/// Update interrupt handler extending the counter of an [`ExtendedQei`].
+pub struct InterruptHandler<T: GeneralInstance4Channel> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: GeneralInstance4Channel> interrupt::typelevel::Handler<T::UpdateInterrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        #[cfg(feature = "low-power")]
+        crate::low_power::on_wakeup_irq();
+
+        let regs = crate::pac::timer::TimGp16::from_ptr(T::regs());
+
+        if regs.sr().read().uif() {
+            regs.sr().write_value(crate::pac::timer::regs::SrGp16(!0x0000_0001));
+
+            // The update event fires on both overflow and underflow. The counter can't have moved
+            // by half its range since, so its value tells which one it was.
+            let wrap = if regs.cnt().read().cnt() < 0x8000 { 1 } else { -1 };
+            T::state().qei_wraps.fetch_add(wrap, Ordering::Release);
+            T::state().up_waker.wake();
+        }
+    }
+}
 
 /// Counting direction
 pub enum Direction {
@@ -104,3 +138,153 @@ impl<'d, T: GeneralInstance4Channel> Qei<'d, T> {
         self.inner.regs_gp16().cnt().read().cnt()
     }
 }
+
+/// Quadrature decoder with the 16-bit counter extended in software.
+///
+/// Counter overflows and underflows are counted by [`InterruptHandler`], so the position keeps
+/// going past the range of the hardware counter. The handler must be serviced before the encoder
+/// moves by half the counter range (32768 counts) after a wrap.
+pub struct ExtendedQei<'d, T: GeneralInstance4Channel> {
+    qei: Qei<'d, T>,
+    #[cfg(feature = "time")]
+    last_velocity_sample: Option<(i64, embassy_time::Instant)>,
+}
+
+impl<'d, T: GeneralInstance4Channel> ExtendedQei<'d, T> {
+    /// Create a new quadrature decoder driver with an extended position.
+    ///
+    /// The capture/compare interrupt is used by [`wait_for_position`](Self::wait_for_position).
+    pub fn new(
+        tim: impl Peripheral<P = T> + 'd,
+        _ch1: QeiPin<'d, T, Ch1>,
+        _ch2: QeiPin<'d, T, Ch2>,
+        _irq: impl Binding<T::UpdateInterrupt, InterruptHandler<T>>
+            + Binding<T::CaptureCompareInterrupt, CaptureCompareInterruptHandler<T>>
+            + 'd,
+    ) -> Self {
+        let qei = Qei::new_inner(tim);
+        let r = qei.inner.regs_gp16();
+
+        T::state().qei_wraps.store(0, Ordering::Relaxed);
+
+        // Only counter overflows and underflows raise the update interrupt.
+        r.cr1().modify(|w| w.set_urs(vals::Urs::COUNTERONLY));
+        r.sr().write_value(crate::pac::timer::regs::SrGp16(!0x0000_0001));
+        r.dier().modify(|w| w.set_uie(true));
+
+        T::UpdateInterrupt::unpend();
+        unsafe { T::UpdateInterrupt::enable() };
+        T::CaptureCompareInterrupt::unpend();
+        unsafe { T::CaptureCompareInterrupt::enable() };
+
+        Self {
+            qei,
+            #[cfg(feature = "time")]
+            last_velocity_sample: None,
+        }
+    }
+
+    /// Get the underlying quadrature decoder.
+    pub fn qei(&self) -> &Qei<'d, T> {
+        &self.qei
+    }
+
+    /// Get the extended position, in counts.
+    pub fn position(&self) -> i64 {
+        let r = self.qei.inner.regs_gp16();
+        let state = T::state();
+
+        loop {
+            let wraps = state.qei_wraps.load(Ordering::Acquire);
+            let pending_before = r.sr().read().uif();
+            let cnt = r.cnt().read().cnt();
+            let pending_after = r.sr().read().uif();
+
+            if state.qei_wraps.load(Ordering::Acquire) != wraps || pending_before != pending_after {
+                // Raced the interrupt handler or a wrap, try again.
+                continue;
+            }
+
+            // A wrap is pending but not handled yet, e.g. because interrupts are masked.
+            let wraps = match (pending_after, cnt < 0x8000) {
+                (false, _) => wraps,
+                (true, true) => wraps + 1,
+                (true, false) => wraps - 1,
+            };
+
+            return ((wraps as i64) << 16) + cnt as i64;
+        }
+    }
+
+    /// Get the extended position, truncated to 32 bits.
+    pub fn position_i32(&self) -> i32 {
+        self.position() as i32
+    }
+
+    /// Wait until the encoder has moved by `delta` counts from its current position, and return
+    /// the position reached.
+    ///
+    /// A positive `delta` waits for the position to reach `current + delta` or more, a negative
+    /// one for `current + delta` or less.
+    pub async fn wait_for_position(&mut self, delta: i32) -> i64 {
+        let r = self.qei.inner.regs_gp16();
+        let state = T::state();
+        let target = self.position() + delta as i64;
+        let reached = |pos: i64| if delta >= 0 { pos >= target } else { pos <= target };
+
+        let pos = poll_fn(|cx| {
+            state.up_waker.register(cx.waker());
+            state.cc_waker[COMPARE_CHANNEL].register(cx.waker());
+
+            let pos = self.position();
+            if reached(pos) {
+                return Poll::Ready(pos);
+            }
+
+            // Once the target is within reach of the hardware counter, compare against it;
+            // until then, wraps wake us up.
+            if (target - pos).abs() < 0x8000 {
+                r.ccr(COMPARE_CHANNEL).write(|w| w.set_ccr(target as u16));
+
+                // Drop a match latched for an older compare value, which would fire at once.
+                // The flags are rc_w0: only clear ours, a pending update must not be lost.
+                let mut sr = crate::pac::timer::regs::SrGp16(!0);
+                sr.set_ccif(COMPARE_CHANNEL, false);
+                r.sr().write_value(sr);
+                r.dier().modify(|w| w.set_ccie(COMPARE_CHANNEL, true));
+
+                // The counter may have passed the target while the compare was set up.
+                let pos = self.position();
+                if reached(pos) {
+                    return Poll::Ready(pos);
+                }
+            }
+
+            Poll::Pending
+        })
+        .await;
+
+        r.dier().modify(|w| w.set_ccie(COMPARE_CHANNEL, false));
+        pos
+    }
+
+    /// Estimate the velocity in counts per second, over the time elapsed since the previous call.
+    ///
+    /// The first call returns 0. Call it at a regular interval, e.g. from the control loop; a
+    /// longer interval gives a smoother but more delayed estimate.
+    #[cfg(feature = "time")]
+    pub fn velocity(&mut self) -> f32 {
+        let now = embassy_time::Instant::now();
+        let pos = self.position();
+
+        let velocity = match self.last_velocity_sample {
+            Some((last_pos, last_time)) if now > last_time => {
+                (pos - last_pos) as f32 * 1_000_000.0 / (now - last_time).as_micros() as f32
+            }
+            _ => 0.0,
+        };
+
+        self.last_velocity_sample = Some((pos, now));
+        velocity
+    }
+}
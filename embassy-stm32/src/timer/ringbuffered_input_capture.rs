@@ -0,0 +1,188 @@
+//! Input capture streaming edge timestamps into a DMA ring buffer.
+
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use embassy_hal_internal::into_ref;
+
+use super::input_capture::InputCapture;
+use super::low_level::{FilterValue, InputCaptureMode, InputTISelection, Timer};
+use super::{Channel, GeneralInstance4Channel};
+use crate::dma::{Priority, ReadableRingBuffer, TransferOptions};
+use crate::pac::timer::vals::Ccds;
+use crate::Peripheral;
+
+/// Capture or DMA overrun while streaming edge timestamps.
+///
+/// Timestamps were lost because the ring buffer was not read fast enough, or because the DMA
+/// could not keep up with the captured edges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OverrunError;
+
+/// Input capture channel streaming edge timestamps into a circular DMA buffer.
+///
+/// Every captured edge stores the counter value into the ring buffer without any interrupt, so
+/// edges can be measured at rates far above what per-edge interrupts allow. Timestamps are
+/// 16 bit counter values; the time between two edges is the `wrapping_sub` of their timestamps,
+/// as long as the edges are less than one counter period apart.
+///
+/// Created with one of the `InputCapture::into_ring_buffered_chX` methods.
+pub struct RingBufferedInputCapture<'d, T: GeneralInstance4Channel> {
+    inner: Timer<'d, T>,
+    channel: Channel,
+    ring_buf: ReadableRingBuffer<'d, u16>,
+}
+
+macro_rules! impl_ring_buffered_chx {
+    ($fn_name:ident, $dma_ch:ident, $cc_ch:ident) => {
+        impl<'d, T: GeneralInstance4Channel> InputCapture<'d, T> {
+            /// Stream the timestamps of the edges selected by `mode` into `dma_buf`.
+            ///
+            /// Note:
+            /// you will need to provide corresponding TIMx_CHy DMA channel to use this method.
+            ///
+            /// `dma_buf` should be large enough to absorb the edges captured between two reads,
+            /// and its length should be even, since the DMA only signals half and full transfer
+            /// completion.
+            pub fn $fn_name(
+                self,
+                dma: impl Peripheral<P = impl super::$dma_ch<T>> + 'd,
+                dma_buf: &'d mut [u16],
+                mode: InputCaptureMode,
+            ) -> RingBufferedInputCapture<'d, T> {
+                assert!(!dma_buf.is_empty() && dma_buf.len() <= 0xFFFF);
+                into_ref!(dma);
+
+                let inner = self.into_inner();
+                let channel = Channel::$cc_ch;
+
+                inner.enable_input_interrupt(channel, false);
+                inner.set_input_ti_selection(channel, InputTISelection::Normal);
+                inner.set_input_capture_filter(channel, FilterValue::NOFILTER);
+                inner.set_input_capture_mode(channel, mode);
+                inner.set_input_capture_prescaler(channel, 0);
+
+                let opts = TransferOptions {
+                    half_transfer_ir: true,
+                    priority: Priority::VeryHigh,
+                    ..Default::default()
+                };
+
+                #[allow(clippy::let_unit_value)] // eg. stm32f334
+                let request = dma.request();
+                let src = inner.regs_gp16().ccr(channel.index()).as_ptr() as *mut u16;
+
+                let ring_buf = unsafe { ReadableRingBuffer::new(dma, request, src, dma_buf, opts) };
+
+                RingBufferedInputCapture {
+                    inner,
+                    channel,
+                    ring_buf,
+                }
+            }
+        }
+    };
+}
+
+impl_ring_buffered_chx!(into_ring_buffered_ch1, Ch1Dma, Ch1);
+impl_ring_buffered_chx!(into_ring_buffered_ch2, Ch2Dma, Ch2);
+impl_ring_buffered_chx!(into_ring_buffered_ch3, Ch3Dma, Ch3);
+impl_ring_buffered_chx!(into_ring_buffered_ch4, Ch4Dma, Ch4);
+
+impl<'d, T: GeneralInstance4Channel> RingBufferedInputCapture<'d, T> {
+    /// Starts capturing edges into the ring buffer.
+    pub fn start(&mut self) {
+        compiler_fence(Ordering::SeqCst);
+
+        self.ring_buf.start();
+
+        let regs = self.inner.regs_gp16();
+        regs.sr().modify(|w| {
+            w.set_ccif(self.channel.index(), false);
+            w.set_ccof(self.channel.index(), false);
+        });
+
+        // The DMA request must follow the capture event, not the update event.
+        self.inner.set_cc_dma_selection(Ccds::ONCOMPARE);
+        self.inner.set_cc_dma_enable_state(self.channel, true);
+        self.inner.enable_channel(self.channel, true);
+    }
+
+    /// Stops capturing edges and the DMA transfer.
+    ///
+    /// Calling [`start`](Self::start) or [`read`](Self::read) restarts the stream, discarding
+    /// timestamps still in the ring buffer.
+    pub fn stop(&mut self) {
+        self.inner.enable_channel(self.channel, false);
+        self.inner.set_cc_dma_enable_state(self.channel, false);
+
+        self.ring_buf.request_pause();
+
+        compiler_fence(Ordering::SeqCst);
+    }
+
+    fn is_running(&self) -> bool {
+        self.inner.get_cc_dma_enable_state(self.channel)
+    }
+
+    fn overcaptured(&self) -> bool {
+        self.inner.regs_gp16().sr().read().ccof(self.channel.index())
+    }
+
+    fn overrun(&mut self) -> Result<usize, OverrunError> {
+        self.stop();
+        Err(OverrunError)
+    }
+
+    /// Waits until `timestamps` can be filled entirely, and copies the oldest timestamps into it.
+    ///
+    /// The DMA only signals half and full transfer completion, so a batch of half the ring
+    /// buffer length is returned as soon as the DMA fills one half; other lengths may wait for
+    /// up to half a ring buffer of extra edges.
+    ///
+    /// Returns the number of timestamps still available in the ring buffer. If timestamps were
+    /// lost, the stream is stopped and [`OverrunError`] is returned; the next call to `read`
+    /// restarts it.
+    pub async fn read(&mut self, timestamps: &mut [u16]) -> Result<usize, OverrunError> {
+        if !self.is_running() {
+            self.start();
+        }
+
+        if self.overcaptured() {
+            return self.overrun();
+        }
+
+        match self.ring_buf.read_exact(timestamps).await {
+            Ok(_) if self.overcaptured() => self.overrun(),
+            Ok(len) => Ok(len),
+            Err(_) => self.overrun(),
+        }
+    }
+
+    /// Copies the timestamps captured so far into `timestamps`, without waiting.
+    ///
+    /// Returns the number of timestamps copied. Like [`read`](Self::read), this starts the
+    /// stream if it is not running, and stops it on overrun.
+    pub fn read_available(&mut self, timestamps: &mut [u16]) -> Result<usize, OverrunError> {
+        if !self.is_running() {
+            self.start();
+        }
+
+        match self.ring_buf.read(timestamps) {
+            Ok(_) if self.overcaptured() => self.overrun(),
+            Ok((len, _)) => Ok(len),
+            Err(_) => self.overrun(),
+        }
+    }
+
+    /// Get the channel this capture streams from.
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+}
+
+impl<'d, T: GeneralInstance4Channel> Drop for RingBufferedInputCapture<'d, T> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -356,6 +356,93 @@ impl<'d, T: GeneralInstance4Channel> SimplePwm<'d, T> {
             self.inner.enable_update_dma(false);
         }
     }
+
+    /// Generate a sequence of PWM waveform on several channels at once, using a DMA burst.
+    ///
+    /// `duty` is interleaved: each update event writes one value to every channel from
+    /// `starting_channel` to `ending_channel` included, through the DMAR register. For example with
+    /// channels 1 to 3, `duty` is `[ch1, ch2, ch3, ch1, ch2, ch3, ...]`.
+    ///
+    /// The last values are held until the end of the period they are loaded in, so ending the
+    /// sequence with a period of zeros returns the outputs to low.
+    ///
+    /// Note:
+    /// you will need to provide corresponding TIMx_UP DMA channel to use this method.
+    pub async fn waveform_up_multi_channel(
+        &mut self,
+        dma: impl Peripheral<P = impl super::UpDma<T>>,
+        starting_channel: Channel,
+        ending_channel: Channel,
+        duty: &[u16],
+    ) {
+        let cr_start = starting_channel.index();
+        let cr_end = ending_channel.index();
+        assert!(cr_start <= cr_end);
+        assert!(duty.len() % (cr_end - cr_start + 1) == 0);
+
+        into_ref!(dma);
+
+        #[allow(clippy::let_unit_value)] // eg. stm32f334
+        let req = dma.request();
+
+        let channels = [Channel::Ch1, Channel::Ch2, Channel::Ch3, Channel::Ch4];
+        let channels = &channels[cr_start..=cr_end];
+
+        let mut original_states = [(0u16, false); 4];
+        for &ch in channels {
+            original_states[ch.index()] = (self.channel(ch).current_duty_cycle(), self.channel(ch).is_enabled());
+            if !self.channel(ch).is_enabled() {
+                self.channel(ch).enable();
+            }
+        }
+
+        let original_update_dma_state = self.inner.get_update_dma_state();
+        if !original_update_dma_state {
+            self.inner.enable_update_dma(true);
+        }
+
+        // The DMA base address is counted in 32-bit words from CR1; CCR1 is at offset 0x34.
+        // Every update DMA request then writes DBL + 1 consecutive registers through DMAR.
+        let r = self.inner.regs_gp16();
+        r.dcr().modify(|w| {
+            w.set_dba(0x34 / 4 + cr_start as u8);
+            w.set_dbl((cr_end - cr_start) as u8);
+        });
+
+        unsafe {
+            #[cfg(not(any(bdma, gpdma)))]
+            use crate::dma::{Burst, FifoThreshold};
+            use crate::dma::{Transfer, TransferOptions};
+
+            let dma_transfer_option = TransferOptions {
+                #[cfg(not(any(bdma, gpdma)))]
+                fifo_threshold: Some(FifoThreshold::Full),
+                #[cfg(not(any(bdma, gpdma)))]
+                mburst: Burst::Incr4,
+                ..Default::default()
+            };
+
+            Transfer::new_write(&mut dma, req, duty, r.dmar().as_ptr() as *mut _, dma_transfer_option).await
+        };
+
+        // restore output compare state
+        for &ch in channels {
+            let (duty, enabled) = original_states[ch.index()];
+            self.channel(ch).set_duty_cycle(duty);
+            if !enabled {
+                self.channel(ch).disable();
+            }
+        }
+
+        r.dcr().modify(|w| {
+            w.set_dba(0);
+            w.set_dbl(0);
+        });
+
+        if !original_update_dma_state {
+            self.inner.enable_update_dma(false);
+        }
+    }
 }
 
 macro_rules! impl_waveform_chx {
@@ -0,0 +1,86 @@
+//! WS2812 (NeoPixel) LED driver on top of DMA-driven PWM.
+//!
+//! Each bit is one 800 kHz PWM period, with a duty cycle of about 2/3 for a 1 and 1/3 for a 0.
+//! The whole LED string is encoded into a buffer of duty values that is streamed to the timer
+//! channel with the update DMA request.
+
+use embassy_hal_internal::{into_ref, PeripheralRef};
+
+use super::simple_pwm::SimplePwm;
+use super::{Channel, GeneralInstance4Channel, UpDma};
+use crate::time::khz;
+use crate::Peripheral;
+
+/// Number of duty values needed per LED.
+pub const SLOTS_PER_LED: usize = 24;
+
+/// Length of the duty buffer needed for `leds` LEDs, including the trailing reset slot.
+pub const fn buffer_len(leds: usize) -> usize {
+    leds * SLOTS_PER_LED + 1
+}
+
+/// Encode `colors`, given as `[r, g, b]`, into PWM duty values for a timer whose max duty cycle
+/// is `max_duty`.
+///
+/// The LEDs expect the colors in GRB order, most significant bit first; the buffer ends with a
+/// zero duty slot so the line stays low afterwards. Returns the number of values written.
+///
+/// # Panics
+///
+/// Panics if `buf` is shorter than [`buffer_len`]`(colors.len())`.
+pub fn encode(colors: &[[u8; 3]], max_duty: u16, buf: &mut [u16]) -> usize {
+    let len = buffer_len(colors.len());
+    assert!(buf.len() >= len);
+
+    let one = (max_duty as u32 * 2 / 3) as u16;
+    let zero = (max_duty as u32 / 3) as u16;
+
+    for (color, slots) in colors.iter().zip(buf.chunks_exact_mut(SLOTS_PER_LED)) {
+        let [r, g, b] = *color;
+        let grb = (g as u32) << 16 | (r as u32) << 8 | b as u32;
+        for (i, slot) in slots.iter_mut().enumerate() {
+            *slot = if grb & (1 << (23 - i)) != 0 { one } else { zero };
+        }
+    }
+    buf[len - 1] = 0;
+
+    len
+}
+
+/// WS2812 LED string driver.
+pub struct Ws2812<'d, T: GeneralInstance4Channel, D: UpDma<T>> {
+    pwm: SimplePwm<'d, T>,
+    dma: PeripheralRef<'d, D>,
+    channel: Channel,
+}
+
+impl<'d, T: GeneralInstance4Channel, D: UpDma<T>> Ws2812<'d, T, D> {
+    /// Create a new WS2812 driver, driving the LEDs connected to `channel` of `pwm`.
+    ///
+    /// The PWM frequency is set to 800 kHz, the bit rate of the LEDs.
+    pub fn new(mut pwm: SimplePwm<'d, T>, dma: impl Peripheral<P = D> + 'd, channel: Channel) -> Self {
+        into_ref!(dma);
+
+        pwm.set_frequency(khz(800));
+        pwm.channel(channel).set_duty_cycle(0);
+
+        Self { pwm, dma, channel }
+    }
+
+    /// Send `colors`, given as `[r, g, b]`, to the LED string.
+    ///
+    /// `buf` is used to hold the encoded bits and must be at least
+    /// [`buffer_len`]`(colors.len())` long. After this returns, the line must stay low for at
+    /// least 50 us before the next write for the LEDs to latch the colors.
+    pub async fn write(&mut self, colors: &[[u8; 3]], buf: &mut [u16]) {
+        let len = encode(colors, self.pwm.max_duty_cycle(), buf);
+        self.pwm
+            .waveform_up(self.dma.reborrow(), self.channel, &buf[..len])
+            .await;
+    }
+
+    /// Release the PWM driver and the DMA channel.
+    pub fn into_inner(self) -> (SimplePwm<'d, T>, PeripheralRef<'d, D>) {
+        (self.pwm, self.dma)
+    }
+}
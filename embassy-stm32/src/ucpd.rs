@@ -298,6 +298,24 @@ impl<'d, T: Instance> CcPhy<'d, T> {
         (sr.typec_vstate_cc1(), sr.typec_vstate_cc2())
     }
 
+    /// Returns the CC line a sink is attached to, based on the current voltage state.
+    ///
+    /// Only meaningful while a source pull-up is configured with [`set_pull`](Self::set_pull).
+    /// A sink presents Rd on one CC line, while the other line is left open or, for powered cables,
+    /// pulled down by Ra. Returns `None` when no sink is attached or when Rd is seen on both lines
+    /// (debug accessory mode).
+    ///
+    /// The USB Type-C specification requires the state to be stable for tCCDebounce (100..200ms)
+    /// before it is considered attached; debouncing is left to the caller.
+    pub fn sink_attached(&self) -> Option<CcSel> {
+        match self.vstate() {
+            (CcVState::LOW, CcVState::LOW) => None,
+            (CcVState::LOW, _) => Some(CcSel::CC1),
+            (_, CcVState::LOW) => Some(CcSel::CC2),
+            _ => None,
+        }
+    }
+
     /// Waits for a change in voltage state on either CC line.
     pub async fn wait_for_vstate_change(&self) -> (CcVState, CcVState) {
         let _on_drop = OnDrop::new(|| self.enable_cc_interrupts(false));
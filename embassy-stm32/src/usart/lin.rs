@@ -0,0 +1,308 @@
+//! LIN (Local Interconnect Network) mode
+use core::future::poll_fn;
+use core::sync::atomic::{compiler_fence, Ordering};
+use core::task::Poll;
+
+use super::{sr, Error, Kind, Sr, Uart};
+use crate::mode::Async;
+use crate::pac::usart::vals;
+
+/// Value of the sync byte sent after the break field.
+const SYNC: u8 = 0x55;
+
+/// LIN break detection length
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LinBreakLength {
+    /// 10 bit break detection
+    Bits10,
+    /// 11 bit break detection
+    Bits11,
+}
+
+/// LIN checksum model
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LinChecksum {
+    /// LIN 1.x classic checksum, over the data bytes only.
+    ///
+    /// Also used for the diagnostic frames (identifiers 0x3C and 0x3D) in LIN 2.x.
+    Classic,
+    /// LIN 2.x enhanced checksum, over the protected identifier and the data bytes.
+    Enhanced,
+}
+
+/// LIN error
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum LinError {
+    /// Error of the underlying UART
+    Uart(Error),
+    /// The sync byte following the break was not 0x55
+    Sync,
+    /// The parity bits of the protected identifier are wrong
+    Parity,
+    /// The checksum of the received frame is wrong
+    Checksum,
+    /// The frame identifier does not fit in 6 bits
+    InvalidId,
+    /// The frame has more than 8 data bytes
+    FrameTooLong,
+}
+
+impl From<Error> for LinError {
+    fn from(e: Error) -> Self {
+        Self::Uart(e)
+    }
+}
+
+/// Compute the protected identifier (identifier with parity bits) of a 6-bit frame identifier.
+pub fn protected_id(id: u8) -> Result<u8, LinError> {
+    if id > 0x3F {
+        return Err(LinError::InvalidId);
+    }
+    let bit = |n: u8| (id >> n) & 1;
+    let p0 = bit(0) ^ bit(1) ^ bit(2) ^ bit(4);
+    let p1 = !(bit(1) ^ bit(3) ^ bit(4) ^ bit(5)) & 1;
+    Ok(id | (p0 << 6) | (p1 << 7))
+}
+
+/// Compute the checksum of a frame with the given protected identifier and data.
+pub fn checksum(model: LinChecksum, pid: u8, data: &[u8]) -> u8 {
+    let init = match model {
+        LinChecksum::Classic => 0,
+        LinChecksum::Enhanced => pid as u16,
+    };
+    let sum = data.iter().fold(init, |sum, &b| {
+        let sum = sum + b as u16;
+        // add the carry back in
+        if sum > 0xFF {
+            sum - 0xFF
+        } else {
+            sum
+        }
+    });
+    !(sum as u8)
+}
+
+/// UART in LIN mode.
+///
+/// Created with [`Uart::into_lin`]. The bus is assumed to be connected through a LIN transceiver,
+/// which echoes transmitted bytes back on RX; these echoes are discarded before every receive.
+/// The master also relies on the echo to know when the break it sent is over.
+///
+/// As a master, use [`write_frame`](Self::write_frame) to publish a frame and
+/// [`read_frame`](Self::read_frame) to request a response from a slave. As a slave, use
+/// [`wait_header`](Self::wait_header) followed by [`write_response`](Self::write_response) or
+/// [`read_response`](Self::read_response), depending on the identifier.
+pub struct Lin<'d> {
+    uart: Uart<'d, Async>,
+}
+
+impl<'d> Uart<'d, Async> {
+    /// Switch the UART to LIN mode.
+    ///
+    /// The UART must be configured with 8 data bits, no parity and 1 stop bit.
+    /// LIN is not available on LPUART instances.
+    pub fn into_lin(self, break_length: LinBreakLength) -> Lin<'d> {
+        let info = self.tx.info;
+        assert!(info.kind == Kind::Uart, "LIN mode is not supported on LPUART");
+
+        let r = info.regs;
+        let cr1 = r.cr1().read();
+        // LINEN can only be written while the UART is disabled.
+        r.cr1().modify(|w| w.set_ue(false));
+        r.cr2().modify(|w| {
+            w.set_linen(true);
+            w.set_lbdl(match break_length {
+                LinBreakLength::Bits10 => vals::Lbdl::BIT10,
+                LinBreakLength::Bits11 => vals::Lbdl::BIT11,
+            });
+        });
+        r.cr3().modify(|w| {
+            w.set_hdsel(false);
+            w.set_scen(false);
+            w.set_iren(false);
+        });
+        r.cr1().write_value(cr1);
+
+        Lin { uart: self }
+    }
+}
+
+impl<'d> Lin<'d> {
+    /// Send a frame header (break, sync byte and protected identifier) as master.
+    pub async fn write_header(&mut self, id: u8) -> Result<(), LinError> {
+        let pid = protected_id(id)?;
+        self.send_break().await;
+        self.uart.write(&[SYNC, pid]).await?;
+        Ok(())
+    }
+
+    /// Publish a complete frame as master: header followed by `data` and its checksum.
+    pub async fn write_frame(&mut self, id: u8, data: &[u8], model: LinChecksum) -> Result<(), LinError> {
+        self.write_header(id).await?;
+        self.write_response(id, data, model).await
+    }
+
+    /// Request a frame as master: send the header, then receive the slave response into `data`.
+    ///
+    /// The length of `data` is the expected number of data bytes of the frame.
+    pub async fn read_frame(&mut self, id: u8, data: &mut [u8], model: LinChecksum) -> Result<(), LinError> {
+        self.write_header(id).await?;
+        self.uart.flush().await?;
+        self.read_response(id, data, model).await
+    }
+
+    /// Wait for a frame header as slave, and return its frame identifier.
+    pub async fn wait_header(&mut self) -> Result<u8, LinError> {
+        self.wait_break().await;
+
+        // The break itself is received as a zero byte with a framing error.
+        let mut sync = [0u8];
+        loop {
+            match self.uart.read(&mut sync).await {
+                Err(Error::Framing) => continue,
+                Err(e) => return Err(e.into()),
+                Ok(()) if sync[0] == 0 => continue,
+                Ok(()) => break,
+            }
+        }
+        if sync[0] != SYNC {
+            return Err(LinError::Sync);
+        }
+
+        let mut pid = [0u8];
+        self.uart.read(&mut pid).await?;
+        let id = pid[0] & 0x3F;
+        if protected_id(id)? != pid[0] {
+            return Err(LinError::Parity);
+        }
+        Ok(id)
+    }
+
+    /// Send a response: `data` followed by its checksum.
+    ///
+    /// Used by a slave after [`wait_header`](Self::wait_header), and by
+    /// [`write_frame`](Self::write_frame) on the master side.
+    pub async fn write_response(&mut self, id: u8, data: &[u8], model: LinChecksum) -> Result<(), LinError> {
+        if data.len() > 8 {
+            return Err(LinError::FrameTooLong);
+        }
+        let pid = protected_id(id)?;
+
+        let mut buf = [0u8; 9];
+        buf[..data.len()].copy_from_slice(data);
+        buf[data.len()] = checksum(model, pid, data);
+        self.uart.write(&buf[..data.len() + 1]).await?;
+        self.uart.flush().await?;
+        Ok(())
+    }
+
+    /// Receive a response into `data` and verify its checksum.
+    ///
+    /// The length of `data` is the expected number of data bytes of the frame.
+    pub async fn read_response(&mut self, id: u8, data: &mut [u8], model: LinChecksum) -> Result<(), LinError> {
+        if data.len() > 8 {
+            return Err(LinError::FrameTooLong);
+        }
+        let pid = protected_id(id)?;
+
+        self.discard_rx();
+        let mut buf = [0u8; 9];
+        self.uart.read(&mut buf[..data.len() + 1]).await?;
+        data.copy_from_slice(&buf[..data.len()]);
+        if buf[data.len()] != checksum(model, pid, data) {
+            return Err(LinError::Checksum);
+        }
+        Ok(())
+    }
+
+    /// Leave LIN mode and return the underlying UART.
+    pub fn into_inner(self) -> Uart<'d, Async> {
+        let r = self.uart.tx.info.regs;
+        let cr1 = r.cr1().read();
+        r.cr1().modify(|w| w.set_ue(false));
+        r.cr2().modify(|w| {
+            w.set_linen(false);
+            w.set_lbdie(false);
+        });
+        r.cr1().write_value(cr1);
+        self.uart
+    }
+
+    async fn send_break(&mut self) {
+        let r = self.uart.tx.info.regs;
+
+        // The sync byte must only be queued once the break has been sent. The transceiver echoes
+        // the break back, so wait for it to be detected on RX.
+        self.arm_break_detection();
+        self.uart.send_break();
+        self.break_detected().await;
+
+        // The break is detected after 10 or 11 of its 13 low bits: only a few bit times are left.
+        #[cfg(any(usart_v1, usart_v2))]
+        while r.cr1().read().sbk() {}
+        #[cfg(any(usart_v3, usart_v4))]
+        while r.isr().read().sbkf() {}
+    }
+
+    async fn wait_break(&mut self) {
+        self.arm_break_detection();
+        self.break_detected().await;
+        self.discard_rx();
+    }
+
+    fn arm_break_detection(&mut self) {
+        let r = self.uart.rx.info.regs;
+        clear_break_flag(r);
+        r.cr2().modify(|w| w.set_lbdie(true));
+
+        compiler_fence(Ordering::SeqCst);
+    }
+
+    /// Wait for the break detection armed with `arm_break_detection`, through the interrupt.
+    async fn break_detected(&mut self) {
+        let state = self.uart.rx.state;
+        let r = self.uart.rx.info.regs;
+
+        poll_fn(|cx| {
+            state.rx_waker.register(cx.waker());
+            if break_detected(sr(r).read()) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+
+        r.cr2().modify(|w| w.set_lbdie(false));
+        clear_break_flag(r);
+    }
+
+    /// Drop received bytes and errors left over from echoes or a break.
+    fn discard_rx(&mut self) {
+        while !matches!(self.uart.rx.nb_read(), Err(nb::Error::WouldBlock)) {}
+    }
+}
+
+#[cfg(any(usart_v1, usart_v2))]
+pub(super) fn break_detected(sr: Sr) -> bool {
+    sr.lbd()
+}
+
+#[cfg(any(usart_v3, usart_v4))]
+pub(super) fn break_detected(sr: Sr) -> bool {
+    sr.lbdf()
+}
+
+#[cfg(any(usart_v1, usart_v2))]
+fn clear_break_flag(r: super::Regs) {
+    r.sr().modify(|w| w.set_lbd(false));
+}
+
+#[cfg(any(usart_v3, usart_v4))]
+fn clear_break_flag(r: super::Regs) {
+    r.icr().write(|w| w.set_lbdcf(true));
+}
@@ -75,6 +75,12 @@ unsafe fn on_interrupt(r: Regs, s: &'static State) {
             // disable Transmission complete interrupt
             w.set_tcie(false);
         });
+    } else if r.cr2().read().lbdie() && lin::break_detected(sr) {
+        // LIN break detected
+        r.cr2().modify(|w| {
+            // disable LIN break detection interrupt
+            w.set_lbdie(false);
+        });
     } else if cr1.rxneie() {
         // We cannot check the RXNE flag as it is auto-cleared by the DMA controller
 
@@ -134,6 +140,12 @@ pub enum ConfigError {
     BaudrateTooHigh,
     /// Rx or Tx not enabled
     RxOrTxNotEnabled,
+    /// Driver enable assertion or deassertion time does not fit in 5 bits
+    #[cfg(any(usart_v3, usart_v4))]
+    DriverEnableTimeTooLong,
+    /// Address wakeup requires 9-bit frames, which cannot carry a parity bit
+    #[cfg(any(usart_v3, usart_v4))]
+    AddressWakeupWithParity,
 }
 
 #[non_exhaustive]
@@ -176,6 +188,26 @@ pub struct Config {
     /// Set the pull configuration for the RX pin.
     pub rx_pull: Pull,
 
+    /// Time between the activation of the DE (driver enable) signal and the start bit, in sample
+    /// time units (1/16 or 1/8 of a bit time, depending on oversampling). Maximum is 31.
+    #[cfg(any(usart_v3, usart_v4))]
+    pub de_assertion_time: u8,
+
+    /// Time between the end of the last stop bit and the deactivation of the DE (driver enable)
+    /// signal, in sample time units (1/16 or 1/8 of a bit time, depending on oversampling).
+    /// Maximum is 31.
+    #[cfg(any(usart_v3, usart_v4))]
+    pub de_deassertion_time: u8,
+
+    /// Enable 9-bit multiprocessor communication with address mark wakeup, using this node address.
+    ///
+    /// Frames are 9 bits long, with the most significant bit marking an address frame. After
+    /// [`UartRx::enter_mute_mode`] the receiver ignores all frames until an address frame
+    /// matching this address is received. Use [`UartTx::write_address`] and
+    /// [`UartTx::write_data`] to transmit. Cannot be combined with parity.
+    #[cfg(any(usart_v3, usart_v4))]
+    pub address_wakeup: Option<u8>,
+
     // private: set by new_half_duplex, not by the user.
     half_duplex: bool,
 }
@@ -216,6 +248,12 @@ impl Default for Config {
             #[cfg(any(usart_v3, usart_v4))]
             invert_rx: false,
             rx_pull: Pull::None,
+            #[cfg(any(usart_v3, usart_v4))]
+            de_assertion_time: 0,
+            #[cfg(any(usart_v3, usart_v4))]
+            de_deassertion_time: 0,
+            #[cfg(any(usart_v3, usart_v4))]
+            address_wakeup: None,
             half_duplex: false,
         }
     }
@@ -428,6 +466,57 @@ impl<'d> UartTx<'d, Async> {
     pub async fn flush(&mut self) -> Result<(), Error> {
         flush(&self.info, &self.state).await
     }
+
+    /// Send an address frame in 9-bit multiprocessor mode.
+    ///
+    /// The frame is sent with its 9th bit set, waking up the receivers whose
+    /// [`Config::address_wakeup`] matches `address`.
+    #[cfg(any(usart_v3, usart_v4))]
+    pub async fn write_address(&mut self, address: u8) -> Result<(), Error> {
+        self.write_frames(&[0x100 | address as u16]).await
+    }
+
+    /// Send data frames in 9-bit multiprocessor mode.
+    ///
+    /// The frames are sent with their 9th bit cleared, so they are only received by nodes that
+    /// were addressed by the preceding [`write_address`](Self::write_address).
+    ///
+    /// [`write`](Self::write) must not be used in this mode: byte writes to the data register
+    /// are replicated across the bus and would set the 9th bit from the data LSB.
+    #[cfg(any(usart_v3, usart_v4))]
+    pub async fn write_data(&mut self, buffer: &[u8]) -> Result<(), Error> {
+        let mut frames = [0u16; 32];
+        for chunk in buffer.chunks(frames.len()) {
+            for (frame, &b) in frames.iter_mut().zip(chunk) {
+                *frame = b as u16;
+            }
+            self.write_frames(&frames[..chunk.len()]).await?;
+        }
+        Ok(())
+    }
+
+    #[cfg(any(usart_v3, usart_v4))]
+    async fn write_frames(&mut self, frames: &[u16]) -> Result<(), Error> {
+        let r = self.info.regs;
+
+        // Enable Transmitter and disable Receiver for Half-Duplex mode
+        let mut cr1 = r.cr1().read();
+        if r.cr3().read().hdsel() && !cr1.te() {
+            cr1.set_te(true);
+            cr1.set_re(false);
+            r.cr1().write_value(cr1);
+        }
+
+        let ch = self.tx_dma.as_mut().unwrap();
+        r.cr3().modify(|reg| {
+            reg.set_dmat(true);
+        });
+        // If we don't assign future to a variable, the data register pointer
+        // is held across an await and makes the future non-Send.
+        let transfer = unsafe { ch.write(frames, tdr(r) as *mut u16, Default::default()) };
+        transfer.await;
+        Ok(())
+    }
 }
 
 impl<'d> UartTx<'d, Blocking> {
@@ -978,6 +1067,16 @@ impl<'d, M: Mode> UartRx<'d, M> {
         Ok(sr.rxne())
     }
 
+    /// Put the receiver in mute mode.
+    ///
+    /// With [`Config::address_wakeup`] set, all frames are then ignored until an address frame
+    /// matching the configured node address is received. The matching address frame itself is
+    /// delivered to the receiver.
+    #[cfg(any(usart_v3, usart_v4))]
+    pub fn enter_mute_mode(&mut self) {
+        self.info.regs.rqr().write(|w| w.set_mmrq(true));
+    }
+
     /// Read a single u8 if there is one available, otherwise return WouldBlock
     pub(crate) fn nb_read(&mut self) -> Result<u8, nb::Error<Error>> {
         let r = self.info.regs;
@@ -1197,6 +1296,18 @@ impl<'d> Uart<'d, Async> {
         self.tx.write(buffer).await
     }
 
+    /// Send an address frame in 9-bit multiprocessor mode
+    #[cfg(any(usart_v3, usart_v4))]
+    pub async fn write_address(&mut self, address: u8) -> Result<(), Error> {
+        self.tx.write_address(address).await
+    }
+
+    /// Send data frames in 9-bit multiprocessor mode
+    #[cfg(any(usart_v3, usart_v4))]
+    pub async fn write_data(&mut self, buffer: &[u8]) -> Result<(), Error> {
+        self.tx.write_data(buffer).await
+    }
+
     /// Wait until transmission complete
     pub async fn flush(&mut self) -> Result<(), Error> {
         self.tx.flush().await
@@ -1451,6 +1562,12 @@ impl<'d, M: Mode> Uart<'d, M> {
     pub fn send_break(&self) {
         self.tx.send_break();
     }
+
+    /// Put the receiver in mute mode until a matching address frame is received
+    #[cfg(any(usart_v3, usart_v4))]
+    pub fn enter_mute_mode(&mut self) {
+        self.rx.enter_mute_mode();
+    }
 }
 
 fn reconfigure(info: &Info, kernel_clock: Hertz, config: &Config) -> Result<(), ConfigError> {
@@ -1480,6 +1597,16 @@ fn configure(
         return Err(ConfigError::RxOrTxNotEnabled);
     }
 
+    #[cfg(any(usart_v3, usart_v4))]
+    {
+        if config.de_assertion_time > 31 || config.de_deassertion_time > 31 {
+            return Err(ConfigError::DriverEnableTimeTooLong);
+        }
+        if config.address_wakeup.is_some() && config.parity != Parity::ParityNone {
+            return Err(ConfigError::AddressWakeupWithParity);
+        }
+    }
+
     #[cfg(not(usart_v4))]
     static DIVS: [(u16, ()); 1] = [(1, ())];
 
@@ -1592,6 +1719,11 @@ fn configure(
             w.set_txinv(config.invert_tx);
             w.set_rxinv(config.invert_rx);
             w.set_swap(config.swap_rx_tx);
+            if let Some(address) = config.address_wakeup {
+                // 9-bit frames compare the full 8-bit address
+                w.set_addm7(vals::Addm7::BIT7);
+                w.set_add(address);
+            }
         }
     });
 
@@ -1617,9 +1749,23 @@ fn configure(
             w.set_re(enable_rx);
         }
 
+        #[cfg(any(usart_v3, usart_v4))]
+        {
+            w.set_deat(config.de_assertion_time);
+            w.set_dedt(config.de_deassertion_time);
+            if config.address_wakeup.is_some() {
+                w.set_mme(true);
+                w.set_wake(vals::Wake::ADDRESS);
+            }
+        }
+        #[cfg(any(usart_v3, usart_v4))]
+        let address_wakeup = config.address_wakeup.is_some();
+        #[cfg(not(any(usart_v3, usart_v4)))]
+        let address_wakeup = false;
+
         // configure word size
         // if using odd or even parity it must be configured to 9bits
-        w.set_m0(if config.parity != Parity::ParityNone {
+        w.set_m0(if config.parity != Parity::ParityNone || address_wakeup {
             trace!("USART: m0: vals::M0::BIT9");
             vals::M0::BIT9
         } else {
@@ -1807,6 +1953,9 @@ pub use buffered::*;
 pub use crate::usart::buffered::InterruptHandler as BufferedInterruptHandler;
 mod buffered;
 
+mod lin;
+pub use lin::{checksum as lin_checksum, protected_id as lin_protected_id, Lin, LinBreakLength, LinChecksum, LinError};
+
 #[cfg(not(gpdma))]
 mod ringbuffered;
 #[cfg(not(gpdma))]
@@ -1,7 +1,7 @@
 use core::marker::PhantomData;
 
 use embassy_hal_internal::{into_ref, Peripheral};
-use embassy_usb_driver::{EndpointAddress, EndpointAllocError, EndpointType, Event, Unsupported};
+use embassy_usb_driver::{EndpointAddress, EndpointAllocError, EndpointType, Event, RemoteWakeupError, Unsupported};
 use embassy_usb_synopsys_otg::otg_v1::vals::Dspd;
 use embassy_usb_synopsys_otg::otg_v1::Otg;
 pub use embassy_usb_synopsys_otg::Config;
@@ -88,6 +88,7 @@ impl<'d, T: Instance> Driver<'d, T> {
             endpoint_count: T::ENDPOINT_COUNT,
             phy_type: PhyType::InternalFullSpeed,
             quirk_setup_late_cnak: quirk_setup_late_cnak(regs),
+            lpm_supported: LPM_SUPPORTED,
             calculate_trdt_fn: calculate_trdt::<T>,
         };
 
@@ -127,6 +128,7 @@ impl<'d, T: Instance> Driver<'d, T> {
             endpoint_count: T::ENDPOINT_COUNT,
             phy_type: PhyType::InternalHighSpeed,
             quirk_setup_late_cnak: quirk_setup_late_cnak(regs),
+            lpm_supported: LPM_SUPPORTED,
             calculate_trdt_fn: calculate_trdt::<T>,
         };
 
@@ -176,6 +178,7 @@ impl<'d, T: Instance> Driver<'d, T> {
             endpoint_count: T::ENDPOINT_COUNT,
             phy_type: PhyType::ExternalFullSpeed,
             quirk_setup_late_cnak: quirk_setup_late_cnak(regs),
+            lpm_supported: LPM_SUPPORTED,
             calculate_trdt_fn: calculate_trdt::<T>,
         };
 
@@ -227,6 +230,7 @@ impl<'d, T: Instance> Driver<'d, T> {
             endpoint_count: T::ENDPOINT_COUNT,
             phy_type: PhyType::ExternalHighSpeed,
             quirk_setup_late_cnak: quirk_setup_late_cnak(regs),
+            lpm_supported: LPM_SUPPORTED,
             calculate_trdt_fn: calculate_trdt::<T>,
         };
 
@@ -386,9 +390,13 @@ impl<'d, T: Instance> embassy_usb_driver::Bus for Bus<'d, T> {
         self.inner.disable().await
     }
 
-    async fn remote_wakeup(&mut self) -> Result<(), Unsupported> {
+    async fn remote_wakeup(&mut self) -> Result<(), RemoteWakeupError> {
         self.inner.remote_wakeup().await
     }
+
+    fn set_l1_accepted(&mut self, accepted: bool) -> Result<(), Unsupported> {
+        self.inner.set_l1_accepted(accepted)
+    }
 }
 
 impl<'d, T: Instance> Drop for Bus<'d, T> {
@@ -580,6 +588,13 @@ fn calculate_trdt<T: Instance>(speed: Dspd) -> u8 {
     }
 }
 
+/// Whether the OTG core implements link power management. Only the cores of the oldest
+/// families lack it.
+const LPM_SUPPORTED: bool = !cfg!(any(
+    stm32f1, stm32f2, stm32f401, stm32f405, stm32f407, stm32f411, stm32f415, stm32f417, stm32f427, stm32f429,
+    stm32f437, stm32f439
+));
+
 fn quirk_setup_late_cnak(r: Otg) -> bool {
     r.cid().read().0 & 0xf000 == 0x1000
 }
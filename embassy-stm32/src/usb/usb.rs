@@ -9,7 +9,7 @@ use embassy_hal_internal::into_ref;
 use embassy_sync::waitqueue::AtomicWaker;
 use embassy_usb_driver as driver;
 use embassy_usb_driver::{
-    Direction, EndpointAddress, EndpointAllocError, EndpointError, EndpointInfo, EndpointType, Event, Unsupported,
+    Direction, EndpointAddress, EndpointAllocError, EndpointError, EndpointInfo, EndpointType, Event, RemoteWakeupError,
 };
 
 use crate::pac::usb::regs;
@@ -26,6 +26,7 @@ pub struct InterruptHandler<T: Instance> {
 impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
     unsafe fn on_interrupt() {
         let regs = T::regs();
+        let state = T::state();
         //let x = regs.istr().read().0;
         //trace!("USB IRQ: {:08x}", x);
 
@@ -33,7 +34,7 @@ impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandl
 
         if istr.susp() {
             //trace!("USB IRQ: susp");
-            IRQ_SUSPEND.store(true, Ordering::Relaxed);
+            state.irq_suspend.store(true, Ordering::Relaxed);
             regs.cntr().modify(|w| {
                 w.set_fsusp(true);
                 w.set_lpmode(true);
@@ -45,12 +46,12 @@ impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandl
             regs.istr().write_value(clear);
 
             // Wake main thread.
-            BUS_WAKER.wake();
+            state.bus_waker.wake();
         }
 
         if istr.wkup() {
             //trace!("USB IRQ: wkup");
-            IRQ_RESUME.store(true, Ordering::Relaxed);
+            state.irq_resume.store(true, Ordering::Relaxed);
             regs.cntr().modify(|w| {
                 w.set_fsusp(false);
                 w.set_lpmode(false);
@@ -62,12 +63,26 @@ impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandl
             regs.istr().write_value(clear);
 
             // Wake main thread.
-            BUS_WAKER.wake();
+            state.bus_waker.wake();
+        }
+
+        #[cfg(any(usb_v3, usb_v4))]
+        if istr.l1req() {
+            //trace!("USB IRQ: l1req");
+            state.irq_l1_sleep.store(true, Ordering::Relaxed);
+
+            // Write 0 to clear.
+            let mut clear = regs::Istr(!0);
+            clear.set_l1req(false);
+            regs.istr().write_value(clear);
+
+            // Wake main thread.
+            state.bus_waker.wake();
         }
 
         if istr.reset() {
             //trace!("USB IRQ: reset");
-            IRQ_RESET.store(true, Ordering::Relaxed);
+            state.irq_reset.store(true, Ordering::Relaxed);
 
             // Write 0 to clear.
             let mut clear = regs::Istr(!0);
@@ -75,24 +90,24 @@ impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandl
             regs.istr().write_value(clear);
 
             // Wake main thread.
-            BUS_WAKER.wake();
+            state.bus_waker.wake();
         }
 
         if istr.ctr() {
             let index = istr.ep_id() as usize;
-            CTR_TRIGGERED[index].store(true, Ordering::Relaxed);
+            state.ctr_triggered[index].store(true, Ordering::Relaxed);
 
             let mut epr = regs.epr(index).read();
             if epr.ctr_rx() {
                 if index == 0 && epr.setup() {
-                    EP0_SETUP.store(true, Ordering::Relaxed);
+                    state.ep0_setup.store(true, Ordering::Relaxed);
                 }
                 //trace!("EP {} RX, setup={}", index, epr.setup());
-                EP_OUT_WAKERS[index].wake();
+                state.ep_out_wakers[index].wake();
             }
             if epr.ctr_tx() {
                 //trace!("EP {} TX", index);
-                EP_IN_WAKERS[index].wake();
+                state.ep_in_wakers[index].wake();
             }
             epr.set_dtog_rx(false);
             epr.set_dtog_tx(false);
@@ -119,18 +134,39 @@ const USBRAM_ALIGN: usize = 2;
 #[cfg(any(usbram_32_2048, usbram_32_1024))]
 const USBRAM_ALIGN: usize = 4;
 
-const NEW_AW: AtomicWaker = AtomicWaker::new();
-static BUS_WAKER: AtomicWaker = NEW_AW;
-static EP0_SETUP: AtomicBool = AtomicBool::new(false);
+/// Interrupt state shared between the interrupt handler and the driver, one per USB instance.
+struct State {
+    bus_waker: AtomicWaker,
+    ep0_setup: AtomicBool,
+    ctr_triggered: [AtomicBool; EP_COUNT],
+    ep_in_wakers: [AtomicWaker; EP_COUNT],
+    ep_out_wakers: [AtomicWaker; EP_COUNT],
+    irq_reset: AtomicBool,
+    irq_suspend: AtomicBool,
+    irq_resume: AtomicBool,
+    irq_l1_sleep: AtomicBool,
+    l1_sleep: AtomicBool,
+}
 
-const NEW_CTR_TRIGGERED: AtomicBool = AtomicBool::new(false);
-static CTR_TRIGGERED: [AtomicBool; EP_COUNT] = [NEW_CTR_TRIGGERED; EP_COUNT];
+impl State {
+    const fn new() -> Self {
+        const NEW_AW: AtomicWaker = AtomicWaker::new();
+        const NEW_FLAG: AtomicBool = AtomicBool::new(false);
 
-static EP_IN_WAKERS: [AtomicWaker; EP_COUNT] = [NEW_AW; EP_COUNT];
-static EP_OUT_WAKERS: [AtomicWaker; EP_COUNT] = [NEW_AW; EP_COUNT];
-static IRQ_RESET: AtomicBool = AtomicBool::new(false);
-static IRQ_SUSPEND: AtomicBool = AtomicBool::new(false);
-static IRQ_RESUME: AtomicBool = AtomicBool::new(false);
+        Self {
+            bus_waker: NEW_AW,
+            ep0_setup: NEW_FLAG,
+            ctr_triggered: [NEW_FLAG; EP_COUNT],
+            ep_in_wakers: [NEW_AW; EP_COUNT],
+            ep_out_wakers: [NEW_AW; EP_COUNT],
+            irq_reset: NEW_FLAG,
+            irq_suspend: NEW_FLAG,
+            irq_resume: NEW_FLAG,
+            irq_l1_sleep: NEW_FLAG,
+            l1_sleep: NEW_FLAG,
+        }
+    }
+}
 
 fn convert_type(t: EndpointType) -> EpType {
     match t {
@@ -248,32 +284,55 @@ struct EndpointBuffer<T: Instance> {
     _phantom: PhantomData<T>,
 }
 
+// USBRAM must be accessed one full word at a time (on some chips it isn't even contiguous), so it can't be
+// memcpy'd. Whole words are copied straight through, only the trailing partial word goes through a bounce buffer.
 impl<T: Instance> EndpointBuffer<T> {
     fn read(&mut self, buf: &mut [u8]) {
         assert!(buf.len() <= self.len as usize);
-        for i in 0..(buf.len() + USBRAM_ALIGN - 1) / USBRAM_ALIGN {
-            let val = USBRAM.mem(self.addr as usize / USBRAM_ALIGN + i).read();
-            let n = USBRAM_ALIGN.min(buf.len() - i * USBRAM_ALIGN);
-            buf[i * USBRAM_ALIGN..][..n].copy_from_slice(&val.to_le_bytes()[..n]);
+        let mut i = self.addr as usize / USBRAM_ALIGN;
+
+        let mut chunks = buf.chunks_exact_mut(USBRAM_ALIGN);
+        for chunk in &mut chunks {
+            chunk.copy_from_slice(&USBRAM.mem(i).read().to_le_bytes());
+            i += 1;
+        }
+
+        let rem = chunks.into_remainder();
+        if !rem.is_empty() {
+            let val = USBRAM.mem(i).read().to_le_bytes();
+            rem.copy_from_slice(&val[..rem.len()]);
         }
     }
 
     fn write(&mut self, buf: &[u8]) {
         assert!(buf.len() <= self.len as usize);
-        for i in 0..(buf.len() + USBRAM_ALIGN - 1) / USBRAM_ALIGN {
+        let mut i = self.addr as usize / USBRAM_ALIGN;
+
+        let mut chunks = buf.chunks_exact(USBRAM_ALIGN);
+        for chunk in &mut chunks {
+            USBRAM.mem(i).write_value(usbram_word(chunk.try_into().unwrap()));
+            i += 1;
+        }
+
+        let rem = chunks.remainder();
+        if !rem.is_empty() {
             let mut val = [0u8; USBRAM_ALIGN];
-            let n = USBRAM_ALIGN.min(buf.len() - i * USBRAM_ALIGN);
-            val[..n].copy_from_slice(&buf[i * USBRAM_ALIGN..][..n]);
-
-            #[cfg(not(any(usbram_32_2048, usbram_32_1024)))]
-            let val = u16::from_le_bytes(val);
-            #[cfg(any(usbram_32_2048, usbram_32_1024))]
-            let val = u32::from_le_bytes(val);
-            USBRAM.mem(self.addr as usize / USBRAM_ALIGN + i).write_value(val);
+            val[..rem.len()].copy_from_slice(rem);
+            USBRAM.mem(i).write_value(usbram_word(val));
         }
     }
 }
 
+#[cfg(not(any(usbram_32_2048, usbram_32_1024)))]
+fn usbram_word(val: [u8; USBRAM_ALIGN]) -> u16 {
+    u16::from_le_bytes(val)
+}
+
+#[cfg(any(usbram_32_2048, usbram_32_1024))]
+fn usbram_word(val: [u8; USBRAM_ALIGN]) -> u32 {
+    u32::from_le_bytes(val)
+}
+
 #[derive(Debug, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 struct EndpointData {
@@ -326,7 +385,7 @@ impl<'d, T: Instance> Driver<'d, T> {
         let _ = (dp, dm); // suppress "unused" warnings.
 
         // Initialize the bus so that it signals that power is available
-        BUS_WAKER.wake();
+        T::state().bus_waker.wake();
 
         Self {
             phantom: PhantomData,
@@ -490,6 +549,8 @@ impl<'d, T: Instance> driver::Driver<'d> for Driver<'d, T> {
             w.set_suspm(true);
             w.set_wkupm(true);
             w.set_ctrm(true);
+            #[cfg(any(usb_v3, usb_v4))]
+            w.set_l1reqm(true);
         });
 
         #[cfg(any(usb_v3, usb_v4))]
@@ -530,8 +591,9 @@ pub struct Bus<'d, T: Instance> {
 
 impl<'d, T: Instance> driver::Bus for Bus<'d, T> {
     async fn poll(&mut self) -> Event {
+        let state = T::state();
         poll_fn(move |cx| {
-            BUS_WAKER.register(cx.waker());
+            state.bus_waker.register(cx.waker());
 
             // TODO: implement VBUS detection.
             if !self.inited {
@@ -541,13 +603,14 @@ impl<'d, T: Instance> driver::Bus for Bus<'d, T> {
 
             let regs = T::regs();
 
-            if IRQ_RESUME.load(Ordering::Acquire) {
-                IRQ_RESUME.store(false, Ordering::Relaxed);
+            if state.irq_resume.load(Ordering::Acquire) {
+                state.irq_resume.store(false, Ordering::Relaxed);
+                state.l1_sleep.store(false, Ordering::Relaxed);
                 return Poll::Ready(Event::Resume);
             }
 
-            if IRQ_RESET.load(Ordering::Acquire) {
-                IRQ_RESET.store(false, Ordering::Relaxed);
+            if state.irq_reset.load(Ordering::Acquire) {
+                state.irq_reset.store(false, Ordering::Relaxed);
 
                 trace!("RESET");
                 regs.daddr().write(|w| {
@@ -568,21 +631,27 @@ impl<'d, T: Instance> driver::Bus for Bus<'d, T> {
                     })
                 }
 
-                for w in &EP_IN_WAKERS {
+                for w in &state.ep_in_wakers {
                     w.wake()
                 }
-                for w in &EP_OUT_WAKERS {
+                for w in &state.ep_out_wakers {
                     w.wake()
                 }
 
                 return Poll::Ready(Event::Reset);
             }
 
-            if IRQ_SUSPEND.load(Ordering::Acquire) {
-                IRQ_SUSPEND.store(false, Ordering::Relaxed);
+            if state.irq_suspend.load(Ordering::Acquire) {
+                state.irq_suspend.store(false, Ordering::Relaxed);
                 return Poll::Ready(Event::Suspend);
             }
 
+            if state.irq_l1_sleep.load(Ordering::Acquire) {
+                state.irq_l1_sleep.store(false, Ordering::Relaxed);
+                state.l1_sleep.store(true, Ordering::Relaxed);
+                return Poll::Ready(Event::L1Sleep);
+            }
+
             Poll::Pending
         })
         .await
@@ -609,7 +678,7 @@ impl<'d, T: Instance> driver::Bus for Bus<'d, T> {
                         }
                     }
                 }
-                EP_IN_WAKERS[ep_addr.index()].wake();
+                T::state().ep_in_wakers[ep_addr.index()].wake();
             }
             Direction::Out => {
                 loop {
@@ -628,7 +697,7 @@ impl<'d, T: Instance> driver::Bus for Bus<'d, T> {
                         }
                     }
                 }
-                EP_OUT_WAKERS[ep_addr.index()].wake();
+                T::state().ep_out_wakers[ep_addr.index()].wake();
             }
         }
     }
@@ -662,7 +731,7 @@ impl<'d, T: Instance> driver::Bus for Bus<'d, T> {
                     w.set_stat_tx(Stat::from_bits(r.stat_tx().to_bits() ^ want_stat.to_bits()));
                     reg.write_value(w);
                 }
-                EP_IN_WAKERS[ep_addr.index()].wake();
+                T::state().ep_in_wakers[ep_addr.index()].wake();
             }
             Direction::Out => {
                 loop {
@@ -678,7 +747,7 @@ impl<'d, T: Instance> driver::Bus for Bus<'d, T> {
                     w.set_stat_rx(Stat::from_bits(r.stat_rx().to_bits() ^ want_stat.to_bits()));
                     reg.write_value(w);
                 }
-                EP_OUT_WAKERS[ep_addr.index()].wake();
+                T::state().ep_out_wakers[ep_addr.index()].wake();
             }
         }
         trace!("EPR after: {:04x}", reg.read().0);
@@ -687,8 +756,43 @@ impl<'d, T: Instance> driver::Bus for Bus<'d, T> {
     async fn enable(&mut self) {}
     async fn disable(&mut self) {}
 
-    async fn remote_wakeup(&mut self) -> Result<(), Unsupported> {
-        Err(Unsupported)
+    async fn remote_wakeup(&mut self) -> Result<(), RemoteWakeupError> {
+        let regs = T::regs();
+
+        #[cfg(any(usb_v3, usb_v4))]
+        if T::state().l1_sleep.load(Ordering::Relaxed) {
+            // The host allows remote wakeup separately for each L1 request.
+            if !regs.lpmcsr().read().remwake() {
+                return Err(RemoteWakeupError::Unsupported);
+            }
+            // The peripheral ends the L1 resume signalling on its own.
+            regs.cntr().modify(|w| w.set_l1resume(true));
+            return Ok(());
+        }
+
+        regs.cntr().modify(|w| {
+            w.set_fsusp(false);
+            w.set_lpmode(false);
+            w.set_resume(true);
+        });
+
+        // Resume signalling must last between 1 and 15ms.
+        #[cfg(feature = "time")]
+        embassy_time::Timer::after_millis(2).await;
+        #[cfg(not(feature = "time"))]
+        cortex_m::asm::delay(unsafe { crate::rcc::get_freqs() }.sys.to_hertz().unwrap().0 / 500);
+
+        regs.cntr().modify(|w| w.set_resume(false));
+        Ok(())
+    }
+
+    #[cfg(any(usb_v3, usb_v4))]
+    fn set_l1_accepted(&mut self, accepted: bool) -> Result<(), driver::Unsupported> {
+        T::regs().lpmcsr().write(|w| {
+            w.set_lpmen(true);
+            w.set_lpmack(accepted);
+        });
+        Ok(())
     }
 }
 
@@ -795,7 +899,7 @@ impl<'d, T: Instance> driver::Endpoint for Endpoint<'d, T, In> {
         trace!("wait_enabled IN WAITING");
         let index = self.info.addr.index();
         poll_fn(|cx| {
-            EP_IN_WAKERS[index].register(cx.waker());
+            T::state().ep_in_wakers[index].register(cx.waker());
             let regs = T::regs();
             if regs.epr(index).read().stat_tx() == Stat::DISABLED {
                 Poll::Pending
@@ -817,7 +921,7 @@ impl<'d, T: Instance> driver::Endpoint for Endpoint<'d, T, Out> {
         trace!("wait_enabled OUT WAITING");
         let index = self.info.addr.index();
         poll_fn(|cx| {
-            EP_OUT_WAKERS[index].register(cx.waker());
+            T::state().ep_out_wakers[index].register(cx.waker());
             let regs = T::regs();
             if regs.epr(index).read().stat_rx() == Stat::DISABLED {
                 Poll::Pending
@@ -835,13 +939,13 @@ impl<'d, T: Instance> driver::EndpointOut for Endpoint<'d, T, Out> {
         trace!("READ WAITING, buf.len() = {}", buf.len());
         let index = self.info.addr.index();
         let stat = poll_fn(|cx| {
-            EP_OUT_WAKERS[index].register(cx.waker());
+            T::state().ep_out_wakers[index].register(cx.waker());
             let regs = T::regs();
             let stat = regs.epr(index).read().stat_rx();
             if self.info.ep_type == EndpointType::Isochronous {
                 // The isochronous endpoint does not change its `STAT_RX` field to `NAK` when receiving a packet.
                 // Therefore, this instead waits until the `CTR` interrupt was triggered.
-                if matches!(stat, Stat::DISABLED) || CTR_TRIGGERED[index].load(Ordering::Relaxed) {
+                if matches!(stat, Stat::DISABLED) || T::state().ctr_triggered[index].load(Ordering::Relaxed) {
                     Poll::Ready(stat)
                 } else {
                     Poll::Pending
@@ -856,7 +960,7 @@ impl<'d, T: Instance> driver::EndpointOut for Endpoint<'d, T, Out> {
         })
         .await;
 
-        CTR_TRIGGERED[index].store(false, Ordering::Relaxed);
+        T::state().ctr_triggered[index].store(false, Ordering::Relaxed);
 
         if stat == Stat::DISABLED {
             return Err(EndpointError::Disabled);
@@ -905,13 +1009,13 @@ impl<'d, T: Instance> driver::EndpointIn for Endpoint<'d, T, In> {
 
         trace!("WRITE WAITING");
         let stat = poll_fn(|cx| {
-            EP_IN_WAKERS[index].register(cx.waker());
+            T::state().ep_in_wakers[index].register(cx.waker());
             let regs = T::regs();
             let stat = regs.epr(index).read().stat_tx();
             if self.info.ep_type == EndpointType::Isochronous {
                 // The isochronous endpoint does not change its `STAT_RX` field to `NAK` when receiving a packet.
                 // Therefore, this instead waits until the `CTR` interrupt was triggered.
-                if matches!(stat, Stat::DISABLED) || CTR_TRIGGERED[index].load(Ordering::Relaxed) {
+                if matches!(stat, Stat::DISABLED) || T::state().ctr_triggered[index].load(Ordering::Relaxed) {
                     Poll::Ready(stat)
                 } else {
                     Poll::Pending
@@ -926,7 +1030,7 @@ impl<'d, T: Instance> driver::EndpointIn for Endpoint<'d, T, In> {
         })
         .await;
 
-        CTR_TRIGGERED[index].store(false, Ordering::Relaxed);
+        T::state().ctr_triggered[index].store(false, Ordering::Relaxed);
 
         if stat == Stat::DISABLED {
             return Err(EndpointError::Disabled);
@@ -984,8 +1088,8 @@ impl<'d, T: Instance> driver::ControlPipe for ControlPipe<'d, T> {
         loop {
             trace!("SETUP read waiting");
             poll_fn(|cx| {
-                EP_OUT_WAKERS[0].register(cx.waker());
-                if EP0_SETUP.load(Ordering::Relaxed) {
+                T::state().ep_out_wakers[0].register(cx.waker());
+                if T::state().ep0_setup.load(Ordering::Relaxed) {
                     Poll::Ready(())
                 } else {
                     Poll::Pending
@@ -1000,7 +1104,7 @@ impl<'d, T: Instance> driver::ControlPipe for ControlPipe<'d, T> {
                 continue;
             }
 
-            EP0_SETUP.store(false, Ordering::Relaxed);
+            T::state().ep0_setup.store(false, Ordering::Relaxed);
 
             trace!("SETUP read ok");
             return buf;
@@ -1040,7 +1144,7 @@ impl<'d, T: Instance> driver::ControlPipe for ControlPipe<'d, T> {
 
         trace!("data_out WAITING, buf.len() = {}", buf.len());
         poll_fn(|cx| {
-            EP_OUT_WAKERS[0].register(cx.waker());
+            T::state().ep_out_wakers[0].register(cx.waker());
             let regs = T::regs();
             if regs.epr(0).read().stat_rx() == Stat::NAK {
                 Poll::Ready(())
@@ -1050,7 +1154,7 @@ impl<'d, T: Instance> driver::ControlPipe for ControlPipe<'d, T> {
         })
         .await;
 
-        if EP0_SETUP.load(Ordering::Relaxed) {
+        if T::state().ep0_setup.load(Ordering::Relaxed) {
             trace!("received another SETUP, aborting data_out.");
             return Err(EndpointError::Disabled);
         }
@@ -1107,8 +1211,8 @@ impl<'d, T: Instance> driver::ControlPipe for ControlPipe<'d, T> {
 
         trace!("WRITE WAITING");
         poll_fn(|cx| {
-            EP_IN_WAKERS[0].register(cx.waker());
-            EP_OUT_WAKERS[0].register(cx.waker());
+            T::state().ep_in_wakers[0].register(cx.waker());
+            T::state().ep_out_wakers[0].register(cx.waker());
             let regs = T::regs();
             if regs.epr(0).read().stat_tx() == Stat::NAK {
                 Poll::Ready(())
@@ -1118,7 +1222,7 @@ impl<'d, T: Instance> driver::ControlPipe for ControlPipe<'d, T> {
         })
         .await;
 
-        if EP0_SETUP.load(Ordering::Relaxed) {
+        if T::state().ep0_setup.load(Ordering::Relaxed) {
             trace!("received another SETUP, aborting data_in.");
             return Err(EndpointError::Disabled);
         }
@@ -1159,7 +1263,7 @@ impl<'d, T: Instance> driver::ControlPipe for ControlPipe<'d, T> {
         // Wait is needed, so that we don't set the address too soon, breaking the status stage.
         // (embassy-usb sets the address after accept() returns)
         poll_fn(|cx| {
-            EP_IN_WAKERS[0].register(cx.waker());
+            T::state().ep_in_wakers[0].register(cx.waker());
             let regs = T::regs();
             if regs.epr(0).read().stat_tx() == Stat::NAK {
                 Poll::Ready(())
@@ -1201,6 +1305,7 @@ impl<'d, T: Instance> driver::ControlPipe for ControlPipe<'d, T> {
 
 trait SealedInstance {
     fn regs() -> crate::pac::usb::Usb;
+    fn state() -> &'static State;
 }
 
 /// USB instance trait.
@@ -1220,6 +1325,11 @@ foreach_interrupt!(
             fn regs() -> crate::pac::usb::Usb {
                 crate::pac::$inst
             }
+
+            fn state() -> &'static State {
+                static STATE: State = State::new();
+                &STATE
+            }
         }
 
         impl Instance for crate::peripherals::$inst {
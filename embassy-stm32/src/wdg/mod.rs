@@ -6,6 +6,11 @@ use stm32_metapac::iwdg::vals::{Key, Pr};
 
 use crate::rcc::LSI_FREQ;
 
+#[cfg(wwdg)]
+mod wwdg;
+#[cfg(wwdg)]
+pub use wwdg::*;
+
 /// Independent watchdog (IWDG) driver.
 pub struct IndependentWatchdog<'d, T: Instance> {
     wdg: PhantomData<&'d mut T>,
@@ -78,6 +83,21 @@ impl<'d, T: Instance> IndependentWatchdog<'d, T> {
     pub fn pet(&mut self) {
         T::regs().kr().write(|w| w.set_key(Key::RESET));
     }
+
+    /// Keep the watchdog fed by petting it every `interval`, forever.
+    ///
+    /// This is meant to be the body of a dedicated task, so the refresh policy lives in one
+    /// place instead of `pet()` calls spread through the application. `interval` must be shorter
+    /// than the timeout, and the feeder task must not be starved by other tasks for longer than
+    /// the remaining margin.
+    #[cfg(feature = "time")]
+    pub async fn run_feeder(&mut self, interval: embassy_time::Duration) -> ! {
+        let mut ticker = embassy_time::Ticker::every(interval);
+        loop {
+            self.pet();
+            ticker.next().await;
+        }
+    }
 }
 
 trait SealedInstance {
@@ -0,0 +1,206 @@
+//! Window watchdog (WWDG)
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::Poll;
+
+use embassy_hal_internal::{into_ref, Peripheral};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::interrupt;
+use crate::interrupt::typelevel::Interrupt;
+use crate::pac::wwdg::vals::{Wdga, Wdgtb};
+use crate::rcc::{self, RccPeripheral, SealedRccPeripheral};
+
+static EARLY_WAKEUP: AtomicBool = AtomicBool::new(false);
+static WAKER: AtomicWaker = AtomicWaker::new();
+
+/// WWDG early wakeup interrupt handler.
+pub struct InterruptHandler<T: WindowInstance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: WindowInstance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        // The early wakeup interrupt can't be disabled once enabled, only its flag cleared.
+        T::regs().sr().write(|w| w.set_ewif(false));
+        EARLY_WAKEUP.store(true, Ordering::Release);
+        WAKER.wake();
+    }
+}
+
+// 7-bit down counter, the MCU is reset when it goes from 0x40 to 0x3F.
+const COUNTER_MIN: u8 = 0x40;
+const COUNTER_MAX: u8 = 0x7F;
+const TICKS_MAX: u32 = (COUNTER_MAX - COUNTER_MIN) as u32 + 1;
+
+#[cfg(not(wwdg_v2))]
+const MAX_WDGTB: u8 = 3;
+#[cfg(wwdg_v2)]
+const MAX_WDGTB: u8 = 7;
+
+/// Counter tick period in us, for the given PCLK frequency and WDGTB prescaler.
+const fn tick_us(pclk: u32, wdgtb: u8) -> u32 {
+    ((4096u64 << wdgtb) * 1_000_000 / pclk as u64) as u32
+}
+
+/// Window watchdog (WWDG) driver.
+///
+/// The watchdog resets the MCU when it is not refreshed before the timeout expires, or when it is
+/// refreshed too early, before the refresh window opens. Shortly before the timeout, the early
+/// wakeup interrupt signals that a reset is imminent, see [`Self::wait_early_warning`].
+pub struct WindowWatchdog<'d, T: WindowInstance> {
+    counter: u8,
+    wdg: PhantomData<&'d mut T>,
+}
+
+impl<'d, T: WindowInstance> WindowWatchdog<'d, T> {
+    /// Creates a WWDG (Window Watchdog) instance with a given timeout value in microseconds.
+    ///
+    /// Refreshing is only allowed in the last `window_us` microseconds before the timeout; a
+    /// `window_us` greater than or equal to `timeout_us` disables the window.
+    ///
+    /// [Self] has to be started with [Self::unleash()]. The WWDG is clocked from PCLK, so its
+    /// timeout is at most a few tens of milliseconds.
+    pub fn new(
+        _instance: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        timeout_us: u32,
+        window_us: u32,
+    ) -> Self {
+        into_ref!(_instance);
+
+        rcc::enable_and_reset::<T>();
+
+        let pclk = T::frequency().0;
+
+        // Find lowest prescaler value, which makes watchdog period longer or equal to timeout.
+        let wdgtb = unwrap!((0..=MAX_WDGTB).find(|wdgtb| timeout_us <= TICKS_MAX * tick_us(pclk, *wdgtb)));
+        let tick = tick_us(pclk, wdgtb);
+
+        let ticks = (timeout_us / tick).clamp(1, TICKS_MAX);
+        let counter = COUNTER_MIN + ticks as u8 - 1;
+        let window_ticks = (window_us / tick).clamp(1, TICKS_MAX);
+        let window = COUNTER_MIN + window_ticks as u8 - 1;
+
+        // The early wakeup interrupt is only enabled by `unleash`: the downcounter already runs
+        // while the watchdog is disabled, and would raise it spuriously.
+        T::regs().cfr().write(|w| {
+            w.set_wdgtb(Wdgtb::from_bits(wdgtb));
+            w.set_w(window);
+        });
+
+        trace!(
+            "Window watchdog configured with {}us timeout, desired was {}us (WDGTB={}, T={}, W={})",
+            ticks * tick,
+            timeout_us,
+            wdgtb,
+            counter,
+            window
+        );
+
+        Self {
+            counter,
+            wdg: PhantomData,
+        }
+    }
+
+    /// Unleash (start) the watchdog.
+    ///
+    /// Once started, the watchdog can only be stopped by a reset.
+    pub fn unleash(&mut self) {
+        let wdg = T::regs();
+        wdg.cr().write(|w| {
+            w.set_t(self.counter);
+            w.set_wdga(Wdga::ENABLED);
+        });
+
+        // Drop an early wakeup raised by the free-running counter before the watchdog started.
+        wdg.sr().write(|w| w.set_ewif(false));
+        EARLY_WAKEUP.store(false, Ordering::Relaxed);
+        wdg.cfr().modify(|w| w.set_ewi(true));
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+    }
+
+    /// Pet (reload, refresh) the watchdog.
+    ///
+    /// Refreshing before the window opens resets the MCU.
+    pub fn pet(&mut self) {
+        T::regs().cr().write(|w| {
+            w.set_t(self.counter);
+            w.set_wdga(Wdga::ENABLED);
+        });
+    }
+
+    /// Returns `true` if the refresh window is open, i.e. [`Self::pet`] won't reset the MCU.
+    pub fn window_open(&self) -> bool {
+        T::regs().cr().read().t() <= T::regs().cfr().read().w()
+    }
+
+    /// Wait for the early wakeup interrupt, raised one counter tick before the watchdog resets
+    /// the MCU.
+    ///
+    /// This is a last chance to save state or log the failure. Petting the watchdog in response
+    /// prevents the reset, but only if the waiting task gets to run within one tick.
+    pub async fn wait_early_warning(&mut self) {
+        poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            if EARLY_WAKEUP.swap(false, Ordering::Acquire) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Keep the watchdog fed by petting it every `interval`, forever.
+    ///
+    /// This is meant to be the body of a dedicated task, so the refresh policy lives in one
+    /// place. Unlike the independent watchdog, petting too early resets the MCU: after each
+    /// refresh the window stays closed for `timeout_us - window_us`, so `interval` must lie
+    /// between that and `timeout_us`, with margin on both sides for the feeder task's latency.
+    /// An interval of `timeout_us - window_us / 2` is a good default.
+    ///
+    /// The first pet happens one `interval` after this is called. A tick that finds the window
+    /// still closed is skipped rather than resetting the MCU, which usually means the watchdog
+    /// times out before the next one.
+    #[cfg(feature = "time")]
+    pub async fn run_feeder(&mut self, interval: embassy_time::Duration) -> ! {
+        let mut ticker = embassy_time::Ticker::every(interval);
+        loop {
+            ticker.next().await;
+            if self.window_open() {
+                self.pet();
+            } else {
+                warn!("wwdg: feeder ticked before the refresh window opened");
+            }
+        }
+    }
+}
+
+trait SealedWindowInstance {
+    fn regs() -> crate::pac::wwdg::Wwdg;
+}
+
+/// WWDG instance trait.
+#[allow(private_bounds)]
+pub trait WindowInstance: SealedWindowInstance + RccPeripheral + 'static {
+    /// Early wakeup interrupt for this instance.
+    type Interrupt: interrupt::typelevel::Interrupt;
+}
+
+foreach_interrupt!(
+    ($inst:ident, wwdg, $block:ident, $signal_name:ident, $irq:ident) => {
+        impl SealedWindowInstance for crate::peripherals::$inst {
+            fn regs() -> crate::pac::wwdg::Wwdg {
+                crate::pac::$inst
+            }
+        }
+
+        impl WindowInstance for crate::peripherals::$inst {
+            type Interrupt = crate::interrupt::typelevel::$irq;
+        }
+    };
+);
@@ -213,9 +213,27 @@ pub trait Bus {
     ///
     /// # Errors
     ///
-    /// * [`Unsupported`](crate::Unsupported) - This UsbBus implementation doesn't support
+    /// * [`RemoteWakeupError::Unsupported`] - This UsbBus implementation doesn't support
     ///   remote wakeup or it has not been enabled at creation time.
-    async fn remote_wakeup(&mut self) -> Result<(), Unsupported>;
+    /// * [`RemoteWakeupError::Timeout`] - The peripheral did not get ready to signal the
+    ///   wakeup in time.
+    async fn remote_wakeup(&mut self) -> Result<(), RemoteWakeupError>;
+
+    /// Set whether L1 (sleep) requests from the host are accepted.
+    ///
+    /// Accepted requests are acknowledged and reported with [`Event::L1Sleep`]. Rejected ones are
+    /// answered with NYET, keeping the link in L0.
+    ///
+    /// The default implementation just returns `Unsupported`.
+    ///
+    /// # Errors
+    ///
+    /// * [`Unsupported`](crate::Unsupported) - This UsbBus implementation doesn't support
+    ///   link power management.
+    fn set_l1_accepted(&mut self, accepted: bool) -> Result<(), Unsupported> {
+        let _ = accepted;
+        Err(Unsupported)
+    }
 }
 
 /// Endpoint trait, common for OUT and IN.
@@ -352,6 +370,7 @@ pub trait EndpointIn: Endpoint {
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 /// Event returned by [`Bus::poll`].
+#[non_exhaustive]
 pub enum Event {
     /// The USB reset condition has been detected.
     Reset,
@@ -364,6 +383,11 @@ pub enum Event {
     /// devices, the device has been connected to the USB bus.
     Resume,
 
+    /// The host has put the link into the L1 (sleep) state with link power management.
+    ///
+    /// The link leaves L1 with a [`Event::Resume`].
+    L1Sleep,
+
     /// The USB power has been detected.
     PowerDetected,
 
@@ -384,6 +408,23 @@ pub struct EndpointAllocError;
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Unsupported;
 
+/// Errors returned by [`Bus::remote_wakeup`].
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum RemoteWakeupError {
+    /// Remote wakeup is not supported by the driver, or not allowed by the host.
+    Unsupported,
+    /// The peripheral did not get ready to signal the wakeup in time.
+    Timeout,
+}
+
+impl From<Unsupported> for RemoteWakeupError {
+    fn from(_: Unsupported) -> Self {
+        RemoteWakeupError::Unsupported
+    }
+}
+
 /// Errors returned by [`EndpointIn::write`] and [`EndpointOut::read`]
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -15,7 +15,7 @@ use core::task::Poll;
 use embassy_sync::waitqueue::AtomicWaker;
 use embassy_usb_driver::{
     Bus as _, Direction, EndpointAddress, EndpointAllocError, EndpointError, EndpointIn, EndpointInfo, EndpointOut,
-    EndpointType, Event, Unsupported,
+    EndpointType, Event, RemoteWakeupError, Unsupported,
 };
 
 pub mod otg_v1;
@@ -30,7 +30,14 @@ pub unsafe fn on_interrupt<const MAX_EP_COUNT: usize>(
     quirk_setup_late_cnak: bool,
 ) {
     let ints = r.gintsts().read();
-    if ints.wkupint() || ints.usbsusp() || ints.usbrst() || ints.enumdne() || ints.otgint() || ints.srqint() {
+    if ints.wkupint()
+        || ints.usbsusp()
+        || ints.usbrst()
+        || ints.enumdne()
+        || ints.otgint()
+        || ints.srqint()
+        || ints.lpmint()
+    {
         // Mask interrupts and notify `Bus` to process them
         r.gintmsk().write(|_| {});
         state.bus_waker.wake();
@@ -215,6 +222,12 @@ impl PhyType {
 /// Indicates that [State::ep_out_buffers] is empty.
 const EP_OUT_BUFFER_EMPTY: u16 = u16::MAX;
 
+/// Time to wait for L1RSMOK before giving up a remote wakeup from L1.
+///
+/// This is far above the few tens of microseconds L1RSMOK normally takes.
+#[cfg(feature = "time")]
+const L1_RESUME_OK_TIMEOUT: embassy_time::Duration = embassy_time::Duration::from_millis(2);
+
 struct EpState {
     in_waker: AtomicWaker,
     out_waker: AtomicWaker,
@@ -509,6 +522,7 @@ pub struct Bus<'d, const MAX_EP_COUNT: usize> {
 
 impl<'d, const MAX_EP_COUNT: usize> Bus<'d, MAX_EP_COUNT> {
     fn restore_irqs(&mut self) {
+        let lpm = self.instance.lpm_supported && self.instance.regs.glpmcfg().read().lpmen();
         self.instance.regs.gintmsk().write(|w| {
             w.set_usbrst(true);
             w.set_enumdnem(true);
@@ -519,6 +533,7 @@ impl<'d, const MAX_EP_COUNT: usize> Bus<'d, MAX_EP_COUNT> {
             w.set_rxflvlm(true);
             w.set_srqim(true);
             w.set_otgint(true);
+            w.set_lpmintm(lpm);
         });
     }
 
@@ -847,11 +862,22 @@ impl<'d, const MAX_EP_COUNT: usize> embassy_usb_driver::Bus for Bus<'d, MAX_EP_C
 
             if ints.wkupint() {
                 trace!("resume");
+                // Ends a remote wakeup from L1.
+                regs.dctl().modify(|w| w.set_rwusig(false));
                 regs.gintsts().write(|w| w.set_wkupint(true)); // clear
                 self.restore_irqs();
                 return Poll::Ready(Event::Resume);
             }
 
+            if ints.lpmint() {
+                regs.gintsts().write(|w| w.set_lpmint(true)); // clear
+                self.restore_irqs();
+                if regs.glpmcfg().read().slpsts() {
+                    trace!("l1 sleep");
+                    return Poll::Ready(Event::L1Sleep);
+                }
+            }
+
             Poll::Pending
         })
         .await
@@ -980,8 +1006,81 @@ impl<'d, const MAX_EP_COUNT: usize> embassy_usb_driver::Bus for Bus<'d, MAX_EP_C
         //Bus::disable(self);
     }
 
-    async fn remote_wakeup(&mut self) -> Result<(), Unsupported> {
-        Err(Unsupported)
+    async fn remote_wakeup(&mut self) -> Result<(), RemoteWakeupError> {
+        #[cfg(feature = "time")]
+        {
+            let regs = self.instance.regs;
+
+            let lpmcfg = regs.glpmcfg().read();
+            if self.instance.lpm_supported && lpmcfg.slpsts() {
+                // The host allows remote wakeup separately for each L1 request.
+                if !lpmcfg.remwake() {
+                    return Err(RemoteWakeupError::Unsupported);
+                }
+
+                // Wait until the core is allowed to signal resume, or the host resumed on its own.
+                // L1RSMOK is set a few tens of microseconds into L1 and has no interrupt, so it is
+                // polled with a timer.
+                let deadline = embassy_time::Instant::now() + L1_RESUME_OK_TIMEOUT;
+                loop {
+                    let lpmcfg = regs.glpmcfg().read();
+                    if !lpmcfg.slpsts() {
+                        return Ok(());
+                    }
+                    if lpmcfg.l1rsmok() {
+                        break;
+                    }
+                    if embassy_time::Instant::now() >= deadline {
+                        warn!("remote wakeup: timed out waiting for L1RSMOK");
+                        return Err(RemoteWakeupError::Timeout);
+                    }
+                    embassy_time::Timer::after_micros(10).await;
+                }
+
+                // The core ends the L1 resume signalling on its own. RWUSIG is cleared again once
+                // the resume is detected, see `poll`.
+                regs.dctl().modify(|w| w.set_rwusig(true));
+                return Ok(());
+            }
+
+            if !regs.dsts().read().suspsts() {
+                // The host resumed the bus on its own.
+                return Ok(());
+            }
+
+            // The PHY clock may have been stopped while suspended.
+            regs.pcgcctl().modify(|w| {
+                w.set_stppclk(false);
+                w.set_gatehclk(false);
+            });
+
+            regs.dctl().modify(|w| w.set_rwusig(true));
+            // Resume signalling must last between 1 and 15ms.
+            embassy_time::Timer::after_millis(2).await;
+            regs.dctl().modify(|w| w.set_rwusig(false));
+            Ok(())
+        }
+
+        // Resume signalling has to be timed, which needs `embassy-time`.
+        #[cfg(not(feature = "time"))]
+        Err(RemoteWakeupError::Unsupported)
+    }
+
+    fn set_l1_accepted(&mut self, accepted: bool) -> Result<(), Unsupported> {
+        let regs = self.instance.regs;
+
+        if !self.instance.lpm_supported {
+            return Err(Unsupported);
+        }
+
+        critical_section::with(|_| {
+            regs.glpmcfg().modify(|w| {
+                w.set_lpmen(true);
+                w.set_lpmack(accepted);
+            });
+        });
+        self.restore_irqs();
+        Ok(())
     }
 }
 
@@ -1387,6 +1486,8 @@ pub struct OtgInstance<'d, const MAX_EP_COUNT: usize> {
     pub extra_rx_fifo_words: u16,
     /// Whether to set up late cnak
     pub quirk_setup_late_cnak: bool,
+    /// Whether the core implements link power management (LPM), and the `GLPMCFG` register.
+    pub lpm_supported: bool,
     /// Function to calculate TRDT value based on some internal clock speed.
     pub calculate_trdt_fn: fn(speed: vals::Dspd) -> u8,
 }
@@ -2680,6 +2680,17 @@ pub mod regs {
         pub fn set_ptxfe(&mut self, val: bool) {
             self.0 = (self.0 & !(0x01 << 26usize)) | (((val as u32) & 0x01) << 26usize);
         }
+        #[doc = "LPM interrupt"]
+        #[inline(always)]
+        pub const fn lpmint(&self) -> bool {
+            let val = (self.0 >> 27usize) & 0x01;
+            val != 0
+        }
+        #[doc = "LPM interrupt"]
+        #[inline(always)]
+        pub fn set_lpmint(&mut self, val: bool) {
+            self.0 = (self.0 & !(0x01 << 27usize)) | (((val as u32) & 0x01) << 27usize);
+        }
         #[doc = "Connector ID status change"]
         #[inline(always)]
         pub const fn cidschg(&self) -> bool {
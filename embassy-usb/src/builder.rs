@@ -66,6 +66,14 @@ pub struct Config<'a> {
     /// Default: `false`
     pub supports_remote_wakeup: bool,
 
+    /// Whether the device supports link power management (LPM), letting the host put the link
+    /// into the L1 (sleep) state.
+    ///
+    /// L1 requests are accepted by default, see [`UsbDevice::set_l1_accepted`].
+    ///
+    /// Default: `false`
+    pub supports_lpm: bool,
+
     /// Configures the device as a composite device with interface association descriptors.
     ///
     /// If set to `true`, the following fields should have the given values:
@@ -113,6 +121,7 @@ impl<'a> Config<'a> {
             serial_number: None,
             self_powered: false,
             supports_remote_wakeup: false,
+            supports_lpm: false,
             composite_with_iads: false,
             max_power: 100,
         }
@@ -170,7 +179,7 @@ impl<'d, D: Driver<'d>> Builder<'d, D> {
         let mut bos_descriptor = BosWriter::new(DescriptorWriter::new(bos_descriptor_buf));
 
         config_descriptor.configuration(&config);
-        bos_descriptor.bos();
+        bos_descriptor.bos(config.supports_lpm);
 
         Builder {
             driver,
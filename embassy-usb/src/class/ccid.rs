@@ -0,0 +1,511 @@
+//! CCID (Chip/Smart Card Interface Devices) class implementation, aka smartcard reader.
+//!
+//! Exposes a reader with a single, always present card slot doing short APDU level exchanges.
+//! APDUs sent by the host are forwarded to a [`Card`] implementation, which makes it possible to
+//! build security tokens and HSM-like devices.
+//!
+//! The CCID message definitions live in [`message`]. They don't depend on the device stack, so
+//! they can be shared with a host-side CCID driver.
+
+use core::mem::MaybeUninit;
+
+use self::message::*;
+use crate::control::{InResponse, OutResponse, Recipient, Request, RequestType};
+use crate::driver::{Driver, Endpoint, EndpointError, EndpointIn, EndpointOut};
+use crate::types::InterfaceNumber;
+use crate::{Builder, Handler};
+
+/// This should be used as `device_class` when building the `UsbDevice`.
+pub const USB_CLASS_CCID: u8 = 0x0b;
+
+const CCID_SUBCLASS_NONE: u8 = 0x00;
+const CCID_PROTOCOL_NONE: u8 = 0x00;
+
+const CCID_DESCRIPTOR_TYPE: u8 = 0x21;
+
+const REQ_ABORT: u8 = 0x01;
+
+/// Maximum length of a short command APDU: header, Lc, 255 data bytes and Le.
+pub const MAX_COMMAND_APDU_LEN: usize = 261;
+
+/// Maximum length of a short response APDU: 256 data bytes and the status word.
+pub const MAX_RESPONSE_APDU_LEN: usize = 258;
+
+const MAX_MESSAGE_LEN: usize = HEADER_LEN + MAX_COMMAND_APDU_LEN;
+
+// Large enough for `MAX_MESSAGE_LEN` bytes, received in packets of up to 64 bytes.
+const READ_BUF_LEN: usize = 320;
+
+/// Protocol data structure for T=1 returned in `RDR_to_PC_Parameters`: Fi/Di = 372/1, LRC,
+/// BWI = 4, CWI = 5, IFSC = 254.
+const T1_PARAMETERS: [u8; 7] = [0x11, 0x10, 0x00, 0x45, 0x00, 0xfe, 0x00];
+
+/// CCID message definitions, as found on the bulk and interrupt pipes.
+///
+/// Every bulk message starts with a [`Header`], followed by `length` bytes of data.
+pub mod message {
+    /// Length of the header of bulk messages.
+    pub const HEADER_LEN: usize = 10;
+
+    /// `PC_to_RDR_IccPowerOn` message type.
+    pub const PC_TO_RDR_ICC_POWER_ON: u8 = 0x62;
+    /// `PC_to_RDR_IccPowerOff` message type.
+    pub const PC_TO_RDR_ICC_POWER_OFF: u8 = 0x63;
+    /// `PC_to_RDR_GetSlotStatus` message type.
+    pub const PC_TO_RDR_GET_SLOT_STATUS: u8 = 0x65;
+    /// `PC_to_RDR_XfrBlock` message type.
+    pub const PC_TO_RDR_XFR_BLOCK: u8 = 0x6f;
+    /// `PC_to_RDR_GetParameters` message type.
+    pub const PC_TO_RDR_GET_PARAMETERS: u8 = 0x6c;
+    /// `PC_to_RDR_ResetParameters` message type.
+    pub const PC_TO_RDR_RESET_PARAMETERS: u8 = 0x6d;
+    /// `PC_to_RDR_SetParameters` message type.
+    pub const PC_TO_RDR_SET_PARAMETERS: u8 = 0x61;
+    /// `PC_to_RDR_Escape` message type.
+    pub const PC_TO_RDR_ESCAPE: u8 = 0x6b;
+    /// `PC_to_RDR_IccClock` message type.
+    pub const PC_TO_RDR_ICC_CLOCK: u8 = 0x6e;
+    /// `PC_to_RDR_T0APDU` message type.
+    pub const PC_TO_RDR_T0_APDU: u8 = 0x6a;
+    /// `PC_to_RDR_Secure` message type.
+    pub const PC_TO_RDR_SECURE: u8 = 0x69;
+    /// `PC_to_RDR_Mechanical` message type.
+    pub const PC_TO_RDR_MECHANICAL: u8 = 0x71;
+    /// `PC_to_RDR_Abort` message type.
+    pub const PC_TO_RDR_ABORT: u8 = 0x72;
+    /// `PC_to_RDR_SetDataRateAndClockFrequency` message type.
+    pub const PC_TO_RDR_SET_DATA_RATE_AND_CLOCK_FREQUENCY: u8 = 0x73;
+
+    /// `RDR_to_PC_DataBlock` message type.
+    pub const RDR_TO_PC_DATA_BLOCK: u8 = 0x80;
+    /// `RDR_to_PC_SlotStatus` message type.
+    pub const RDR_TO_PC_SLOT_STATUS: u8 = 0x81;
+    /// `RDR_to_PC_Parameters` message type.
+    pub const RDR_TO_PC_PARAMETERS: u8 = 0x82;
+    /// `RDR_to_PC_Escape` message type.
+    pub const RDR_TO_PC_ESCAPE: u8 = 0x83;
+    /// `RDR_to_PC_DataRateAndClockFrequency` message type.
+    pub const RDR_TO_PC_DATA_RATE_AND_CLOCK_FREQUENCY: u8 = 0x84;
+
+    /// `RDR_to_PC_NotifySlotChange` interrupt message type.
+    pub const RDR_TO_PC_NOTIFY_SLOT_CHANGE: u8 = 0x50;
+    /// `RDR_to_PC_HardwareError` interrupt message type.
+    pub const RDR_TO_PC_HARDWARE_ERROR: u8 = 0x51;
+
+    /// `bmICCStatus`: a card is present and active.
+    pub const ICC_STATUS_ACTIVE: u8 = 0x00;
+    /// `bmICCStatus`: a card is present but inactive.
+    pub const ICC_STATUS_INACTIVE: u8 = 0x01;
+    /// `bmICCStatus`: no card is present.
+    pub const ICC_STATUS_NO_ICC: u8 = 0x02;
+
+    /// `bmCommandStatus`: the command was processed without error.
+    pub const COMMAND_STATUS_OK: u8 = 0x00;
+    /// `bmCommandStatus`: the command failed, see `bError`.
+    pub const COMMAND_STATUS_FAILED: u8 = 0x40;
+    /// `bmCommandStatus`: time extension is requested.
+    pub const COMMAND_STATUS_TIME_EXTENSION: u8 = 0x80;
+
+    /// `bError`: the command is not supported.
+    pub const ERROR_CMD_NOT_SUPPORTED: u8 = 0x00;
+    /// `bError`: the slot is busy with another command.
+    pub const ERROR_CMD_SLOT_BUSY: u8 = 0xe0;
+    /// `bError`: a hardware error occurred.
+    pub const ERROR_HW_ERROR: u8 = 0xfb;
+    /// `bError`: the card did not respond.
+    pub const ERROR_ICC_MUTE: u8 = 0xfe;
+    /// `bError`: the command was aborted by the host.
+    pub const ERROR_CMD_ABORTED: u8 = 0xff;
+    /// `bError`: invalid `dwLength` field. Other values below `0x80` give the offset of the
+    /// faulty field in the same way.
+    pub const ERROR_BAD_LENGTH: u8 = 1;
+    /// `bError`: invalid `bSlot` field.
+    pub const ERROR_BAD_SLOT: u8 = 5;
+    /// `bError`: invalid `wLevelParameter` field of `PC_to_RDR_XfrBlock`.
+    pub const ERROR_BAD_LEVEL_PARAMETER: u8 = 8;
+
+    /// Header of a bulk message, in either direction.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub struct Header {
+        /// `bMessageType`.
+        pub message_type: u8,
+        /// `dwLength`, the number of data bytes following the header.
+        pub length: u32,
+        /// `bSlot`.
+        pub slot: u8,
+        /// `bSeq`, echoed in the response to a command.
+        pub seq: u8,
+        /// Message specific bytes. In responses these are `bStatus`, `bError` and a third byte
+        /// depending on the message type.
+        pub specific: [u8; 3],
+    }
+
+    impl Header {
+        /// Parses a header from the start of `buf`.
+        pub fn parse(buf: &[u8]) -> Option<Self> {
+            if buf.len() < HEADER_LEN {
+                return None;
+            }
+
+            Some(Self {
+                message_type: buf[0],
+                length: u32::from_le_bytes(buf[1..5].try_into().unwrap()),
+                slot: buf[5],
+                seq: buf[6],
+                specific: [buf[7], buf[8], buf[9]],
+            })
+        }
+
+        /// Returns the total length of the message, header included.
+        ///
+        /// Returns `None` if `length` is too large to be represented, which can't be a valid message.
+        pub fn message_len(&self) -> Option<usize> {
+            usize::try_from(self.length).ok()?.checked_add(HEADER_LEN)
+        }
+
+        /// Writes the header to the start of `buf`, which must be at least [`HEADER_LEN`] bytes long.
+        pub fn write(&self, buf: &mut [u8]) {
+            buf[0] = self.message_type;
+            buf[1..5].copy_from_slice(&self.length.to_le_bytes());
+            buf[5] = self.slot;
+            buf[6] = self.seq;
+            buf[7..10].copy_from_slice(&self.specific);
+        }
+    }
+
+    /// Returns the message type the reader answers `message_type` with.
+    pub const fn response_type(message_type: u8) -> u8 {
+        match message_type {
+            PC_TO_RDR_ICC_POWER_ON | PC_TO_RDR_XFR_BLOCK | PC_TO_RDR_SECURE => RDR_TO_PC_DATA_BLOCK,
+            PC_TO_RDR_GET_PARAMETERS | PC_TO_RDR_RESET_PARAMETERS | PC_TO_RDR_SET_PARAMETERS => RDR_TO_PC_PARAMETERS,
+            PC_TO_RDR_ESCAPE => RDR_TO_PC_ESCAPE,
+            PC_TO_RDR_SET_DATA_RATE_AND_CLOCK_FREQUENCY => RDR_TO_PC_DATA_RATE_AND_CLOCK_FREQUENCY,
+            _ => RDR_TO_PC_SLOT_STATUS,
+        }
+    }
+}
+
+/// Error returned by a [`Card`] when it can't answer an APDU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CardError {
+    /// The card didn't respond. Reported to the host as `ICC_MUTE`.
+    Mute,
+    /// The card failed. Reported to the host as `HW_ERROR`.
+    Hardware,
+}
+
+/// Smartcard behind the reader slot.
+#[allow(async_fn_in_trait)]
+pub trait Card {
+    /// Called when the host powers the card on, before the ATR is returned.
+    fn power_on(&mut self) {}
+
+    /// Called when the host powers the card off, or the device is disconnected.
+    fn power_off(&mut self) {}
+
+    /// Processes a command APDU, writing the response APDU including the status word to
+    /// `response`. Returns the length of the response.
+    ///
+    /// `response` is [`MAX_RESPONSE_APDU_LEN`] bytes long.
+    async fn transmit(&mut self, apdu: &[u8], response: &mut [u8]) -> Result<usize, CardError>;
+}
+
+/// Configuration for the CCID class.
+pub struct Config<'d> {
+    /// Answer To Reset returned when the card is powered on, at most 33 bytes.
+    ///
+    /// The reader only advertises the T=1 protocol, the ATR should too.
+    pub atr: &'d [u8],
+
+    /// Max packet size for both the IN and OUT endpoints, at most 64.
+    pub max_packet_size: u16,
+}
+
+/// Internal state for USB CCID.
+pub struct State {
+    control: MaybeUninit<Control>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl State {
+    /// Create a new `State`.
+    pub const fn new() -> Self {
+        Self {
+            control: MaybeUninit::uninit(),
+        }
+    }
+}
+
+struct Control {
+    if_num: InterfaceNumber,
+}
+
+impl Handler for Control {
+    fn control_out(&mut self, req: Request, _data: &[u8]) -> Option<OutResponse> {
+        if (req.request_type, req.recipient, req.index)
+            != (RequestType::Class, Recipient::Interface, self.if_num.0 as u16)
+        {
+            return None;
+        }
+
+        match req.request {
+            // Commands are processed one at a time and can't be interrupted, so there is
+            // nothing to abort. The host follows up with `PC_to_RDR_Abort` on the bulk pipe.
+            REQ_ABORT => Some(OutResponse::Accepted),
+            _ => Some(OutResponse::Rejected),
+        }
+    }
+
+    fn control_in<'a>(&'a mut self, req: Request, _buf: &'a mut [u8]) -> Option<InResponse<'a>> {
+        if (req.request_type, req.recipient, req.index)
+            != (RequestType::Class, Recipient::Interface, self.if_num.0 as u16)
+        {
+            return None;
+        }
+
+        // GET_CLOCK_FREQUENCIES and GET_DATA_RATES are not needed: the class descriptor
+        // reports a single default clock and data rate.
+        Some(InResponse::Rejected)
+    }
+}
+
+/// USB CCID class, forwarding APDUs to a [`Card`].
+///
+/// The card can't be removed, so the class has no interrupt endpoint for slot change
+/// notifications. Time extension requests aren't sent: the card must answer before the host
+/// times out.
+pub struct CcidClass<'d, D: Driver<'d>, C: Card> {
+    read_ep: D::EndpointOut,
+    write_ep: D::EndpointIn,
+    card: C,
+    atr: &'d [u8],
+    powered: bool,
+    read_buf: [u8; READ_BUF_LEN],
+    write_buf: [u8; HEADER_LEN + MAX_RESPONSE_APDU_LEN],
+}
+
+impl<'d, D: Driver<'d>, C: Card> CcidClass<'d, D, C> {
+    /// Creates a new CcidClass with the provided UsbBus and card.
+    pub fn new(builder: &mut Builder<'d, D>, state: &'d mut State, card: C, config: Config<'d>) -> Self {
+        assert!(config.atr.len() <= 33);
+        assert!(config.max_packet_size <= 64);
+
+        let mut func = builder.function(USB_CLASS_CCID, CCID_SUBCLASS_NONE, CCID_PROTOCOL_NONE);
+        let mut iface = func.interface();
+        let if_num = iface.interface_number();
+        let mut alt = iface.alt_setting(USB_CLASS_CCID, CCID_SUBCLASS_NONE, CCID_PROTOCOL_NONE, None);
+        alt.descriptor(CCID_DESCRIPTOR_TYPE, &class_descriptor());
+        let read_ep = alt.endpoint_bulk_out(config.max_packet_size);
+        let write_ep = alt.endpoint_bulk_in(config.max_packet_size);
+        drop(func);
+
+        let control = state.control.write(Control { if_num });
+        builder.handler(control);
+
+        Self {
+            read_ep,
+            write_ep,
+            card,
+            atr: config.atr,
+            powered: false,
+            read_buf: [0; READ_BUF_LEN],
+            write_buf: [0; HEADER_LEN + MAX_RESPONSE_APDU_LEN],
+        }
+    }
+
+    /// Gets the maximum packet size in bytes.
+    pub fn max_packet_size(&self) -> u16 {
+        // The size is the same for both endpoints.
+        self.read_ep.info().max_packet_size
+    }
+
+    /// Waits for the USB host to enable this interface
+    pub async fn wait_connection(&mut self) {
+        self.read_ep.wait_enabled().await;
+    }
+
+    /// Serves CCID commands from the host.
+    ///
+    /// This must be running for the host to be able to talk to the card.
+    pub async fn run(&mut self) -> ! {
+        loop {
+            self.wait_connection().await;
+            info!("ccid: connected");
+
+            loop {
+                if let Err(e) = self.handle_message().await {
+                    match e {
+                        EndpointError::Disabled => break,
+                        EndpointError::BufferOverflow => warn!("ccid: received invalid packet"),
+                    }
+                }
+            }
+
+            if self.powered {
+                self.powered = false;
+                self.card.power_off();
+            }
+            info!("ccid: disconnected");
+        }
+    }
+
+    async fn handle_message(&mut self) -> Result<(), EndpointError> {
+        let n = self.read_message().await?;
+        let Some(header) = Header::parse(&self.read_buf[..n]) else {
+            warn!("ccid: message too short");
+            return Ok(());
+        };
+
+        trace!("ccid: message {:02x}, length={}", header.message_type, header.length);
+        let (status, error, param, len) = self.dispatch(&header, n).await;
+
+        let len = len.min(MAX_RESPONSE_APDU_LEN);
+        let icc_status = if header.slot != 0 {
+            ICC_STATUS_NO_ICC
+        } else if self.powered {
+            ICC_STATUS_ACTIVE
+        } else {
+            ICC_STATUS_INACTIVE
+        };
+        Header {
+            message_type: response_type(header.message_type),
+            length: len as u32,
+            slot: header.slot,
+            seq: header.seq,
+            specific: [icc_status | status, error, param],
+        }
+        .write(&mut self.write_buf);
+
+        self.write_message(HEADER_LEN + len).await
+    }
+
+    /// Executes a command whose response data is written after the header in `write_buf`.
+    ///
+    /// Returns the command status, error, message specific parameter and response data length.
+    async fn dispatch(&mut self, header: &Header, n: usize) -> (u8, u8, u8, usize) {
+        const FAILED: u8 = COMMAND_STATUS_FAILED;
+
+        if header.slot != 0 {
+            return (FAILED, ERROR_BAD_SLOT, 0, 0);
+        }
+        if n > self.read_buf.len() || header.message_len() != Some(n) {
+            return (FAILED, ERROR_BAD_LENGTH, 0, 0);
+        }
+
+        match header.message_type {
+            PC_TO_RDR_ICC_POWER_ON => {
+                if !self.powered {
+                    self.powered = true;
+                    self.card.power_on();
+                }
+                self.write_buf[HEADER_LEN..][..self.atr.len()].copy_from_slice(self.atr);
+                (COMMAND_STATUS_OK, 0, 0, self.atr.len())
+            }
+            PC_TO_RDR_ICC_POWER_OFF => {
+                if self.powered {
+                    self.powered = false;
+                    self.card.power_off();
+                }
+                (COMMAND_STATUS_OK, 0, 0, 0)
+            }
+            PC_TO_RDR_GET_SLOT_STATUS | PC_TO_RDR_ICC_CLOCK | PC_TO_RDR_ABORT => (COMMAND_STATUS_OK, 0, 0, 0),
+            PC_TO_RDR_GET_PARAMETERS | PC_TO_RDR_RESET_PARAMETERS | PC_TO_RDR_SET_PARAMETERS => {
+                // The parameters are fixed, a SetParameters just returns them unchanged.
+                self.write_buf[HEADER_LEN..][..T1_PARAMETERS.len()].copy_from_slice(&T1_PARAMETERS);
+                (COMMAND_STATUS_OK, 0, 1, T1_PARAMETERS.len())
+            }
+            PC_TO_RDR_XFR_BLOCK => {
+                if !self.powered {
+                    return (FAILED, ERROR_ICC_MUTE, 0, 0);
+                }
+                // Short APDU level exchange: the APDU always fits in one message.
+                if header.specific[1..3] != [0, 0] {
+                    return (FAILED, ERROR_BAD_LEVEL_PARAMETER, 0, 0);
+                }
+
+                let apdu = &self.read_buf[HEADER_LEN..n];
+                let response = &mut self.write_buf[HEADER_LEN..];
+                match self.card.transmit(apdu, response).await {
+                    Ok(len) => (COMMAND_STATUS_OK, 0, 0, len),
+                    Err(CardError::Mute) => (FAILED, ERROR_ICC_MUTE, 0, 0),
+                    Err(CardError::Hardware) => (FAILED, ERROR_HW_ERROR, 0, 0),
+                }
+            }
+            t => {
+                debug!("ccid: unsupported message {:02x}", t);
+                (FAILED, ERROR_CMD_NOT_SUPPORTED, 0, 0)
+            }
+        }
+    }
+
+    /// Reads a message into `read_buf`, returning its length.
+    async fn read_message(&mut self) -> Result<usize, EndpointError> {
+        let mps = self.max_packet_size() as usize;
+        let mut n = 0;
+        loop {
+            let buf = &mut self.read_buf[n..];
+            if buf.len() < mps {
+                return Err(EndpointError::BufferOverflow);
+            }
+            let len = self.read_ep.read(buf).await?;
+            n += len;
+
+            if let Some(header) = Header::parse(&self.read_buf[..n]) {
+                if header.message_len().is_some_and(|len| n >= len) {
+                    return Ok(n);
+                }
+            }
+            if len < mps {
+                return Ok(n);
+            }
+        }
+    }
+
+    /// Writes the first `len` bytes of `write_buf` as a message.
+    async fn write_message(&mut self, len: usize) -> Result<(), EndpointError> {
+        let mps = self.max_packet_size() as usize;
+        for chunk in self.write_buf[..len].chunks(mps) {
+            self.write_ep.write(chunk).await?;
+        }
+        if len % mps == 0 {
+            self.write_ep.write(&[]).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds the CCID class descriptor, without the length and type bytes.
+fn class_descriptor() -> [u8; 52] {
+    let mut d = [0; 52];
+    d[0..2].copy_from_slice(&0x0110u16.to_le_bytes()); // bcdCCID
+    d[2] = 0; // bMaxSlotIndex
+    d[3] = 0x07; // bVoltageSupport: 5V, 3V, 1.8V
+    d[4..8].copy_from_slice(&0x0000_0002u32.to_le_bytes()); // dwProtocols: T=1
+    d[8..12].copy_from_slice(&4000u32.to_le_bytes()); // dwDefaultClock, in kHz
+    d[12..16].copy_from_slice(&4000u32.to_le_bytes()); // dwMaximumClock
+    d[16] = 0; // bNumClockSupported
+    d[17..21].copy_from_slice(&10752u32.to_le_bytes()); // dwDataRate, in bps
+    d[21..25].copy_from_slice(&10752u32.to_le_bytes()); // dwMaxDataRate
+    d[25] = 0; // bNumDataRatesSupported
+    d[26..30].copy_from_slice(&254u32.to_le_bytes()); // dwMaxIFSD
+    d[30..34].copy_from_slice(&0u32.to_le_bytes()); // dwSynchProtocols
+    d[34..38].copy_from_slice(&0u32.to_le_bytes()); // dwMechanical
+
+    // dwFeatures: automatic parameter configuration, activation, voltage, clock, baud rate and
+    // PPS, with short APDU level exchange.
+    d[38..42].copy_from_slice(&0x0004_00beu32.to_le_bytes());
+    d[42..46].copy_from_slice(&(MAX_MESSAGE_LEN as u32).to_le_bytes()); // dwMaxCCIDMessageLength
+    d[46] = 0xff; // bClassGetResponse: echo the class of the APDU
+    d[47] = 0xff; // bClassEnvelope: echo the class of the APDU
+    d[48..50].copy_from_slice(&0u16.to_le_bytes()); // wLcdLayout: no LCD
+    d[50] = 0; // bPINSupport: no PIN pad
+    d[51] = 1; // bMaxCCIDBusySlots
+    d
+}
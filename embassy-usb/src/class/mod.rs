@@ -1,6 +1,11 @@
 //! Implementations of well-known USB classes.
+pub mod ccid;
 pub mod cdc_acm;
 pub mod cdc_ncm;
 pub mod hid;
 pub mod midi;
+#[cfg(feature = "msc")]
+pub mod msc;
+pub mod uac2;
+pub mod uvc;
 pub mod web_usb;
@@ -0,0 +1,496 @@
+//! USB Mass Storage Class (MSC) implementation, aka USB drive.
+//!
+//! Implements the Bulk-Only Transport (BOT) with the SCSI transparent command set, exposing a
+//! [`NorFlash`] as a single logical unit made of [`BLOCK_SIZE`] byte blocks.
+
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use embedded_storage_async::nor_flash::NorFlash;
+
+use crate::control::{InResponse, OutResponse, Recipient, Request, RequestType};
+use crate::driver::{Driver, Endpoint, EndpointError, EndpointIn, EndpointOut};
+use crate::types::InterfaceNumber;
+use crate::{Builder, Handler};
+
+/// This should be used as `device_class` when building the `UsbDevice`.
+pub const USB_CLASS_MSC: u8 = 0x08;
+
+const MSC_SUBCLASS_SCSI: u8 = 0x06;
+const MSC_PROTOCOL_BBB: u8 = 0x50;
+
+const REQ_GET_MAX_LUN: u8 = 0xfe;
+const REQ_BULK_ONLY_RESET: u8 = 0xff;
+
+const CBW_SIGNATURE: u32 = 0x4342_5355;
+const CBW_LEN: usize = 31;
+const CSW_SIGNATURE: u32 = 0x5342_5355;
+const CSW_LEN: usize = 13;
+
+const SCSI_TEST_UNIT_READY: u8 = 0x00;
+const SCSI_REQUEST_SENSE: u8 = 0x03;
+const SCSI_INQUIRY: u8 = 0x12;
+const SCSI_MODE_SENSE_6: u8 = 0x1a;
+const SCSI_START_STOP_UNIT: u8 = 0x1b;
+const SCSI_PREVENT_ALLOW_MEDIUM_REMOVAL: u8 = 0x1e;
+const SCSI_READ_FORMAT_CAPACITIES: u8 = 0x23;
+const SCSI_READ_CAPACITY_10: u8 = 0x25;
+const SCSI_READ_10: u8 = 0x28;
+const SCSI_WRITE_10: u8 = 0x2a;
+const SCSI_VERIFY_10: u8 = 0x2f;
+const SCSI_SYNCHRONIZE_CACHE_10: u8 = 0x35;
+const SCSI_MODE_SENSE_10: u8 = 0x5a;
+
+/// Size in bytes of a logical block, as seen by the host.
+pub const BLOCK_SIZE: usize = 512;
+
+/// Configuration for the MSC class.
+pub struct Config<'d> {
+    /// T10 vendor identification reported in the INQUIRY response, at most 8 characters.
+    pub vendor: &'d str,
+
+    /// Product identification reported in the INQUIRY response, at most 16 characters.
+    pub product: &'d str,
+
+    /// Product revision level reported in the INQUIRY response, at most 4 characters.
+    pub revision: &'d str,
+
+    /// Max packet size for both the IN and OUT endpoints.
+    pub max_packet_size: u16,
+}
+
+/// Internal state for USB MSC.
+pub struct State<'d> {
+    control: MaybeUninit<Control<'d>>,
+    shared: ControlShared,
+}
+
+impl<'d> Default for State<'d> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'d> State<'d> {
+    /// Create a new `State`.
+    pub const fn new() -> Self {
+        Self {
+            control: MaybeUninit::uninit(),
+            shared: ControlShared {
+                reset: AtomicBool::new(false),
+            },
+        }
+    }
+}
+
+/// Shared data between Control and MscClass
+struct ControlShared {
+    reset: AtomicBool,
+}
+
+struct Control<'d> {
+    if_num: InterfaceNumber,
+    shared: &'d ControlShared,
+}
+
+impl<'d> Handler for Control<'d> {
+    fn reset(&mut self) {
+        self.shared.reset.store(true, Ordering::Relaxed);
+    }
+
+    fn control_out(&mut self, req: Request, _data: &[u8]) -> Option<OutResponse> {
+        if (req.request_type, req.recipient, req.index)
+            != (RequestType::Class, Recipient::Interface, self.if_num.0 as u16)
+        {
+            return None;
+        }
+
+        match req.request {
+            REQ_BULK_ONLY_RESET => {
+                debug!("msc: bulk-only mass storage reset");
+                self.shared.reset.store(true, Ordering::Relaxed);
+                Some(OutResponse::Accepted)
+            }
+            _ => Some(OutResponse::Rejected),
+        }
+    }
+
+    fn control_in<'a>(&'a mut self, req: Request, buf: &'a mut [u8]) -> Option<InResponse<'a>> {
+        if (req.request_type, req.recipient, req.index)
+            != (RequestType::Class, Recipient::Interface, self.if_num.0 as u16)
+        {
+            return None;
+        }
+
+        match req.request {
+            REQ_GET_MAX_LUN => {
+                // Only a single logical unit is supported.
+                buf[0] = 0;
+                Some(InResponse::Accepted(&buf[..1]))
+            }
+            _ => Some(InResponse::Rejected),
+        }
+    }
+}
+
+/// SCSI sense data describing why the last command failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Sense {
+    key: u8,
+    asc: u8,
+}
+
+impl Sense {
+    const NO_SENSE: Self = Self { key: 0x00, asc: 0x00 };
+    const UNRECOVERED_READ_ERROR: Self = Self { key: 0x03, asc: 0x11 };
+    const WRITE_ERROR: Self = Self { key: 0x03, asc: 0x0c };
+    const INVALID_COMMAND: Self = Self { key: 0x05, asc: 0x20 };
+    const LBA_OUT_OF_RANGE: Self = Self { key: 0x05, asc: 0x21 };
+}
+
+/// Command status reported to the host in the CSW.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum Status {
+    Passed = 0x00,
+    Failed = 0x01,
+    /// The host and the device disagree on the data stage; the host recovers with a reset.
+    PhaseError = 0x02,
+}
+
+/// Command Block Wrapper, sent by the host to start a command.
+struct Cbw {
+    tag: u32,
+    data_len: u32,
+    dir_in: bool,
+    cb: [u8; 16],
+}
+
+impl Cbw {
+    fn parse(buf: &[u8]) -> Option<Self> {
+        if buf.len() != CBW_LEN || u32::from_le_bytes(buf[0..4].try_into().unwrap()) != CBW_SIGNATURE {
+            return None;
+        }
+
+        let cb_len = buf[14] as usize;
+        if buf[13] != 0 || !(1..=16).contains(&cb_len) {
+            return None;
+        }
+
+        let mut cb = [0; 16];
+        cb[..cb_len].copy_from_slice(&buf[15..15 + cb_len]);
+        Some(Self {
+            tag: u32::from_le_bytes(buf[4..8].try_into().unwrap()),
+            data_len: u32::from_le_bytes(buf[8..12].try_into().unwrap()),
+            dir_in: buf[12] & 0x80 != 0,
+            cb,
+        })
+    }
+}
+
+/// USB Mass Storage class, serving SCSI commands from a [`NorFlash`].
+///
+/// The flash is presented to the host as a disk made of [`BLOCK_SIZE`] byte blocks. Writes are
+/// done by reading a whole erase sector into the scratch buffer, merging the written blocks in,
+/// then erasing and reprogramming the sector.
+///
+/// Errors in the data stage are reported through the command status and sense data, without
+/// stalling the bulk endpoints, which the class can't do on its own.
+pub struct MscClass<'d, D: Driver<'d>, F: NorFlash> {
+    read_ep: D::EndpointOut,
+    write_ep: D::EndpointIn,
+    control: &'d ControlShared,
+    flash: F,
+    buf: &'d mut [u8],
+    inquiry: [u8; 36],
+    sense: Sense,
+}
+
+impl<'d, D: Driver<'d>, F: NorFlash> MscClass<'d, D, F> {
+    /// Creates a new MscClass exposing `flash` to the host.
+    ///
+    /// `buf` is used as scratch space for the erase sectors and must be at least `F::ERASE_SIZE`
+    /// bytes long. `F::ERASE_SIZE` must be a multiple of [`BLOCK_SIZE`], and the max packet size a
+    /// divisor of it. The flash must hold at least one block.
+    pub fn new(
+        builder: &mut Builder<'d, D>,
+        state: &'d mut State<'d>,
+        flash: F,
+        buf: &'d mut [u8],
+        config: Config<'d>,
+    ) -> Self {
+        assert!(F::ERASE_SIZE % BLOCK_SIZE == 0);
+        assert!(BLOCK_SIZE % config.max_packet_size as usize == 0);
+        assert!(buf.len() >= F::ERASE_SIZE);
+        assert!(flash.capacity() >= BLOCK_SIZE);
+
+        let mut func = builder.function(USB_CLASS_MSC, MSC_SUBCLASS_SCSI, MSC_PROTOCOL_BBB);
+        let mut iface = func.interface();
+        let if_num = iface.interface_number();
+        let mut alt = iface.alt_setting(USB_CLASS_MSC, MSC_SUBCLASS_SCSI, MSC_PROTOCOL_BBB, None);
+        let read_ep = alt.endpoint_bulk_out(config.max_packet_size);
+        let write_ep = alt.endpoint_bulk_in(config.max_packet_size);
+        drop(func);
+
+        let control = state.control.write(Control {
+            if_num,
+            shared: &state.shared,
+        });
+        builder.handler(control);
+
+        let mut inquiry = [0; 36];
+        inquiry[1] = 0x80; // removable medium
+        inquiry[2] = 0x04; // SPC-2
+        inquiry[3] = 0x02; // response data format
+        inquiry[4] = inquiry.len() as u8 - 5; // additional length
+        fill_ascii(&mut inquiry[8..16], config.vendor);
+        fill_ascii(&mut inquiry[16..32], config.product);
+        fill_ascii(&mut inquiry[32..36], config.revision);
+
+        Self {
+            read_ep,
+            write_ep,
+            control: &state.shared,
+            flash,
+            buf,
+            inquiry,
+            sense: Sense::NO_SENSE,
+        }
+    }
+
+    /// Gets the maximum packet size in bytes.
+    pub fn max_packet_size(&self) -> u16 {
+        // The size is the same for both endpoints.
+        self.read_ep.info().max_packet_size
+    }
+
+    /// Waits for the USB host to enable this interface
+    pub async fn wait_connection(&mut self) {
+        self.read_ep.wait_enabled().await;
+    }
+
+    /// Serves SCSI commands from the host.
+    ///
+    /// This must be running for the host to be able to access the drive.
+    pub async fn run(&mut self) -> ! {
+        loop {
+            self.wait_connection().await;
+            info!("msc: connected");
+            self.control.reset.store(false, Ordering::Relaxed);
+            self.sense = Sense::NO_SENSE;
+
+            loop {
+                if let Err(e) = self.handle_command().await {
+                    match e {
+                        EndpointError::Disabled => break,
+                        EndpointError::BufferOverflow => warn!("msc: received invalid packet"),
+                    }
+                }
+            }
+            info!("msc: disconnected");
+        }
+    }
+
+    async fn handle_command(&mut self) -> Result<(), EndpointError> {
+        let mut packet = [0; CBW_LEN];
+        let n = self.read_ep.read(&mut packet).await?;
+
+        if self.control.reset.swap(false, Ordering::Relaxed) {
+            debug!("msc: discarding packet received before reset");
+            return Ok(());
+        }
+
+        let Some(cbw) = Cbw::parse(&packet[..n]) else {
+            warn!("msc: invalid CBW");
+            return Ok(());
+        };
+
+        trace!("msc: command {:02x}, data_len={}", cbw.cb[0], cbw.data_len);
+        let (transferred, status) = self.dispatch(&cbw).await?;
+
+        // The host expects exactly `data_len` bytes to be moved. Terminate the IN data stage
+        // with a short packet, and drain what the host still wants to send. After a phase
+        // error the host already ended the data stage.
+        if transferred < cbw.data_len && status != Status::PhaseError {
+            if cbw.dir_in {
+                if transferred % self.max_packet_size() as u32 == 0 {
+                    self.write_ep.write(&[]).await?;
+                }
+            } else {
+                self.discard(cbw.data_len - transferred).await?;
+            }
+        }
+
+        let mut csw = [0; CSW_LEN];
+        csw[0..4].copy_from_slice(&CSW_SIGNATURE.to_le_bytes());
+        csw[4..8].copy_from_slice(&cbw.tag.to_le_bytes());
+        csw[8..12].copy_from_slice(&(cbw.data_len - transferred).to_le_bytes());
+        csw[12] = status as u8;
+        self.write_ep.write(&csw).await
+    }
+
+    /// Executes a command. Returns the number of data bytes transferred, and the command status.
+    async fn dispatch(&mut self, cbw: &Cbw) -> Result<(u32, Status), EndpointError> {
+        let cb = &cbw.cb;
+        let blocks = self.flash.capacity() / BLOCK_SIZE;
+
+        let mut resp = [0; 18];
+        let resp: &[u8] = match cb[0] {
+            SCSI_TEST_UNIT_READY
+            | SCSI_START_STOP_UNIT
+            | SCSI_PREVENT_ALLOW_MEDIUM_REMOVAL
+            | SCSI_VERIFY_10
+            | SCSI_SYNCHRONIZE_CACHE_10 => return Ok((0, Status::Passed)),
+            SCSI_INQUIRY => &self.inquiry,
+            SCSI_REQUEST_SENSE => {
+                resp[0] = 0x70; // current error, fixed format
+                resp[2] = self.sense.key;
+                resp[7] = 10; // additional sense length
+                resp[12] = self.sense.asc;
+                self.sense = Sense::NO_SENSE;
+                &resp[..18]
+            }
+            SCSI_MODE_SENSE_6 => {
+                resp[0] = 3; // mode data length
+                &resp[..4]
+            }
+            SCSI_MODE_SENSE_10 => {
+                resp[1] = 6; // mode data length
+                &resp[..8]
+            }
+            SCSI_READ_FORMAT_CAPACITIES => {
+                resp[3] = 8; // capacity list length
+                resp[4..8].copy_from_slice(&(blocks as u32).to_be_bytes());
+                resp[8] = 0x02; // formatted media
+                resp[9..12].copy_from_slice(&(BLOCK_SIZE as u32).to_be_bytes()[1..]);
+                &resp[..12]
+            }
+            SCSI_READ_CAPACITY_10 => {
+                resp[0..4].copy_from_slice(&(blocks as u32 - 1).to_be_bytes());
+                resp[4..8].copy_from_slice(&(BLOCK_SIZE as u32).to_be_bytes());
+                &resp[..8]
+            }
+            SCSI_READ_10 | SCSI_WRITE_10 => {
+                let lba = u32::from_be_bytes(cb[2..6].try_into().unwrap()) as usize;
+                let count = u16::from_be_bytes(cb[7..9].try_into().unwrap()) as usize;
+                let len = count * BLOCK_SIZE;
+
+                if lba.checked_add(count).map_or(true, |end| end > blocks) {
+                    self.sense = Sense::LBA_OUT_OF_RANGE;
+                    return Ok((0, Status::Failed));
+                }
+                if len > cbw.data_len as usize || cbw.dir_in != (cb[0] == SCSI_READ_10) {
+                    self.sense = Sense::INVALID_COMMAND;
+                    return Ok((0, Status::Failed));
+                }
+
+                return match cb[0] {
+                    SCSI_READ_10 => self.read_blocks(lba, count).await,
+                    _ => self.write_blocks(lba, count).await,
+                };
+            }
+            op => {
+                debug!("msc: unsupported command {:02x}", op);
+                self.sense = Sense::INVALID_COMMAND;
+                return Ok((0, Status::Failed));
+            }
+        };
+
+        let len = resp.len().min(cbw.data_len as usize);
+        for chunk in resp[..len].chunks(self.max_packet_size() as usize) {
+            self.write_ep.write(chunk).await?;
+        }
+        Ok((len as u32, Status::Passed))
+    }
+
+    async fn read_blocks(&mut self, lba: usize, count: usize) -> Result<(u32, Status), EndpointError> {
+        let mps = self.max_packet_size() as usize;
+        let buf = &mut self.buf[..BLOCK_SIZE];
+
+        for i in 0..count {
+            let offset = (lba + i) * BLOCK_SIZE;
+            if self.flash.read(offset as u32, buf).await.is_err() {
+                self.sense = Sense::UNRECOVERED_READ_ERROR;
+                return Ok(((i * BLOCK_SIZE) as u32, Status::Failed));
+            }
+            for chunk in buf.chunks(mps) {
+                self.write_ep.write(chunk).await?;
+            }
+        }
+
+        Ok(((count * BLOCK_SIZE) as u32, Status::Passed))
+    }
+
+    async fn write_blocks(&mut self, lba: usize, count: usize) -> Result<(u32, Status), EndpointError> {
+        let mps = self.max_packet_size() as usize;
+        let mut loaded = None;
+        let mut passed = true;
+
+        for i in 0..count {
+            let offset = (lba + i) * BLOCK_SIZE;
+            let sector = offset - offset % F::ERASE_SIZE;
+
+            if passed && loaded != Some(sector) {
+                if let Some(prev) = loaded {
+                    passed = self.program_sector(prev).await;
+                }
+                if passed {
+                    let buf = &mut self.buf[..F::ERASE_SIZE];
+                    passed = self.flash.read(sector as u32, buf).await.is_ok();
+                }
+                loaded = Some(sector);
+            }
+
+            // Keep receiving the data even after a failure: the host will send it anyway.
+            let start = offset - sector;
+            for (j, chunk) in self.buf[start..start + BLOCK_SIZE].chunks_mut(mps).enumerate() {
+                let n = self.read_ep.read(chunk).await?;
+                if n != chunk.len() {
+                    // The host ended the data stage early: nothing of the block can be trusted,
+                    // and the residue no longer matches what the host thinks it sent.
+                    warn!("msc: short packet in WRITE(10) data stage");
+                    return Ok(((i * BLOCK_SIZE + j * mps + n) as u32, Status::PhaseError));
+                }
+            }
+        }
+
+        if passed {
+            if let Some(sector) = loaded {
+                passed = self.program_sector(sector).await;
+            }
+        }
+
+        if !passed {
+            self.sense = Sense::WRITE_ERROR;
+            return Ok(((count * BLOCK_SIZE) as u32, Status::Failed));
+        }
+        Ok(((count * BLOCK_SIZE) as u32, Status::Passed))
+    }
+
+    /// Writes back the erase sector held in the scratch buffer.
+    async fn program_sector(&mut self, sector: usize) -> bool {
+        let from = sector as u32;
+        let to = from + F::ERASE_SIZE as u32;
+        self.flash.erase(from, to).await.is_ok() && self.flash.write(from, &self.buf[..F::ERASE_SIZE]).await.is_ok()
+    }
+
+    async fn discard(&mut self, mut len: u32) -> Result<(), EndpointError> {
+        let mps = self.max_packet_size() as usize;
+        while len > 0 {
+            let n = self.read_ep.read(&mut self.buf[..mps]).await?;
+            len = len.saturating_sub(n as u32);
+            if n < mps {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Copies `s` into `dst`, padded with spaces as required for SCSI ASCII fields.
+fn fill_ascii(dst: &mut [u8], s: &str) {
+    dst.fill(b' ');
+    let n = s.len().min(dst.len());
+    dst[..n].copy_from_slice(&s.as_bytes()[..n]);
+}
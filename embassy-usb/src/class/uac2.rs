@@ -0,0 +1,514 @@
+//! USB Audio Class 2.0 implementation.
+//!
+//! Implements a speaker: PCM audio is streamed from the host through an asynchronous isochronous
+//! OUT endpoint, and the device reports its actual sample rate through an explicit feedback
+//! endpoint. Only full-speed operation is supported.
+
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicBool, AtomicI16, AtomicU32, Ordering};
+use core::task::Poll;
+
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::control::{InResponse, OutResponse, Recipient, Request, RequestType};
+use crate::descriptor::{SynchronizationType, UsageType};
+use crate::driver::{Driver, Endpoint, EndpointError, EndpointIn, EndpointOut};
+use crate::types::InterfaceNumber;
+use crate::{Builder, Handler};
+
+/// This should be used as `device_class` when building the `UsbDevice`.
+pub const USB_AUDIO_CLASS: u8 = 0x01;
+
+const FUNCTION_SUBCLASS_UNDEFINED: u8 = 0x00;
+const AF_VERSION_02_00: u8 = 0x20;
+const USB_AUDIOCONTROL_SUBCLASS: u8 = 0x01;
+const USB_AUDIOSTREAMING_SUBCLASS: u8 = 0x02;
+const IP_VERSION_02_00: u8 = 0x20;
+
+const CS_INTERFACE: u8 = 0x24;
+const CS_ENDPOINT: u8 = 0x25;
+
+// Audio control interface descriptor subtypes
+const AC_HEADER: u8 = 0x01;
+const AC_INPUT_TERMINAL: u8 = 0x02;
+const AC_OUTPUT_TERMINAL: u8 = 0x03;
+const AC_FEATURE_UNIT: u8 = 0x06;
+const AC_CLOCK_SOURCE: u8 = 0x0a;
+
+// Audio streaming interface descriptor subtypes
+const AS_GENERAL: u8 = 0x01;
+const AS_FORMAT_TYPE: u8 = 0x02;
+const EP_GENERAL: u8 = 0x01;
+
+const FUNCTION_CATEGORY_DESKTOP_SPEAKER: u8 = 0x01;
+const TERMINAL_USB_STREAMING: u16 = 0x0101;
+const TERMINAL_SPEAKER: u16 = 0x0301;
+const FORMAT_TYPE_I: u8 = 0x01;
+const FORMAT_PCM: u32 = 0x0000_0001;
+
+// Entity IDs
+const CLOCK_SOURCE_ID: u8 = 1;
+const INPUT_TERMINAL_ID: u8 = 2;
+const FEATURE_UNIT_ID: u8 = 3;
+const OUTPUT_TERMINAL_ID: u8 = 4;
+
+// Class-specific requests
+const REQ_CUR: u8 = 0x01;
+const REQ_RANGE: u8 = 0x02;
+
+// Control selectors
+const CS_SAM_FREQ_CONTROL: u8 = 0x01;
+const CS_CLOCK_VALID_CONTROL: u8 = 0x02;
+const FU_MUTE_CONTROL: u8 = 0x01;
+const FU_VOLUME_CONTROL: u8 = 0x02;
+
+/// Volume range reported to the host, in 1/256 dB.
+const VOLUME_MIN: i16 = -100 * 256;
+const VOLUME_MAX: i16 = 0;
+const VOLUME_RES: i16 = 256;
+
+/// Size of a single sample in the stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SampleWidth {
+    /// 16 bit samples, in 2 byte subslots.
+    Width16Bit,
+    /// 24 bit samples, in 3 byte subslots.
+    Width24Bit,
+    /// 32 bit samples, in 4 byte subslots.
+    Width32Bit,
+}
+
+impl SampleWidth {
+    /// Size in bytes of a sample in the stream.
+    pub const fn in_bytes(self) -> usize {
+        match self {
+            SampleWidth::Width16Bit => 2,
+            SampleWidth::Width24Bit => 3,
+            SampleWidth::Width32Bit => 4,
+        }
+    }
+}
+
+/// Configuration for the UAC2 class.
+pub struct Config<'d> {
+    /// Sample rates supported by the device, in Hz. The first one is used until the host selects one.
+    pub sample_rates_hz: &'d [u32],
+
+    /// Number of interleaved channels in the stream, in the standard spatial order (front left,
+    /// front right, front center, ...).
+    pub channels: u8,
+
+    /// Size of a single sample.
+    pub sample_width: SampleWidth,
+}
+
+/// Internal state for USB Audio Class 2.0.
+pub struct State<'d> {
+    control: MaybeUninit<Control<'d>>,
+    shared: ControlShared,
+}
+
+impl<'d> Default for State<'d> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'d> State<'d> {
+    /// Create a new `State`.
+    pub const fn new() -> Self {
+        Self {
+            control: MaybeUninit::uninit(),
+            shared: ControlShared {
+                sample_rate_hz: AtomicU32::new(0),
+                mute: AtomicBool::new(false),
+                volume: AtomicI16::new(VOLUME_MAX),
+                changed: AtomicBool::new(false),
+                waker: AtomicWaker::new(),
+            },
+        }
+    }
+}
+
+/// Shared data between Control and the class handles.
+struct ControlShared {
+    sample_rate_hz: AtomicU32,
+    mute: AtomicBool,
+    volume: AtomicI16,
+    changed: AtomicBool,
+    waker: AtomicWaker,
+}
+
+impl ControlShared {
+    fn notify(&self) {
+        self.changed.store(true, Ordering::Relaxed);
+        self.waker.wake();
+    }
+}
+
+struct Control<'d> {
+    ac_if: InterfaceNumber,
+    sample_rates_hz: &'d [u32],
+    shared: &'d ControlShared,
+}
+
+impl<'d> Control<'d> {
+    /// Returns the addressed (entity, control selector), if the request targets the control interface.
+    fn target(&self, req: &Request) -> Option<(u8, u8)> {
+        if (req.request_type, req.recipient, req.index as u8)
+            != (RequestType::Class, Recipient::Interface, self.ac_if.0)
+        {
+            return None;
+        }
+        Some(((req.index >> 8) as u8, (req.value >> 8) as u8))
+    }
+}
+
+impl<'d> Handler for Control<'d> {
+    fn reset(&mut self) {
+        let shared = self.shared;
+        shared.sample_rate_hz.store(self.sample_rates_hz[0], Ordering::Relaxed);
+        shared.mute.store(false, Ordering::Relaxed);
+        shared.volume.store(VOLUME_MAX, Ordering::Relaxed);
+        shared.notify();
+    }
+
+    fn control_out(&mut self, req: Request, data: &[u8]) -> Option<OutResponse> {
+        let (entity, selector) = self.target(&req)?;
+        if req.request != REQ_CUR {
+            return Some(OutResponse::Rejected);
+        }
+
+        let shared = self.shared;
+        match (entity, selector) {
+            (CLOCK_SOURCE_ID, CS_SAM_FREQ_CONTROL) if data.len() >= 4 => {
+                let rate = u32::from_le_bytes(data[0..4].try_into().unwrap());
+                if !self.sample_rates_hz.contains(&rate) {
+                    return Some(OutResponse::Rejected);
+                }
+                debug!("uac2: set sample rate to {} Hz", rate);
+                shared.sample_rate_hz.store(rate, Ordering::Relaxed);
+            }
+            (FEATURE_UNIT_ID, FU_MUTE_CONTROL) if !data.is_empty() => {
+                shared.mute.store(data[0] != 0, Ordering::Relaxed);
+            }
+            (FEATURE_UNIT_ID, FU_VOLUME_CONTROL) if data.len() >= 2 => {
+                let volume = i16::from_le_bytes(data[0..2].try_into().unwrap());
+                shared
+                    .volume
+                    .store(volume.clamp(VOLUME_MIN, VOLUME_MAX), Ordering::Relaxed);
+            }
+            _ => return Some(OutResponse::Rejected),
+        }
+
+        shared.notify();
+        Some(OutResponse::Accepted)
+    }
+
+    fn control_in<'a>(&'a mut self, req: Request, buf: &'a mut [u8]) -> Option<InResponse<'a>> {
+        let (entity, selector) = self.target(&req)?;
+
+        let shared = self.shared;
+        let len = match (req.request, entity, selector) {
+            (REQ_CUR, CLOCK_SOURCE_ID, CS_SAM_FREQ_CONTROL) => {
+                buf[0..4].copy_from_slice(&shared.sample_rate_hz.load(Ordering::Relaxed).to_le_bytes());
+                4
+            }
+            (REQ_RANGE, CLOCK_SOURCE_ID, CS_SAM_FREQ_CONTROL) => {
+                buf[0..2].copy_from_slice(&(self.sample_rates_hz.len() as u16).to_le_bytes());
+                for (range, &rate) in buf[2..].chunks_exact_mut(12).zip(self.sample_rates_hz) {
+                    range[0..4].copy_from_slice(&rate.to_le_bytes()); // dMIN
+                    range[4..8].copy_from_slice(&rate.to_le_bytes()); // dMAX
+                    range[8..12].fill(0); // dRES
+                }
+                2 + 12 * self.sample_rates_hz.len()
+            }
+            (REQ_CUR, CLOCK_SOURCE_ID, CS_CLOCK_VALID_CONTROL) => {
+                buf[0] = 1;
+                1
+            }
+            (REQ_CUR, FEATURE_UNIT_ID, FU_MUTE_CONTROL) => {
+                buf[0] = shared.mute.load(Ordering::Relaxed) as u8;
+                1
+            }
+            (REQ_CUR, FEATURE_UNIT_ID, FU_VOLUME_CONTROL) => {
+                buf[0..2].copy_from_slice(&shared.volume.load(Ordering::Relaxed).to_le_bytes());
+                2
+            }
+            (REQ_RANGE, FEATURE_UNIT_ID, FU_VOLUME_CONTROL) => {
+                buf[0..2].copy_from_slice(&1u16.to_le_bytes());
+                buf[2..4].copy_from_slice(&VOLUME_MIN.to_le_bytes());
+                buf[4..6].copy_from_slice(&VOLUME_MAX.to_le_bytes());
+                buf[6..8].copy_from_slice(&VOLUME_RES.to_le_bytes());
+                8
+            }
+            _ => return Some(InResponse::Rejected),
+        };
+
+        Some(InResponse::Accepted(&buf[..len]))
+    }
+}
+
+/// USB Audio Class 2.0 speaker.
+///
+/// Use [`Speaker::new`] to create the class, which returns separate handles for the audio stream,
+/// the feedback endpoint and the audio controls so they can be used from different tasks.
+pub struct Speaker;
+
+impl Speaker {
+    /// Creates a new UAC2 speaker on the builder.
+    ///
+    /// The audio function is added with an interface association descriptor, so the device should
+    /// be configured with `composite_with_iads`.
+    ///
+    /// The explicit feedback endpoint is the second endpoint of the streaming interface. Hosts pair
+    /// it with the data endpoint by position, so it doesn't need to share the endpoint number.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new<'d, D: Driver<'d>>(
+        builder: &mut Builder<'d, D>,
+        state: &'d mut State<'d>,
+        config: Config<'d>,
+    ) -> (Stream<'d, D>, Feedback<'d, D>, ControlMonitor<'d>) {
+        assert!(!config.sample_rates_hz.is_empty());
+        assert!((1..=8).contains(&config.channels));
+        assert!(builder.control_buf_len() >= 2 + 12 * config.sample_rates_hz.len());
+
+        let channels = config.channels;
+        let channel_config = ((1u32 << channels) - 1).to_le_bytes();
+        let subslot = config.sample_width.in_bytes();
+        let max_rate = *config.sample_rates_hz.iter().max().unwrap();
+
+        // An asynchronous sink may receive one extra sample per frame.
+        let max_packet_size = ((max_rate as usize / 1000 + 1) * channels as usize * subslot) as u16;
+
+        let mut func = builder.function(USB_AUDIO_CLASS, FUNCTION_SUBCLASS_UNDEFINED, AF_VERSION_02_00);
+
+        // Audio control interface
+        let mut iface = func.interface();
+        let ac_if = iface.interface_number();
+        let mut alt = iface.alt_setting(USB_AUDIO_CLASS, USB_AUDIOCONTROL_SUBCLASS, IP_VERSION_02_00, None);
+
+        let feature_unit_len = 6 + 4 * (channels as usize + 1);
+        let ac_total_length = (9 + 8 + 17 + feature_unit_len + 12) as u16;
+        alt.descriptor(
+            CS_INTERFACE,
+            &[
+                AC_HEADER,
+                0x00,
+                0x02, // bcdADC (2.00)
+                FUNCTION_CATEGORY_DESKTOP_SPEAKER,
+                ac_total_length as u8,
+                (ac_total_length >> 8) as u8,
+                0x00, // bmControls
+            ],
+        );
+        alt.descriptor(
+            CS_INTERFACE,
+            &[
+                AC_CLOCK_SOURCE,
+                CLOCK_SOURCE_ID,
+                0x03, // bmAttributes: internal programmable clock
+                0x07, // bmControls: frequency read/write, validity read-only
+                0x00, // bAssocTerminal
+                0x00, // iClockSource
+            ],
+        );
+        alt.descriptor(
+            CS_INTERFACE,
+            &[
+                AC_INPUT_TERMINAL,
+                INPUT_TERMINAL_ID,
+                TERMINAL_USB_STREAMING as u8,
+                (TERMINAL_USB_STREAMING >> 8) as u8,
+                0x00, // bAssocTerminal
+                CLOCK_SOURCE_ID,
+                channels,
+                channel_config[0],
+                channel_config[1],
+                channel_config[2],
+                channel_config[3],
+                0x00, // iChannelNames
+                0x00,
+                0x00, // bmControls
+                0x00, // iTerminal
+            ],
+        );
+
+        // Master mute and volume, no per-channel controls.
+        let mut feature_unit = [0; 3 + 4 * 9 + 1];
+        feature_unit[..3].copy_from_slice(&[AC_FEATURE_UNIT, FEATURE_UNIT_ID, INPUT_TERMINAL_ID]);
+        feature_unit[3] = 0x0f;
+        alt.descriptor(CS_INTERFACE, &feature_unit[..feature_unit_len - 2]);
+
+        alt.descriptor(
+            CS_INTERFACE,
+            &[
+                AC_OUTPUT_TERMINAL,
+                OUTPUT_TERMINAL_ID,
+                TERMINAL_SPEAKER as u8,
+                (TERMINAL_SPEAKER >> 8) as u8,
+                0x00, // bAssocTerminal
+                FEATURE_UNIT_ID,
+                CLOCK_SOURCE_ID,
+                0x00,
+                0x00, // bmControls
+                0x00, // iTerminal
+            ],
+        );
+
+        // Audio streaming interface
+        let mut iface = func.interface();
+
+        // Alternate setting 0 has no endpoints, so that no bandwidth is used while not streaming.
+        iface.alt_setting(USB_AUDIO_CLASS, USB_AUDIOSTREAMING_SUBCLASS, IP_VERSION_02_00, None);
+
+        let mut alt = iface.alt_setting(USB_AUDIO_CLASS, USB_AUDIOSTREAMING_SUBCLASS, IP_VERSION_02_00, None);
+        alt.descriptor(
+            CS_INTERFACE,
+            &[
+                AS_GENERAL,
+                INPUT_TERMINAL_ID,
+                0x00, // bmControls
+                FORMAT_TYPE_I,
+                FORMAT_PCM as u8,
+                (FORMAT_PCM >> 8) as u8,
+                (FORMAT_PCM >> 16) as u8,
+                (FORMAT_PCM >> 24) as u8,
+                channels,
+                channel_config[0],
+                channel_config[1],
+                channel_config[2],
+                channel_config[3],
+                0x00, // iChannelNames
+            ],
+        );
+        alt.descriptor(
+            CS_INTERFACE,
+            &[AS_FORMAT_TYPE, FORMAT_TYPE_I, subslot as u8, (subslot * 8) as u8],
+        );
+
+        let read_ep = alt.endpoint_isochronous_out(
+            max_packet_size,
+            1,
+            SynchronizationType::Asynchronous,
+            UsageType::DataEndpoint,
+            &[],
+        );
+        // No pitch or over/underrun controls, no lock delay.
+        alt.descriptor(CS_ENDPOINT, &[EP_GENERAL, 0x00, 0x00, 0x00, 0x00, 0x00]);
+
+        // Full-speed feedback is a 10.14 fixed point value in 3 bytes.
+        let feedback_ep = alt.endpoint_isochronous_in(
+            3,
+            1,
+            SynchronizationType::NoSynchronization,
+            UsageType::FeedbackEndpoint,
+            &[],
+        );
+
+        drop(func);
+
+        let shared = &state.shared;
+        shared
+            .sample_rate_hz
+            .store(config.sample_rates_hz[0], Ordering::Relaxed);
+        let control = state.control.write(Control {
+            ac_if,
+            sample_rates_hz: config.sample_rates_hz,
+            shared: &state.shared,
+        });
+        builder.handler(control);
+
+        (
+            Stream { read_ep },
+            Feedback { write_ep: feedback_ep },
+            ControlMonitor { shared },
+        )
+    }
+}
+
+/// Audio stream from the host.
+///
+/// You can obtain a `Stream` with [`Speaker::new`].
+pub struct Stream<'d, D: Driver<'d>> {
+    read_ep: D::EndpointOut,
+}
+
+impl<'d, D: Driver<'d>> Stream<'d, D> {
+    /// Gets the maximum packet size in bytes.
+    pub fn max_packet_size(&self) -> u16 {
+        self.read_ep.info().max_packet_size
+    }
+
+    /// Reads a single packet of interleaved samples, sent by the host once per frame.
+    ///
+    /// `data` must be at least `max_packet_size` bytes long.
+    pub async fn read_packet(&mut self, data: &mut [u8]) -> Result<usize, EndpointError> {
+        self.read_ep.read(data).await
+    }
+
+    /// Waits for the USB host to start streaming, by selecting the operational alternate setting.
+    pub async fn wait_connection(&mut self) {
+        self.read_ep.wait_enabled().await;
+    }
+}
+
+/// Explicit feedback endpoint.
+///
+/// The host adjusts the number of samples it sends per frame to the value written here, so the
+/// stream follows the device's audio clock. You can obtain a `Feedback` with [`Speaker::new`].
+pub struct Feedback<'d, D: Driver<'d>> {
+    write_ep: D::EndpointIn,
+}
+
+impl<'d, D: Driver<'d>> Feedback<'d, D> {
+    /// Writes the feedback value, in samples per frame as a 10.14 fixed point number.
+    ///
+    /// For example, a 48 kHz stream running exactly on time corresponds to `48 << 14`.
+    pub async fn write_feedback(&mut self, samples_per_frame: u32) -> Result<(), EndpointError> {
+        self.write_ep.write(&samples_per_frame.to_le_bytes()[..3]).await
+    }
+
+    /// Waits for the USB host to start streaming, by selecting the operational alternate setting.
+    pub async fn wait_connection(&mut self) {
+        self.write_ep.wait_enabled().await;
+    }
+}
+
+/// Monitor for the audio controls set by the host.
+///
+/// You can obtain a `ControlMonitor` with [`Speaker::new`].
+pub struct ControlMonitor<'d> {
+    shared: &'d ControlShared,
+}
+
+impl<'d> ControlMonitor<'d> {
+    /// Gets the sample rate selected by the host, in Hz.
+    pub fn sample_rate_hz(&self) -> u32 {
+        self.shared.sample_rate_hz.load(Ordering::Relaxed)
+    }
+
+    /// Gets the master mute state.
+    pub fn muted(&self) -> bool {
+        self.shared.mute.load(Ordering::Relaxed)
+    }
+
+    /// Gets the master volume in 1/256 dB, between -100 dB and 0 dB.
+    pub fn volume(&self) -> i16 {
+        self.shared.volume.load(Ordering::Relaxed)
+    }
+
+    /// Waits for the host to change the sample rate, mute or volume.
+    pub async fn changed(&self) {
+        core::future::poll_fn(|cx| {
+            if self.shared.changed.swap(false, Ordering::Relaxed) {
+                Poll::Ready(())
+            } else {
+                self.shared.waker.register(cx.waker());
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
@@ -0,0 +1,465 @@
+//! USB Video Class (UVC) implementation, aka webcam.
+//!
+//! Implements a camera streaming MJPEG frames of a single, fixed resolution over a bulk or
+//! isochronous endpoint. Each packet is a complete UVC payload, made of a 2 byte payload header
+//! followed by frame data. Frames come from a user-provided [`FrameSource`].
+
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::Poll;
+
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::control::{InResponse, OutResponse, Recipient, Request, RequestType};
+use crate::descriptor::{SynchronizationType, UsageType};
+use crate::driver::{Driver, Endpoint, EndpointError, EndpointIn, EndpointType};
+use crate::types::InterfaceNumber;
+use crate::{Builder, Handler};
+
+/// This should be used as `device_class` when building the `UsbDevice`.
+pub const USB_CLASS_VIDEO: u8 = 0x0e;
+
+const SC_VIDEOCONTROL: u8 = 0x01;
+const SC_VIDEOSTREAMING: u8 = 0x02;
+const SC_VIDEO_INTERFACE_COLLECTION: u8 = 0x03;
+const PC_PROTOCOL_UNDEFINED: u8 = 0x00;
+
+const CS_INTERFACE: u8 = 0x24;
+
+// Video control interface descriptor subtypes
+const VC_HEADER: u8 = 0x01;
+const VC_INPUT_TERMINAL: u8 = 0x02;
+const VC_OUTPUT_TERMINAL: u8 = 0x03;
+
+// Video streaming interface descriptor subtypes
+const VS_INPUT_HEADER: u8 = 0x01;
+const VS_FORMAT_MJPEG: u8 = 0x06;
+const VS_FRAME_MJPEG: u8 = 0x07;
+
+const ITT_CAMERA: u16 = 0x0201;
+const TT_STREAMING: u16 = 0x0101;
+
+// Entity IDs
+const CAMERA_TERMINAL_ID: u8 = 1;
+const OUTPUT_TERMINAL_ID: u8 = 2;
+
+// Class-specific requests
+const SET_CUR: u8 = 0x01;
+const GET_CUR: u8 = 0x81;
+const GET_MIN: u8 = 0x82;
+const GET_MAX: u8 = 0x83;
+const GET_RES: u8 = 0x84;
+const GET_LEN: u8 = 0x85;
+const GET_INFO: u8 = 0x86;
+const GET_DEF: u8 = 0x87;
+
+// Video streaming interface control selectors
+const VS_PROBE_CONTROL: u8 = 0x01;
+const VS_COMMIT_CONTROL: u8 = 0x02;
+
+/// Length of the UVC 1.1 video probe and commit controls.
+const PROBE_LEN: usize = 34;
+
+const HEADER_LEN: usize = 2;
+const HEADER_FID: u8 = 1 << 0;
+const HEADER_EOF: u8 = 1 << 1;
+const HEADER_EOH: u8 = 1 << 7;
+
+/// Transfer type of the video streaming endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StreamingTransfer {
+    /// Bulk endpoint: no bandwidth is reserved, frames use whatever the bus has left.
+    Bulk,
+    /// Isochronous endpoint, in alternate setting 1 of the streaming interface: the host reserves
+    /// one packet per frame (or microframe), and selects the setting when it starts streaming.
+    Isochronous,
+}
+
+/// Source of the frames streamed by [`UvcClass::run`], e.g. a camera sensor with a JPEG encoder.
+#[allow(async_fn_in_trait)]
+pub trait FrameSource {
+    /// Captures the next MJPEG frame into `buf`, and returns its length.
+    ///
+    /// This is called once the previous frame has been sent, so it sets the pace of the stream.
+    async fn next_frame(&mut self, buf: &mut [u8]) -> usize;
+}
+
+/// Configuration for the UVC class.
+pub struct Config {
+    /// Frame width in pixels.
+    pub width: u16,
+
+    /// Frame height in pixels.
+    pub height: u16,
+
+    /// Interval between frames, in 100 ns units. For example, 30 fps is `333_333`. Must not be 0.
+    pub frame_interval: u32,
+
+    /// Maximum size of a compressed frame in bytes.
+    pub max_frame_size: u32,
+
+    /// Transfer type of the streaming endpoint.
+    pub transfer: StreamingTransfer,
+
+    /// Max packet size of the streaming IN endpoint.
+    pub max_packet_size: u16,
+}
+
+/// Internal state for USB Video Class.
+pub struct State<'d> {
+    control: MaybeUninit<Control<'d>>,
+    shared: ControlShared,
+}
+
+impl<'d> Default for State<'d> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'d> State<'d> {
+    /// Create a new `State`.
+    pub const fn new() -> Self {
+        Self {
+            control: MaybeUninit::uninit(),
+            shared: ControlShared {
+                committed: AtomicBool::new(false),
+                waker: AtomicWaker::new(),
+            },
+        }
+    }
+}
+
+/// Shared data between Control and UvcClass
+struct ControlShared {
+    committed: AtomicBool,
+    waker: AtomicWaker,
+}
+
+struct Control<'d> {
+    vs_if: InterfaceNumber,
+    probe: [u8; PROBE_LEN],
+    shared: &'d ControlShared,
+}
+
+impl<'d> Handler for Control<'d> {
+    fn reset(&mut self) {
+        self.shared.committed.store(false, Ordering::Relaxed);
+    }
+
+    fn set_alternate_setting(&mut self, iface: InterfaceNumber, alternate_setting: u8) {
+        // Alternate setting 0 has no bandwidth reserved: the host stopped streaming, and will
+        // probe and commit again before restarting.
+        if iface == self.vs_if && alternate_setting == 0 {
+            debug!("uvc: streaming stopped");
+            self.shared.committed.store(false, Ordering::Relaxed);
+        }
+    }
+
+    fn control_out(&mut self, req: Request, data: &[u8]) -> Option<OutResponse> {
+        if (req.request_type, req.recipient, req.index)
+            != (RequestType::Class, Recipient::Interface, self.vs_if.0 as u16)
+        {
+            return None;
+        }
+
+        // Only one format and frame size is supported, so whatever the host asks for, the
+        // negotiated parameters stay the same. The host reads them back with GET_CUR.
+        match (req.request, (req.value >> 8) as u8) {
+            (SET_CUR, VS_PROBE_CONTROL) => Some(OutResponse::Accepted),
+            (SET_CUR, VS_COMMIT_CONTROL) if data.len() >= 26 => {
+                debug!("uvc: streaming parameters committed");
+                self.shared.committed.store(true, Ordering::Relaxed);
+                self.shared.waker.wake();
+                Some(OutResponse::Accepted)
+            }
+            _ => Some(OutResponse::Rejected),
+        }
+    }
+
+    fn control_in<'a>(&'a mut self, req: Request, buf: &'a mut [u8]) -> Option<InResponse<'a>> {
+        if (req.request_type, req.recipient, req.index)
+            != (RequestType::Class, Recipient::Interface, self.vs_if.0 as u16)
+        {
+            return None;
+        }
+
+        let selector = (req.value >> 8) as u8;
+        if selector != VS_PROBE_CONTROL && selector != VS_COMMIT_CONTROL {
+            return Some(InResponse::Rejected);
+        }
+
+        match req.request {
+            GET_CUR | GET_MIN | GET_MAX | GET_DEF => Some(InResponse::Accepted(&self.probe)),
+            GET_RES => {
+                buf[..PROBE_LEN].fill(0);
+                Some(InResponse::Accepted(&buf[..PROBE_LEN]))
+            }
+            GET_LEN => {
+                buf[..2].copy_from_slice(&(PROBE_LEN as u16).to_le_bytes());
+                Some(InResponse::Accepted(&buf[..2]))
+            }
+            GET_INFO => {
+                buf[0] = 0x03; // supports GET and SET
+                Some(InResponse::Accepted(&buf[..1]))
+            }
+            _ => Some(InResponse::Rejected),
+        }
+    }
+}
+
+/// USB Video Class camera, streaming MJPEG frames over a bulk or isochronous endpoint.
+pub struct UvcClass<'d, D: Driver<'d>> {
+    write_ep: D::EndpointIn,
+    control: &'d ControlShared,
+    fid: bool,
+}
+
+impl<'d, D: Driver<'d>> UvcClass<'d, D> {
+    /// Creates a new UvcClass with the provided UsbBus and configuration.
+    ///
+    /// The video function is added with an interface association descriptor, so the device should
+    /// be configured with `composite_with_iads`.
+    pub fn new(builder: &mut Builder<'d, D>, state: &'d mut State<'d>, config: Config) -> Self {
+        assert!(config.max_packet_size as usize > HEADER_LEN && config.max_packet_size <= 1024);
+        assert!(config.frame_interval != 0, "frame_interval must not be 0");
+        assert!(builder.control_buf_len() >= PROBE_LEN);
+
+        let mut func = builder.function(USB_CLASS_VIDEO, SC_VIDEO_INTERFACE_COLLECTION, PC_PROTOCOL_UNDEFINED);
+
+        // Video control interface
+        let mut iface = func.interface();
+        let vs_if = u8::from(iface.interface_number()) + 1;
+        let mut alt = iface.alt_setting(USB_CLASS_VIDEO, SC_VIDEOCONTROL, PC_PROTOCOL_UNDEFINED, None);
+
+        let vc_total_length: u16 = 13 + 18 + 9;
+        alt.descriptor(
+            CS_INTERFACE,
+            &[
+                VC_HEADER,
+                0x10,
+                0x01, // bcdUVC (1.10)
+                vc_total_length as u8,
+                (vc_total_length >> 8) as u8,
+                0x80,
+                0x8d,
+                0x5b,
+                0x00,  // dwClockFrequency (6 MHz, deprecated)
+                0x01,  // bInCollection
+                vs_if, // baInterfaceNr
+            ],
+        );
+        alt.descriptor(
+            CS_INTERFACE,
+            &[
+                VC_INPUT_TERMINAL,
+                CAMERA_TERMINAL_ID,
+                ITT_CAMERA as u8,
+                (ITT_CAMERA >> 8) as u8,
+                0x00, // bAssocTerminal
+                0x00, // iTerminal
+                0x00,
+                0x00, // wObjectiveFocalLengthMin
+                0x00,
+                0x00, // wObjectiveFocalLengthMax
+                0x00,
+                0x00, // wOcularFocalLength
+                0x03, // bControlSize
+                0x00,
+                0x00,
+                0x00, // bmControls
+            ],
+        );
+        alt.descriptor(
+            CS_INTERFACE,
+            &[
+                VC_OUTPUT_TERMINAL,
+                OUTPUT_TERMINAL_ID,
+                TT_STREAMING as u8,
+                (TT_STREAMING >> 8) as u8,
+                0x00, // bAssocTerminal
+                CAMERA_TERMINAL_ID,
+                0x00, // iTerminal
+            ],
+        );
+
+        // Video streaming interface
+        let mut iface = func.interface();
+        let vs_if = iface.interface_number();
+        let mut alt = iface.alt_setting(USB_CLASS_VIDEO, SC_VIDEOSTREAMING, PC_PROTOCOL_UNDEFINED, None);
+
+        // The input header needs the endpoint address, but comes before the endpoint descriptor.
+        let write_ep = match config.transfer {
+            StreamingTransfer::Bulk => alt.alloc_endpoint_in(EndpointType::Bulk, config.max_packet_size, 0),
+            StreamingTransfer::Isochronous => {
+                alt.alloc_endpoint_in(EndpointType::Isochronous, config.max_packet_size, 1)
+            }
+        };
+
+        let vs_total_length: u16 = 14 + 11 + 30;
+        alt.descriptor(
+            CS_INTERFACE,
+            &[
+                VS_INPUT_HEADER,
+                0x01, // bNumFormats
+                vs_total_length as u8,
+                (vs_total_length >> 8) as u8,
+                write_ep.info().addr.into(),
+                0x00,               // bmInfo
+                OUTPUT_TERMINAL_ID, // bTerminalLink
+                0x00,               // bStillCaptureMethod
+                0x00,               // bTriggerSupport
+                0x00,               // bTriggerUsage
+                0x01,               // bControlSize
+                0x00,               // bmaControls
+            ],
+        );
+        alt.descriptor(
+            CS_INTERFACE,
+            &[
+                VS_FORMAT_MJPEG,
+                0x01, // bFormatIndex
+                0x01, // bNumFrameDescriptors
+                0x01, // bmFlags: fixed size samples
+                0x01, // bDefaultFrameIndex
+                0x00, // bAspectRatioX
+                0x00, // bAspectRatioY
+                0x00, // bmInterlaceFlags
+                0x00, // bCopyProtect
+            ],
+        );
+
+        let bit_rate = config.max_frame_size as u64 * 8 * 10_000_000 / config.frame_interval as u64;
+        let bit_rate = bit_rate.min(u32::MAX as u64) as u32;
+        let mut frame = [0; 28];
+        frame[0] = VS_FRAME_MJPEG;
+        frame[1] = 0x01; // bFrameIndex
+        frame[2] = 0x00; // bmCapabilities
+        frame[3..5].copy_from_slice(&config.width.to_le_bytes());
+        frame[5..7].copy_from_slice(&config.height.to_le_bytes());
+        frame[7..11].copy_from_slice(&bit_rate.to_le_bytes()); // dwMinBitRate
+        frame[11..15].copy_from_slice(&bit_rate.to_le_bytes()); // dwMaxBitRate
+        frame[15..19].copy_from_slice(&config.max_frame_size.to_le_bytes());
+        frame[19..23].copy_from_slice(&config.frame_interval.to_le_bytes()); // dwDefaultFrameInterval
+        frame[23] = 0x01; // bFrameIntervalType: one discrete interval
+        frame[24..28].copy_from_slice(&config.frame_interval.to_le_bytes());
+        alt.descriptor(CS_INTERFACE, &frame);
+
+        match config.transfer {
+            StreamingTransfer::Bulk => alt.endpoint_descriptor(
+                write_ep.info(),
+                SynchronizationType::NoSynchronization,
+                UsageType::DataEndpoint,
+                &[],
+            ),
+            StreamingTransfer::Isochronous => {
+                // Shadowing ends the borrow of the zero-bandwidth alt setting.
+                let mut alt = iface.alt_setting(USB_CLASS_VIDEO, SC_VIDEOSTREAMING, PC_PROTOCOL_UNDEFINED, None);
+                alt.endpoint_descriptor(
+                    write_ep.info(),
+                    SynchronizationType::Asynchronous,
+                    UsageType::DataEndpoint,
+                    &[],
+                );
+            }
+        }
+
+        drop(func);
+
+        let mut probe = [0; PROBE_LEN];
+        probe[2] = 0x01; // bFormatIndex
+        probe[3] = 0x01; // bFrameIndex
+        probe[4..8].copy_from_slice(&config.frame_interval.to_le_bytes());
+        probe[18..22].copy_from_slice(&config.max_frame_size.to_le_bytes());
+        probe[22..26].copy_from_slice(&(config.max_packet_size as u32).to_le_bytes()); // dwMaxPayloadTransferSize
+        probe[26..30].copy_from_slice(&6_000_000u32.to_le_bytes()); // dwClockFrequency
+        probe[30] = 0x03; // bmFramingInfo: FID and EOF are used
+        probe[31] = 0x01; // bPreferedVersion
+        probe[32] = 0x01; // bMinVersion
+        probe[33] = 0x01; // bMaxVersion
+
+        let control = state.control.write(Control {
+            vs_if,
+            probe,
+            shared: &state.shared,
+        });
+        builder.handler(control);
+
+        UvcClass {
+            write_ep,
+            control: &state.shared,
+            fid: false,
+        }
+    }
+
+    /// Gets the maximum packet size in bytes.
+    pub fn max_packet_size(&self) -> u16 {
+        self.write_ep.info().max_packet_size
+    }
+
+    /// Waits for the USB host to enable this interface
+    pub async fn wait_connection(&mut self) {
+        self.write_ep.wait_enabled().await;
+    }
+
+    /// Waits for the host to commit the streaming parameters, meaning it is ready to receive frames.
+    pub async fn wait_streaming(&mut self) {
+        core::future::poll_fn(|cx| {
+            if self.control.committed.load(Ordering::Relaxed) {
+                Poll::Ready(())
+            } else {
+                self.control.waker.register(cx.waker());
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Streams frames from `source` to the host, forever.
+    ///
+    /// `buf` holds one frame while it is sent, so it should be `max_frame_size` bytes long.
+    /// Streaming pauses while the host isn't receiving, and resumes once it commits the
+    /// streaming parameters again.
+    pub async fn run<S: FrameSource>(&mut self, source: &mut S, buf: &mut [u8]) -> ! {
+        loop {
+            self.wait_connection().await;
+            self.wait_streaming().await;
+            info!("uvc: streaming");
+
+            while self.control.committed.load(Ordering::Relaxed) {
+                let len = source.next_frame(buf).await;
+                if let Err(e) = self.write_frame(&buf[..len]).await {
+                    match e {
+                        EndpointError::Disabled => break,
+                        EndpointError::BufferOverflow => warn!("uvc: frame packet too large"),
+                    }
+                }
+            }
+            info!("uvc: stopped");
+        }
+    }
+
+    /// Writes a complete MJPEG frame, split into payloads of one packet each.
+    ///
+    /// `frame` must not be larger than the configured `max_frame_size`.
+    pub async fn write_frame(&mut self, frame: &[u8]) -> Result<(), EndpointError> {
+        let mps = self.max_packet_size() as usize;
+        let mut packet = [0; 1024];
+        let packet = &mut packet[..mps];
+
+        let fid = if self.fid { HEADER_FID } else { 0 };
+        self.fid = !self.fid;
+
+        let mut chunks = frame.chunks(mps - HEADER_LEN).peekable();
+        while let Some(chunk) = chunks.next() {
+            let eof = if chunks.peek().is_none() { HEADER_EOF } else { 0 };
+            packet[0] = HEADER_LEN as u8;
+            packet[1] = HEADER_EOH | eof | fid;
+            packet[HEADER_LEN..][..chunk.len()].copy_from_slice(chunk);
+            self.write_ep.write(&packet[..HEADER_LEN + chunk.len()]).await?;
+        }
+
+        Ok(())
+    }
+}
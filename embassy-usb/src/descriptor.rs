@@ -377,7 +377,7 @@ impl<'a> BosWriter<'a> {
         }
     }
 
-    pub(crate) fn bos(&mut self) {
+    pub(crate) fn bos(&mut self, supports_lpm: bool) {
         if (self.writer.buf.len() - self.writer.position) < 5 {
             return;
         }
@@ -391,7 +391,8 @@ impl<'a> BosWriter<'a> {
             &[],
         );
 
-        self.capability(capability_type::USB_2_0_EXTENSION, &[0; 4]);
+        let attributes: u32 = if supports_lpm { 0x02 } else { 0x00 }; // bmAttributes: LPM
+        self.capability(capability_type::USB_2_0_EXTENSION, &attributes.to_le_bytes());
     }
 
     /// Writes capability descriptor to a BOS
@@ -62,6 +62,8 @@ pub enum RemoteWakeupError {
     InvalidState,
     /// The underlying driver doesn't support remote wakeup.
     Unsupported,
+    /// The driver did not get ready to signal the wakeup in time.
+    Timeout,
 }
 
 impl From<driver::Unsupported> for RemoteWakeupError {
@@ -70,6 +72,15 @@ impl From<driver::Unsupported> for RemoteWakeupError {
     }
 }
 
+impl From<driver::RemoteWakeupError> for RemoteWakeupError {
+    fn from(err: driver::RemoteWakeupError) -> Self {
+        match err {
+            driver::RemoteWakeupError::Timeout => RemoteWakeupError::Timeout,
+            _ => RemoteWakeupError::Unsupported,
+        }
+    }
+}
+
 /// The bConfiguration value for the not configured state.
 pub const CONFIGURATION_NONE: u8 = 0;
 
@@ -99,6 +110,9 @@ pub trait Handler {
     fn configured(&mut self, _configured: bool) {}
 
     /// Called when the bus has entered or exited the suspend state.
+    ///
+    /// This is also called for the L1 (sleep) state of link power management, which is typically
+    /// left within microseconds.
     fn suspended(&mut self, _suspended: bool) {}
 
     /// Called when remote wakeup feature is enabled or disabled.
@@ -199,6 +213,9 @@ struct Inner<'d, D: Driver<'d>> {
     suspended: bool,
     remote_wakeup_enabled: bool,
     self_powered: bool,
+    /// Whether the link is in the L1 (sleep) state. `suspended` is also set.
+    l1_sleep: bool,
+    l1_accepted: bool,
 
     /// Our device address, or 0 if none.
     address: u8,
@@ -244,6 +261,8 @@ impl<'d, D: Driver<'d>> UsbDevice<'d, D> {
                 suspended: false,
                 remote_wakeup_enabled: false,
                 self_powered: false,
+                l1_sleep: false,
+                l1_accepted: config.supports_lpm,
                 address: 0,
                 set_address_pending: false,
                 interfaces,
@@ -301,6 +320,7 @@ impl<'d, D: Driver<'d>> UsbDevice<'d, D> {
             self.inner.device_state = UsbDeviceState::Disabled;
             self.inner.suspended = false;
             self.inner.remote_wakeup_enabled = false;
+            self.inner.l1_sleep = false;
 
             for h in &mut self.inner.handlers {
                 h.enabled(false);
@@ -321,15 +341,17 @@ impl<'d, D: Driver<'d>> UsbDevice<'d, D> {
     /// Initiates a device remote wakeup on the USB bus.
     ///
     /// If the bus is not suspended or remote wakeup is not enabled, an error
-    /// will be returned.
+    /// will be returned. In the L1 (sleep) state, remote wakeup is instead allowed
+    /// by the host in each L1 request, which the driver checks.
     ///
     /// This future may leave the bus in an inconsistent state if dropped.
     /// After dropping the future, [`UsbDevice::disable()`] should be called
     /// before calling any other `UsbDevice` methods to fully reset the peripheral.
     pub async fn remote_wakeup(&mut self) -> Result<(), RemoteWakeupError> {
-        if self.inner.suspended && self.inner.remote_wakeup_enabled {
+        if self.inner.suspended && (self.inner.remote_wakeup_enabled || self.inner.l1_sleep) {
             self.inner.bus.remote_wakeup().await?;
             self.inner.suspended = false;
+            self.inner.l1_sleep = false;
 
             for h in &mut self.inner.handlers {
                 h.suspended(false);
@@ -341,6 +363,19 @@ impl<'d, D: Driver<'d>> UsbDevice<'d, D> {
         }
     }
 
+    /// Sets whether L1 (sleep) requests from the host are accepted.
+    ///
+    /// This only has an effect if [`Config::supports_lpm`] is set, in which case requests are
+    /// accepted by default. Rejecting them keeps the link active, e.g. during latency-sensitive
+    /// transfers.
+    pub fn set_l1_accepted(&mut self, accepted: bool) -> Result<(), driver::Unsupported> {
+        self.inner.l1_accepted = accepted;
+        if self.inner.config.supports_lpm {
+            self.inner.bus.set_l1_accepted(accepted)?;
+        }
+        Ok(())
+    }
+
     async fn handle_control(&mut self, req: [u8; 8]) {
         let req = Request::parse(&req);
 
@@ -448,6 +483,7 @@ impl<'d, D: Driver<'d>> Inner<'d, D> {
                 self.device_state = UsbDeviceState::Default;
                 self.suspended = false;
                 self.remote_wakeup_enabled = false;
+                self.l1_sleep = false;
                 self.address = 0;
 
                 for h in &mut self.handlers {
@@ -465,6 +501,7 @@ impl<'d, D: Driver<'d>> Inner<'d, D> {
             Event::Resume => {
                 trace!("usb: resume");
                 self.suspended = false;
+                self.l1_sleep = false;
                 for h in &mut self.handlers {
                     h.suspended(false);
                 }
@@ -476,11 +513,23 @@ impl<'d, D: Driver<'d>> Inner<'d, D> {
                     h.suspended(true);
                 }
             }
+            Event::L1Sleep => {
+                trace!("usb: l1 sleep");
+                self.suspended = true;
+                self.l1_sleep = true;
+                for h in &mut self.handlers {
+                    h.suspended(true);
+                }
+            }
             Event::PowerDetected => {
                 trace!("usb: power detected");
                 self.bus.enable().await;
                 self.device_state = UsbDeviceState::Default;
 
+                if self.config.supports_lpm && self.bus.set_l1_accepted(self.l1_accepted).is_err() {
+                    warn!("usb: driver doesn't support link power management");
+                }
+
                 for h in &mut self.handlers {
                     h.enabled(true);
                 }
@@ -494,6 +543,7 @@ impl<'d, D: Driver<'d>> Inner<'d, D> {
                     h.enabled(false);
                 }
             }
+            _ => {}
         }
     }
 
@@ -0,0 +1,96 @@
+//! This example shows how to use USB (Universal Serial Bus) in the RP2040 chip.
+//!
+//! This creates a USB drive backed by the upper 1MB of the on-board flash.
+//! The host will ask to format it the first time it is plugged in.
+
+#![no_std]
+#![no_main]
+
+use defmt::info;
+use embassy_embedded_hal::flash::partition::Partition;
+use embassy_executor::Spawner;
+use embassy_futures::join::join;
+use embassy_rp::bind_interrupts;
+use embassy_rp::flash::{Async, Flash, ERASE_SIZE};
+use embassy_rp::peripherals::USB;
+use embassy_rp::usb::{Driver, InterruptHandler};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_usb::class::msc::{self, MscClass, State};
+use embassy_usb::{Builder, Config};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    USBCTRL_IRQ => InterruptHandler<USB>;
+});
+
+const FLASH_SIZE: usize = 2 * 1024 * 1024;
+const DISK_OFFSET: u32 = 0x100000;
+const DISK_SIZE: u32 = 0x100000;
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    info!("Hello world!");
+
+    let p = embassy_rp::init(Default::default());
+
+    let flash = Mutex::<NoopRawMutex, _>::new(Flash::<_, Async, FLASH_SIZE>::new(p.FLASH, p.DMA_CH0));
+    let disk = Partition::new(&flash, DISK_OFFSET, DISK_SIZE);
+
+    // Create the driver, from the HAL.
+    let driver = Driver::new(p.USB, Irqs);
+
+    // Create embassy-usb Config
+    let mut config = Config::new(0xc0de, 0xcafe);
+    config.manufacturer = Some("Embassy");
+    config.product = Some("USB-MSC example");
+    config.serial_number = Some("12345678");
+    config.max_power = 100;
+    config.max_packet_size_0 = 64;
+
+    // Create embassy-usb DeviceBuilder using the driver and config.
+    // It needs some buffers for building the descriptors.
+    let mut config_descriptor = [0; 256];
+    let mut bos_descriptor = [0; 256];
+    let mut control_buf = [0; 64];
+
+    let mut state = State::new();
+    // Scratch space holding one flash sector while it is being rewritten.
+    let mut sector_buf = [0; ERASE_SIZE];
+
+    let mut builder = Builder::new(
+        driver,
+        config,
+        &mut config_descriptor,
+        &mut bos_descriptor,
+        &mut [], // no msos descriptors
+        &mut control_buf,
+    );
+
+    // Create classes on the builder.
+    let mut class = MscClass::new(
+        &mut builder,
+        &mut state,
+        disk,
+        &mut sector_buf,
+        msc::Config {
+            vendor: "Embassy",
+            product: "Flash disk",
+            revision: "0.1",
+            max_packet_size: 64,
+        },
+    );
+
+    // Build the builder.
+    let mut usb = builder.build();
+
+    // Run the USB device.
+    let usb_fut = usb.run();
+
+    // Serve the drive to the host.
+    let msc_fut = class.run();
+
+    // Run everything concurrently.
+    // If we had made everything `'static` above instead, we could do this using separate tasks instead.
+    join(usb_fut, msc_fut).await;
+}
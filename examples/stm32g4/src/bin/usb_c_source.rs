@@ -0,0 +1,86 @@
+#![no_std]
+#![no_main]
+
+use defmt::info;
+use embassy_executor::Spawner;
+use embassy_stm32::gpio::{Level, Output, Speed};
+use embassy_stm32::ucpd::{self, CcPhy, CcPull, CcSel, Ucpd};
+use embassy_stm32::{bind_interrupts, peripherals};
+use embassy_time::{with_timeout, Duration};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    UCPD1 => ucpd::InterruptHandler<peripherals::UCPD1>;
+});
+
+// Waits until a sink has been attached for tCCDebounce (100..200ms) and returns its CC line.
+async fn wait_sink_attached<T: ucpd::Instance>(cc_phy: &mut CcPhy<'_, T>) -> CcSel {
+    loop {
+        let Some(cc_sel) = cc_phy.sink_attached() else {
+            // Detached, wait until attached by monitoring the CC lines.
+            cc_phy.wait_for_vstate_change().await;
+            continue;
+        };
+
+        if with_timeout(Duration::from_millis(100), cc_phy.wait_for_vstate_change())
+            .await
+            .is_ok()
+        {
+            // State has changed, restart detection procedure.
+            continue;
+        }
+
+        return cc_sel;
+    }
+}
+
+// Waits until the sink has been gone for tPDDebounce (10..20ms).
+async fn wait_sink_detached<T: ucpd::Instance>(cc_phy: &mut CcPhy<'_, T>) {
+    loop {
+        if cc_phy.sink_attached().is_some() {
+            cc_phy.wait_for_vstate_change().await;
+            continue;
+        }
+
+        if with_timeout(Duration::from_millis(10), cc_phy.wait_for_vstate_change())
+            .await
+            .is_ok()
+        {
+            continue;
+        }
+
+        return;
+    }
+}
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_stm32::init(Default::default());
+
+    info!("Hello World!");
+
+    // Enable pin of the load switch feeding 5V to the VBUS pin of the receptacle.
+    let mut vbus_en = Output::new(p.PA8, Level::Low, Speed::Low);
+
+    let mut ucpd = Ucpd::new(p.UCPD1, Irqs {}, p.PB6, p.PB4, Default::default());
+
+    // Advertise default USB power (500mA/900mA). Use `Source1_5A`/`Source3_0A` if the supply can
+    // deliver more.
+    ucpd.cc_phy().set_pull(CcPull::SourceDefaultUsb);
+
+    loop {
+        info!("Waiting for a sink...");
+        let cc_sel = wait_sink_attached(ucpd.cc_phy()).await;
+        info!("Sink attached on {}, enabling VBUS", cc_sel);
+
+        // VBUS must be applied within tVBUSON (275ms) of the attach being debounced. The USB host
+        // driver can be started from here, once VBUS is up.
+        vbus_en.set_high();
+
+        wait_sink_detached(ucpd.cc_phy()).await;
+        info!("Sink detached, disabling VBUS");
+
+        // VBUS must be removed within tVBUSOFF (650ms) of the detach.
+        vbus_en.set_low();
+    }
+}